@@ -0,0 +1,279 @@
+// Reading the databases out of triage collections (KAPE, Velociraptor, ...) packed as zip or
+// tar(.gz) archives, without unpacking them first: only the directories of the archive holding
+// a Windows Search database are extracted to a temporary directory, so that the transaction
+// logs, WAL and gather databases sitting next to the database come along.
+
+use crate::error::SidrError;
+use crate::report::DbSource;
+use flate2::read::GzDecoder;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+const GZIP_SIGNATURE: &[u8] = &[0x1F, 0x8B];
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_BLOCK_SIZE: u64 = 512;
+// the files of the input directories which are probed for an archive
+const ARCHIVE_EXTENSIONS: [&str; 4] = ["zip", "tar", "tgz", "gz"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn is_tar(r: impl Read) -> bool {
+    let mut header = Vec::new();
+    r.take(TAR_BLOCK_SIZE).read_to_end(&mut header).is_ok()
+        && header.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC)
+}
+
+// Whether a file found in the input directories may be an archive. Their other files
+// (documents, jars, ... which are zip files too) are not opened.
+pub fn has_archive_extension(p: &Path) -> bool {
+    p.extension().is_some_and(|e| {
+        ARCHIVE_EXTENSIONS
+            .iter()
+            .any(|ext| e.eq_ignore_ascii_case(ext))
+    })
+}
+
+// Detects zip and tar(.gz) archives from their header.
+pub fn archive_kind(p: &Path) -> Option<ArchiveKind> {
+    let mut f = File::open(p).ok()?;
+    let mut signature = [0u8; 4];
+    f.read_exact(&mut signature).ok()?;
+    if signature == ZIP_SIGNATURE {
+        Some(ArchiveKind::Zip)
+    } else if signature.starts_with(GZIP_SIGNATURE) {
+        is_tar(GzDecoder::new(File::open(p).ok()?)).then_some(ArchiveKind::TarGz)
+    } else {
+        is_tar(File::open(p).ok()?).then_some(ArchiveKind::Tar)
+    }
+}
+
+// "C/ProgramData/.../Windows/Windows.edb" -> ("C/ProgramData/.../Windows", "Windows.edb")
+fn split_entry_name(name: &str) -> (&str, &str) {
+    name.rsplit_once(['/', '\\']).unwrap_or(("", name))
+}
+
+// Where an entry of the archive is extracted in dir, None for names escaping it.
+fn entry_dest(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut dest = dir.to_path_buf();
+    let mut depth = 0;
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            // drive letters of the collected paths
+            part => {
+                dest.push(part.replace(':', "_"));
+                depth += 1;
+            }
+        }
+    }
+    (depth > 0).then_some(dest)
+}
+
+fn open_tar(p: &Path, kind: ArchiveKind) -> Result<tar::Archive<Box<dyn Read>>, SidrError> {
+    let f = File::open(p)?;
+    let r: Box<dyn Read> = match kind {
+        ArchiveKind::TarGz => Box::new(GzDecoder::new(f)),
+        _ => Box::new(f),
+    };
+    Ok(tar::Archive::new(r))
+}
+
+// Names of the files of the archive.
+fn entry_names(p: &Path, kind: ArchiveKind) -> Result<Vec<String>, SidrError> {
+    let mut names = Vec::new();
+    if kind == ArchiveKind::Zip {
+        let mut zip = zip::ZipArchive::new(File::open(p)?).map_err(zip_error)?;
+        for i in 0..zip.len() {
+            let entry = zip.by_index(i).map_err(zip_error)?;
+            if entry.is_file() {
+                names.push(entry.name().to_string());
+            }
+        }
+    } else {
+        for entry in open_tar(p, kind)?.entries()? {
+            let entry = entry?;
+            if entry.header().entry_type().is_file() {
+                names.push(entry.path()?.to_string_lossy().into_owned());
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn zip_error(e: zip::result::ZipError) -> SidrError {
+    match e {
+        zip::result::ZipError::Io(e) => SidrError::Io(e),
+        e => SidrError::Corrupt(format!("Invalid zip archive: {e}")),
+    }
+}
+
+// Extracts into dir the files of the directories of the archive holding a database
+// (a file for which is_db is true). Returns the extracted files with their path in the archive.
+pub fn extract_archive_dbs(
+    archive: &Path,
+    kind: ArchiveKind,
+    dir: &Path,
+    is_db: &dyn Fn(&Path) -> bool,
+) -> Result<HashMap<PathBuf, DbSource>, SidrError> {
+    let db_dirs: HashSet<String> = entry_names(archive, kind)?
+        .iter()
+        .map(|name| split_entry_name(name))
+        .filter(|(_, file)| is_db(Path::new(file)))
+        .map(|(dir, _)| dir.to_string())
+        .collect();
+    let mut files = HashMap::new();
+    if db_dirs.is_empty() {
+        return Ok(files);
+    }
+
+    let mut extract = |name: String, r: &mut dyn Read| -> Result<(), SidrError> {
+        if !db_dirs.contains(split_entry_name(&name).0) {
+            return Ok(());
+        }
+        let Some(dest) = entry_dest(dir, &name) else {
+            eprintln!("Skipping '{name}' of {}", archive.to_string_lossy());
+            return Ok(());
+        };
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::io::copy(r, &mut File::create(&dest)?)?;
+        files.insert(
+            dest,
+            DbSource::Archive {
+                archive: archive.to_path_buf(),
+                path: name,
+            },
+        );
+        Ok(())
+    };
+    if kind == ArchiveKind::Zip {
+        let mut zip = zip::ZipArchive::new(File::open(archive)?).map_err(zip_error)?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(zip_error)?;
+            if entry.is_file() {
+                extract(entry.name().to_string(), &mut entry)?;
+            }
+        }
+    } else {
+        for entry in open_tar(archive, kind)?.entries()? {
+            let mut entry = entry?;
+            if entry.header().entry_type().is_file() {
+                extract(entry.path()?.to_string_lossy().into_owned(), &mut entry)?;
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const WINDOWS_DIR: &str = "C/ProgramData/Microsoft/Search/Data/Applications/Windows";
+
+    fn is_db(p: &Path) -> bool {
+        p.file_name().is_some_and(|n| n == "Windows.edb")
+    }
+
+    fn check_extracted(archive: &Path, kind: ArchiveKind) {
+        assert_eq!(archive_kind(archive), Some(kind));
+        let dir = tempdir::TempDir::new("sidr_extract").unwrap();
+        let files = extract_archive_dbs(archive, kind, dir.path(), &is_db).unwrap();
+        let mut names: Vec<String> = files
+            .values()
+            .map(|s| match s {
+                DbSource::Archive { path, .. } => path.clone(),
+                _ => panic!("{s:?}"),
+            })
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                format!("{WINDOWS_DIR}/MSS.log"),
+                format!("{WINDOWS_DIR}/Windows.edb")
+            ]
+        );
+        let edb = dir.path().join(WINDOWS_DIR).join("Windows.edb");
+        assert_eq!(std::fs::read(&edb).unwrap(), b"edb");
+        assert!(files.contains_key(&edb));
+    }
+
+    const ENTRIES: [(&str, &[u8]); 4] = [
+        ("Windows.edb", b"edb"),
+        ("MSS.log", b"log"),
+        ("Projects/SystemIndex/file.ci", b"ci"),
+        ("../../readme.txt", b"txt"),
+    ];
+
+    #[test]
+    fn test_extract_zip() {
+        let dir = tempdir::TempDir::new("sidr_zip").unwrap();
+        let path = dir.path().join("collection.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (name, data) in ENTRIES {
+            zip.start_file(format!("{WINDOWS_DIR}/{name}"), Default::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+        check_extracted(&path, ArchiveKind::Zip);
+    }
+
+    #[test]
+    fn test_extract_tar_gz() {
+        let dir = tempdir::TempDir::new("sidr_tar").unwrap();
+        let path = dir.path().join("collection.tar.gz");
+        let gz = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        for (name, data) in ENTRIES.iter().filter(|(name, _)| !name.contains("..")) {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, format!("{WINDOWS_DIR}/{name}"), *data)
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+        check_extracted(&path, ArchiveKind::TarGz);
+    }
+
+    #[test]
+    fn test_has_archive_extension() {
+        assert!(has_archive_extension(Path::new("collection.zip")));
+        assert!(has_archive_extension(Path::new("collection.tar.gz")));
+        assert!(has_archive_extension(Path::new("HOST42.TGZ")));
+        assert!(!has_archive_extension(Path::new("report.docx")));
+        assert!(!has_archive_extension(Path::new("lib.jar")));
+        assert!(!has_archive_extension(Path::new("Windows.db")));
+    }
+
+    #[test]
+    fn test_entry_dest() {
+        let dir = Path::new("tmp");
+        assert_eq!(
+            entry_dest(dir, "uploads/auto/C:/Windows.db"),
+            Some(PathBuf::from("tmp/uploads/auto/C_/Windows.db"))
+        );
+        assert_eq!(
+            entry_dest(dir, ".\\C\\Windows.db"),
+            Some(PathBuf::from("tmp/C/Windows.db"))
+        );
+        assert_eq!(entry_dest(dir, "C/../../Windows.db"), None);
+        assert_eq!(entry_dest(dir, "/"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use simple_error::SimpleError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Debug)]
+pub enum SidrError {
+    // reading the databases or writing the reports
+    Io(std::io::Error),
+    Sqlite(sqlite::Error),
+    // errors returned by ese_parser_lib
+    Ese(String),
+    // invalid report configuration or command line
+    Cfg(String),
+    MissingColumn { column: String, table: String },
+    // an input holding no database to process
+    Input(String),
+    // the database content can't be interpreted
+    Corrupt(String),
+    // a panic while processing a database
+    Panic(String),
+}
+
+impl Display for SidrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::Sqlite(e) => write!(f, "SQLite error: {e}"),
+            Self::Ese(e) => write!(f, "ESE error: {e}"),
+            Self::Cfg(e) => write!(f, "Configuration error: {e}"),
+            Self::MissingColumn { column, table } => {
+                write!(f, "Could not find '{column}' column in '{table}' table")
+            }
+            Self::Input(e) => write!(f, "Invalid input: {e}"),
+            Self::Corrupt(e) => write!(f, "Malformed database: {e}"),
+            Self::Panic(e) => write!(f, "Unexpected failure: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SidrError {}
+
+impl From<std::io::Error> for SidrError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<sqlite::Error> for SidrError {
+    fn from(e: sqlite::Error) -> Self {
+        Self::Sqlite(e)
+    }
+}
+
+// writing the parquet reports
+impl From<parquet::errors::ParquetError> for SidrError {
+    fn from(e: parquet::errors::ParquetError) -> Self {
+        Self::Io(std::io::Error::other(e))
+    }
+}
+
+// ese_parser_lib reports its errors as SimpleError
+impl From<SimpleError> for SidrError {
+    fn from(e: SimpleError) -> Self {
+        Self::Ese(e.to_string())
+    }
+}
+
+#[test]
+fn test_sidr_error() {
+    let e: SidrError = std::io::Error::new(std::io::ErrorKind::NotFound, "gone").into();
+    assert_eq!(e.to_string(), "I/O error: gone");
+    let e: SidrError = SimpleError::new("bad page").into();
+    assert_eq!(e.to_string(), "ESE error: bad page");
+    let e = SidrError::MissingColumn {
+        column: "System_ItemName".to_string(),
+        table: "SystemIndex_PropertyStore".to_string(),
+    };
+    assert_eq!(
+        e.to_string(),
+        "Could not find 'System_ItemName' column in 'SystemIndex_PropertyStore' table"
+    );
+}
@@ -1,18 +1,20 @@
-extern crate exitcode;
-use std::process;
-
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
-use simple_error::SimpleError;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
 use std::path::Path;
 
+use crate::error::SidrError;
+use crate::recover::*;
 use crate::report::*;
 use crate::shared::*;
 use crate::utils::*;
 
 use ese_parser_lib::ese_parser::EseParser;
 use ese_parser_lib::ese_trait::*;
+use ese_parser_lib::parser::jet::DbState;
 use std::io::Write;
+use wsa_lib::{do_reports, ColumnPair, ConstrainedField, EseReader, FieldReader, ReportsCfg};
 
 const CACHE_SIZE_ENTRIES: usize = 10;
 const ESENTUTL_MSG: &str = "Use esentutl for recovery (/r) and repair (/p).\nNote that esentutl must be run from a version of Windows that is equal to or newer than the one that generated the database.";
@@ -48,7 +50,7 @@ fn get_column<T: FromBytes>(
     jdb: &dyn EseDb,
     table: u64,
     column: &ColumnInfo,
-) -> Result<Option<T>, SimpleError> {
+) -> Result<Option<T>, SidrError> {
     match jdb.get_column(table, column.id)? {
         Some(v) => Ok(Some(T::from_bytes(&v))),
         None => Ok(None),
@@ -59,10 +61,10 @@ pub fn ese_get_hostname(
     jdb: &dyn EseDb,
     table_id: u64,
     columns: &[ColumnInfo],
-) -> Result<String, SimpleError> {
+) -> Result<String, SidrError> {
     if !jdb.move_row(table_id, ESE_MoveLast)? {
         // empty table
-        return Err(SimpleError::new(format!("Empty table {table_id}")));
+        return Err(SidrError::Corrupt(format!("Empty table {table_id}")));
     }
     let _rollback = DropMe::new(|| {
         let _ = jdb.move_row(table_id, ESE_MoveFirst);
@@ -70,11 +72,17 @@ pub fn ese_get_hostname(
     let sys_comp_name = columns
         .iter()
         .find(|i| column_string_part(&i.name) == "System_ComputerName")
-        .ok_or_else(|| SimpleError::new("Can't find field 'System_ComputerName'".to_string()))?;
+        .ok_or_else(|| SidrError::MissingColumn {
+            column: "System_ComputerName".to_string(),
+            table: "SystemIndex_PropertyStore".to_string(),
+        })?;
     let sys_item_type = columns
         .iter()
         .find(|i| column_string_part(&i.name) == "System_ItemType")
-        .ok_or_else(|| SimpleError::new("Can't find field 'System_ItemType'".to_string()))?;
+        .ok_or_else(|| SidrError::MissingColumn {
+            column: "System_ItemType".to_string(),
+            table: "SystemIndex_PropertyStore".to_string(),
+        })?;
     loop {
         if let Some(v) = jdb.get_column(table_id, sys_comp_name.id)? {
             // ASDF-5849
@@ -95,7 +103,7 @@ pub fn ese_get_hostname(
             break;
         }
     }
-    Err(SimpleError::new(
+    Err(SidrError::Corrupt(
         "Empty field System_ComputerName".to_string(),
     ))
 }
@@ -103,13 +111,14 @@ pub fn ese_get_hostname(
 pub fn ese_generate_report(
     f: &Path,
     report_prod: &ReportProducer,
+    opts: &ParseOptions,
     status_logger: &mut Box<dyn Write>,
-) -> Result<(), SimpleError> {
-    writeln!(status_logger, "Processing ESE db: {}", &f.to_string_lossy())
-        .map_err(|e| SimpleError::new(format!("{e}")))?;
+) -> Result<(), SidrError> {
+    writeln!(status_logger, "Processing ESE db: {}", &f.to_string_lossy())?;
     let jdb = Box::new(
-        EseParser::load_from_path(CACHE_SIZE_ENTRIES, f)
-            .unwrap_or_else(|_| panic!("\nError opening ESE database.\n{ESENTUTL_MSG}\n")),
+        EseParser::load_from_path(CACHE_SIZE_ENTRIES, f).map_err(|e| {
+            SidrError::Ese(format!("Error opening ESE database: {e}\n{ESENTUTL_MSG}"))
+        })?,
     );
 
     let edb_database_state = jdb.get_database_state();
@@ -118,42 +127,47 @@ pub fn ese_generate_report(
     let cols = jdb.get_columns(t)?;
     if !jdb.move_row(table_id, ESE_MoveFirst)? {
         // empty table
-        return Err(SimpleError::new(format!("Empty table {t}")));
-    }
-    //let gather_table_fields = dump_file_gather_ese(f)?;
-
-    // prepare to query only selected columns
-    let sel_cols = prepare_selected_cols(
-        cols,
-        &vec![
-            "System_ComputerName",
-            "WorkID",
-            // File Report
-            "System_ItemPathDisplay",
-            "System_DateModified",
-            "System_DateCreated",
-            "System_DateAccessed",
-            "System_Size",
-            "System_FileOwner",
-            "System_Search_AutoSummary",
-            "System_Search_GatherTime",
-            "System_ItemType",
-            // IE/Edge History Report
-            "System_ItemUrl",
-            "System_Link_TargetUrl",
-            "System_ItemDate",
-            "System_Title",
-            "System_Link_DateVisited",
-            // Activity History Report
-            "System_ItemNameDisplay",
-            "System_ActivityHistory_StartTime",
-            "System_ActivityHistory_EndTime",
-            "System_Activity_AppDisplayName",
-            "System_ActivityHistory_AppId",
-            "System_Activity_DisplayText",
-            "System_Activity_ContentUri",
-        ],
-    );
+        return Err(SidrError::Corrupt(format!("Empty table {t}")));
+    }
+
+    // prepare to query all or only selected columns
+    let sel_cols = if opts.all_properties {
+        cols
+    } else {
+        prepare_selected_cols(
+            cols,
+            &vec![
+                "System_ComputerName",
+                "WorkID",
+                // File Report
+                "System_ItemPathDisplay",
+                "System_DateModified",
+                "System_DateCreated",
+                "System_DateAccessed",
+                "System_Size",
+                "System_FileOwner",
+                "System_Search_AutoSummary",
+                "System_Search_GatherTime",
+                "System_ItemType",
+                // IE/Edge History Report
+                "System_ItemUrl",
+                "System_Link_TargetUrl",
+                "System_ItemDate",
+                "System_Title",
+                "System_Link_DateVisited",
+                // Activity History Report
+                "System_ItemNameDisplay",
+                "System_ActivityHistory_StartTime",
+                "System_ActivityHistory_EndTime",
+                "System_Activity_AppDisplayName",
+                "System_ActivityHistory_AppId",
+                "System_Activity_DisplayText",
+                "System_Activity_ContentUri",
+            ],
+        )
+    };
+    let col_types: HashMap<String, u32> =
+        sel_cols.iter().map(|c| (c.name.clone(), c.typ)).collect();
 
     // get System_ComputerName value
     let recovered_hostname = match ese_get_hostname(&*jdb, table_id, &sel_cols) {
@@ -163,16 +177,20 @@ pub fn ese_generate_report(
             "Unknown".to_string()
         }
     };
+    let db_sid = sid_from_db_path(f);
+    let recovered_hostname = report_host_name(recovered_hostname, db_sid.as_deref());
 
-    let (mut file_rep, mut ie_rep, mut act_rep) = init_reports(
+    let mut reports = init_reports(
         f,
         report_prod,
         &recovered_hostname,
         status_logger,
         Some(edb_database_state),
+        "",
     )?;
 
     let mut h = HashMap::new();
+    let mut work_ids = HashSet::new();
     loop {
         let mut workId: u32 = 0;
         for c in &sel_cols {
@@ -198,21 +216,272 @@ pub fn ese_generate_report(
                 }
             }
         }
-        let ie_history = ese_IE_history_record(&mut *ie_rep, workId, &h);
-        let act_history = ese_activity_history_record(&mut *act_rep, workId, &h);
-        if !ie_history && !act_history {
-            ese_dump_file_record(&mut *file_rep, workId, &h);
-        }
+        ese_write_record(
+            &mut reports,
+            workId,
+            &h,
+            &col_types,
+            opts,
+            false,
+            db_sid.as_deref(),
+        );
         h.clear();
+        work_ids.insert(workId);
 
         if !jdb.move_row(table_id, ESE_MoveNext)? {
             break;
         }
     }
+    if opts.recover_deleted {
+        let cols = jdb.get_columns(t)?;
+        ese_write_recovered_records(f, t, &cols, &work_ids, &mut reports, &col_types, opts)?;
+    }
+    finish_reports(&mut reports, opts)?;
+    if let Err(e) = ese_generate_gather_report(
+        &*jdb,
+        f,
+        report_prod,
+        &recovered_hostname,
+        &work_ids,
+        status_logger,
+        edb_database_state,
+    ) {
+        eprintln!("Gather History Report skipped: {e}");
+    }
+    warn_if_dirty(report_prod, edb_database_state)?;
+    Ok(())
+}
+
+// Writes a PropertyStore record to the report it belongs to.
+fn ese_write_record(
+    reports: &mut Reports,
+    workId: u32,
+    h: &HashMap<String, Vec<u8>>,
+    col_types: &HashMap<String, u32>,
+    opts: &ParseOptions,
+    recovered: bool,
+    db_sid: Option<&str>,
+) {
+    let (file_rep, ie_rep, act_rep) = reports;
+    let kind = classify_record(&mut EseRecord::new(h));
+    let user_sid = record_user_sid(&mut EseRecord::new(h), db_sid);
+    let user_name = record_user_name(
+        &mut EseRecord::new(h),
+        user_sid.as_deref(),
+        &opts.user_names,
+    );
+    let r: &mut dyn Report = match kind {
+        RecordKind::InternetHistory => &mut **ie_rep,
+        RecordKind::ActivityHistory => &mut **act_rep,
+        RecordKind::File => &mut **file_rep,
+    };
+    if opts.all_properties {
+        ese_dump_all_properties(r, workId, h, col_types);
+    } else {
+        match kind {
+            RecordKind::InternetHistory => ese_IE_history_record(r, workId, h),
+            RecordKind::ActivityHistory => ese_activity_history_record(r, workId, h),
+            RecordKind::File => ese_dump_file_record(r, workId, h),
+        }
+    }
+    if opts.recover_deleted {
+        r.insert_bool("Recovered", recovered);
+    }
+    if let Some(sid) = user_sid {
+        r.insert_str_val("UserSID", sid);
+    }
+    if let Some(name) = user_name {
+        r.insert_str_val("UserName", name);
+    }
+}
+
+// Records carved from the table pages which are deleted or no longer reachable from the B-tree.
+fn ese_write_recovered_records(
+    f: &Path,
+    t: &str,
+    cols: &[ColumnInfo],
+    work_ids: &HashSet<u32>,
+    reports: &mut Reports,
+    col_types: &HashMap<String, u32>,
+    opts: &ParseOptions,
+) -> Result<(), SidrError> {
+    let db_sid = sid_from_db_path(f);
+    for rec in ese_recover_records(f, t, cols)? {
+        let workId = match rec.values.get("WorkID") {
+            Some(v) if v.len() == 4 => u32::from_bytes(v),
+            _ => continue,
+        };
+        if !rec.deleted && work_ids.contains(&workId) {
+            // live record
+            continue;
+        }
+        let h: HashMap<String, Vec<u8>> = rec
+            .values
+            .into_iter()
+            .filter(|(name, _)| name != "WorkID" && col_types.contains_key(name))
+            .collect();
+        ese_write_record(
+            reports,
+            workId,
+            &h,
+            col_types,
+            opts,
+            true,
+            db_sid.as_deref(),
+        );
+    }
+    Ok(())
+}
+
+// Gather History Report: every item crawled by the indexer (SystemIndex_Gthr),
+// including the ones already removed from the PropertyStore.
+fn ese_generate_gather_report(
+    jdb: &dyn EseDb,
+    f: &Path,
+    report_prod: &ReportProducer,
+    recovered_hostname: &str,
+    work_ids: &HashSet<u32>,
+    status_logger: &mut Box<dyn Write>,
+    edb_database_state: DbState,
+) -> Result<(), SidrError> {
+    let db_sid = sid_from_db_path(f);
+    let scopes = ese_get_gather_scopes(jdb)?;
+    let t = "SystemIndex_Gthr";
+    let table_id = jdb.open_table(t)?;
+    let cols = jdb.get_columns(t)?;
+    let col = |name: &str| cols.iter().find(|c| c.name == name);
+    let missing = |column: &str| SidrError::MissingColumn {
+        column: column.to_string(),
+        table: t.to_string(),
+    };
+    let scope_id = col("ScopeID").ok_or_else(|| missing("ScopeID"))?;
+    let document_id = col("DocumentID").ok_or_else(|| missing("DocumentID"))?;
+    let get_int = |c: Option<&ColumnInfo>| -> Option<i64> {
+        let v = jdb.get_column(table_id, c?.id).ok()??;
+        int_from_bytes(&v)
+    };
+
+    let mut gather_rep = init_gather_report(
+        f,
+        report_prod,
+        recovered_hostname,
+        status_logger,
+        Some(edb_database_state),
+    )?;
+    if !jdb.move_row(table_id, ESE_MoveFirst)? {
+        // empty table
+        return gather_rep.finish();
+    }
+    loop {
+        let g = GatherRecord {
+            scope_id: get_int(Some(scope_id)).unwrap_or_default(),
+            document_id: get_int(Some(document_id)).unwrap_or_default(),
+            file_name: col("FileName")
+                .and_then(|c| jdb.get_column(table_id, c.id).ok()?)
+                .map(|v| from_utf16(&v)),
+            last_modified: col("LastModified")
+                .and_then(|c| jdb.get_column(table_id, c.id).ok()?)
+                .filter(|v| v.len() == 8)
+                .map(|v| get_date_time_from_filetime(u64::from_bytes(&v))),
+            transaction_flags: get_int(col("TransactionFlags")),
+            crawl_number_crawled: get_int(col("CrawlNumberCrawled")),
+            deleted_count: get_int(col("DeletedCount")),
+            run_time: get_int(col("RunTime")),
+            last_requested_run_time: get_int(col("LastRequestedRunTime")),
+            failure_update_attempts: get_int(col("FailureUpdateAttempts")),
+        };
+        let in_property_store = work_ids.contains(&(g.document_id as u32));
+        write_gather_record(
+            &mut *gather_rep,
+            &g,
+            &scopes,
+            in_property_store,
+            db_sid.as_deref(),
+        );
+
+        if !jdb.move_row(table_id, ESE_MoveNext)? {
+            break;
+        }
+    }
+    gather_rep.finish()
+}
+
+// SystemIndex_GthrPth: the folder hierarchy of the gathered items.
+fn ese_get_gather_scopes(jdb: &dyn EseDb) -> Result<GatherScopes, SidrError> {
+    let t = "SystemIndex_GthrPth";
+    let table_id = jdb.open_table(t)?;
+    let cols = jdb.get_columns(t)?;
+    let col = |name: &str| {
+        cols.iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| SidrError::MissingColumn {
+                column: name.to_string(),
+                table: t.to_string(),
+            })
+    };
+    let (scope, parent, name) = (col("Scope")?, col("Parent")?, col("Name")?);
+
+    let mut scopes = GatherScopes::new();
+    if !jdb.move_row(table_id, ESE_MoveFirst)? {
+        // empty table
+        return Ok(scopes);
+    }
+    loop {
+        if let Some(s) = jdb
+            .get_column(table_id, scope.id)?
+            .and_then(|s| int_from_bytes(&s))
+        {
+            let p = jdb.get_column(table_id, parent.id)?;
+            let n = jdb.get_column(table_id, name.id)?;
+            scopes.insert(
+                s,
+                (
+                    p.and_then(|p| int_from_bytes(&p)).unwrap_or_default(),
+                    n.map_or(String::new(), |n| from_utf16(&n)),
+                ),
+            );
+        }
+        if !jdb.move_row(table_id, ESE_MoveNext)? {
+            break;
+        }
+    }
+    Ok(scopes)
+}
+
+// Same as ese_generate_report, but the reports are described by a ReportsCfg.
+pub fn ese_generate_cfg_report(
+    f: &Path,
+    cfg: &ReportsCfg,
+    report_prod: &ReportProducer,
+    opts: &ParseOptions,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    writeln!(status_logger, "Processing ESE db: {}", &f.to_string_lossy())?;
+    let mut reader = EseReader::new(&f.to_string_lossy(), &cfg.table_edb)?;
+    let edb_database_state = reader.jdb.get_database_state();
+    do_reports(
+        cfg,
+        &mut reader,
+        report_prod,
+        f,
+        Some(edb_database_state),
+        "",
+        &opts.user_names,
+    )?;
+    warn_if_dirty(report_prod, edb_database_state)?;
+    Ok(())
+}
+
+fn warn_if_dirty(
+    report_prod: &ReportProducer,
+    edb_database_state: DbState,
+) -> Result<(), SidrError> {
     if report_prod.is_db_dirty(Some(edb_database_state)) {
         if report_prod.get_report_type() == ReportOutput::ToStdout {
-            eprintln!("WARNING: The database state is not clean");
-            process::exit(exitcode::DATAERR)
+            // the reports are already written, but the run must not look successful
+            return Err(SidrError::Corrupt(
+                "The database state is not clean".to_string(),
+            ));
         } else {
             eprintln!("WARNING: The database state is not clean.");
             eprintln!(
@@ -224,6 +493,14 @@ pub fn ese_generate_report(
     Ok(())
 }
 
+// FILETIMEs and sizes are 8 byte integers, anything else is kept as hex
+fn ese_insert_filetime(r: &mut dyn Report, csp: &str, val: &[u8]) {
+    match val.len() {
+        8 => r.insert_datetime(csp, get_date_time_from_filetime(u64::from_bytes(val))),
+        _ => r.insert_bytes(csp, val),
+    }
+}
+
 // File Report
 fn ese_dump_file_record(r: &mut dyn Report, workId: u32, h: &HashMap<String, Vec<u8>>) {
     r.create_new_row();
@@ -232,137 +509,116 @@ fn ese_dump_file_record(r: &mut dyn Report, workId: u32, h: &HashMap<String, Vec
         let csp = column_string_part(col);
         match csp {
             "System_ItemPathDisplay" => r.insert_str_val(csp, from_utf16(val)),
-            "System_DateModified" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
-            "System_DateCreated" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
-            "System_DateAccessed" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
-            "System_Size" => r.insert_int_val(csp, u64::from_bytes(val)),
+            "System_DateModified" => ese_insert_filetime(r, csp, val),
+            "System_DateCreated" => ese_insert_filetime(r, csp, val),
+            "System_DateAccessed" => ese_insert_filetime(r, csp, val),
+            "System_Size" if val.len() == 8 => r.insert_int_val(csp, u64::from_bytes(val)),
+            "System_Size" => r.insert_bytes(csp, val),
             "System_FileOwner" => r.insert_str_val(csp, from_utf16(val)),
             "System_Search_AutoSummary" => r.insert_str_val(csp, from_utf16(val)),
-            "System_Search_GatherTime" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
+            "System_Search_GatherTime" => ese_insert_filetime(r, csp, val),
             "System_ItemType" => r.insert_str_val(csp, from_utf16(val)),
             "System_ComputerName" => r.insert_str_val(csp, from_utf16(val)),
-            // "ScopeID" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "DocumentID" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "SDID" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "LastModified" => println!("{}: {}", col, format_date_time(get_date_time_from_filetime(u64::from_bytes(&val)))),
-            // "TransactionFlags" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "TransactionExtendedFlags" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "CrawlNumberCrawled" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "StartAddressIdentifier" => println!("{}: {}", col, u16::from_bytes(val)),
-            // "Priority" => println!("{}: {}", col, u8::from_bytes(val)),
-            // "FileName" => println!("{}: {}", col, from_utf16(val)),
-            // "DeletedCount" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "RunTime" => println!("{}: {}", col, i32::from_bytes(val)),
-            // "FailureUpdateAttempts" => println!("{}: {}", col, u8::from_bytes(val)),
-            // "ClientID" => println!("{}: {}", col, u32::from_bytes(val)),
-            // "LastRequestedRunTime" => println!("{}: {}", col, u32::from_bytes(val)),
-            // "CalculatedPropertyFlags" => println!("{}: {}", col, u32::from_bytes(val)),
-            _ => {
-                // /*
-                // field: UserData
-                // field: AppOwnerId
-                // field: RequiredSIDs
-                // field: StorageProviderId
-                // */
-                // if col.chars().nth(0).unwrap().is_alphabetic() {
-                //     r.insert_str_val(col, format!("{:?}", val));
-                // }
-            }
+            // SystemIndex_Gthr fields go to the Gather History Report
+            _ => {}
         }
     }
 }
 
-// IE/Edge History Report
-fn ese_IE_history_record(r: &mut dyn Report, workId: u32, h: &HashMap<String, Vec<u8>>) -> bool {
-    let url = h.get_key_value("33-System_ItemUrl");
-    if url.is_none() {
-        return false;
-    }
-    if let Some((_, val)) = url {
-        let v = from_utf16(val);
-        if !(v.starts_with("iehistory://")
-            || v.starts_with("winrt://")
-                && v.contains("/LS/Desktop/Microsoft Edge/stable/Default/"))
+// All properties, decoded according to the ESE column type
+fn ese_dump_all_properties(
+    r: &mut dyn Report,
+    workId: u32,
+    h: &HashMap<String, Vec<u8>>,
+    col_types: &HashMap<String, u32>,
+) {
+    r.create_new_row();
+    r.insert_int_val("WorkId", workId as u64);
+    for (col, val) in h.iter().sorted() {
+        let csp = column_string_part(col);
+        match col_types.get(col) {
+            Some(col_type) => ese_insert_typed_val(r, csp, *col_type, val),
+            None => r.insert_bytes(csp, val),
+        }
+    }
+}
+
+fn ese_insert_typed_val(r: &mut dyn Report, csp: &str, col_type: u32, val: &[u8]) {
+    // FILETIMEs are stored as 8 byte integers or binary blobs
+    let is_filetime = val.len() == 8 && (csp.contains("Date") || csp.contains("Time"));
+    match col_type {
+        // the integers of an unexpected size are written as hex, like the other undecodable values
+        ESE_coltypBit | ESE_coltypUnsignedByte if val.len() == 1 => {
+            r.insert_int_val(csp, u8::from_bytes(val) as u64)
+        }
+        ESE_coltypShort if val.len() == 2 => r.insert_i64(csp, i16::from_bytes(val) as i64),
+        ESE_coltypUnsignedShort if val.len() == 2 => {
+            r.insert_int_val(csp, u16::from_bytes(val) as u64)
+        }
+        ESE_coltypLong if val.len() == 4 => r.insert_i64(csp, i32::from_bytes(val) as i64),
+        ESE_coltypUnsignedLong if val.len() == 4 => {
+            r.insert_int_val(csp, u32::from_bytes(val) as u64)
+        }
+        ESE_coltypLongLong | ESE_coltypCurrency | ESE_coltypBinary | ESE_coltypLongBinary
+            if is_filetime =>
         {
-            return false;
+            r.insert_datetime(csp, get_date_time_from_filetime(u64::from_bytes(val)))
+        }
+        ESE_coltypLongLong | ESE_coltypCurrency if val.len() == 8 => {
+            r.insert_i64(csp, i64::from_bytes(val))
+        }
+        ESE_coltypIEEESingle if val.len() == 4 => {
+            insert_f32(r, csp, f32::from_le_bytes(val.try_into().unwrap()))
+        }
+        ESE_coltypIEEEDouble if val.len() == 8 => {
+            r.insert_f64(csp, f64::from_le_bytes(val.try_into().unwrap()))
+        }
+        ESE_coltypDateTime if val.len() == 8 => {
+            match get_date_time_from_variant_time(f64::from_le_bytes(val.try_into().unwrap())) {
+                Some(dt) => r.insert_datetime(csp, dt),
+                None => r.insert_bytes(csp, val),
+            }
+        }
+        ESE_coltypText | ESE_coltypLongText => r.insert_str_val(csp, from_utf16(val)),
+        ESE_coltypGUID if val.len() == 16 => r.insert_str_val(csp, format_guid(val)),
+        ESE_coltypBinary | ESE_coltypLongBinary if csp == "System_FileAttributes" => {
+            r.insert_str_val(csp, file_attributes_to_string(&val.to_vec()))
         }
+        _ => r.insert_bytes(csp, val),
     }
+}
 
+// IE/Edge History Report
+fn ese_IE_history_record(r: &mut dyn Report, workId: u32, h: &HashMap<String, Vec<u8>>) {
     r.create_new_row();
     r.insert_int_val("WorkId", workId as u64);
     for (col, val) in h.iter().sorted() {
         let csp = column_string_part(col);
         match csp {
-            "System_DateModified" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
+            "System_DateModified" => ese_insert_filetime(r, csp, val),
             "System_ItemUrl" => r.insert_str_val(csp, from_utf16(val)),
             "System_Link_TargetUrl" => r.insert_str_val(csp, from_utf16(val)),
-            "System_ItemDate" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
-            "System_Search_GatherTime" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
+            "System_ItemDate" => ese_insert_filetime(r, csp, val),
+            "System_Search_GatherTime" => ese_insert_filetime(r, csp, val),
             "System_Title" => r.insert_str_val(csp, from_utf16(val)),
-            "System_Link_DateVisited" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
+            "System_Link_DateVisited" => ese_insert_filetime(r, csp, val),
             "System_ComputerName" => r.insert_str_val(csp, from_utf16(val)),
             _ => {}
         }
     }
-    true
 }
 
 // Activity History Report
-fn ese_activity_history_record(
-    r: &mut dyn Report,
-    workId: u32,
-    h: &HashMap<String, Vec<u8>>,
-) -> bool {
-    // record only if "4450-System_ItemType" == "ActivityHistoryItem"
-    let item_type = h.get_key_value("4450-System_ItemType");
-    if item_type.is_none() {
-        return false;
-    }
-    if let Some((_, val)) = item_type {
-        let v = from_utf16(val);
-        if v != "ActivityHistoryItem" {
-            return false;
-        }
-    }
+fn ese_activity_history_record(r: &mut dyn Report, workId: u32, h: &HashMap<String, Vec<u8>>) {
     r.create_new_row();
     r.insert_int_val("WorkId", workId as u64);
     for (col, val) in h.iter().sorted() {
         let csp = column_string_part(col);
         match csp {
             "System_ItemNameDisplay" => r.insert_str_val(csp, from_utf16(val)),
-            "System_ItemUrl" => r.insert_str_val(csp, from_utf16(val)), // TODO: get UserSID from here
-            "System_ActivityHistory_StartTime" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
-            "System_ActivityHistory_EndTime" => r.insert_str_val(
-                csp,
-                format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-            ),
+            "System_ItemUrl" => r.insert_str_val(csp, from_utf16(val)),
+            "System_ActivityHistory_StartTime" => ese_insert_filetime(r, csp, val),
+            "System_ActivityHistory_EndTime" => ese_insert_filetime(r, csp, val),
             "System_Activity_AppDisplayName" => r.insert_str_val(csp, from_utf16(val)),
             "System_ActivityHistory_AppId" => r.insert_str_val(csp, from_utf16(val)),
             "System_Activity_DisplayText" => r.insert_str_val(csp, from_utf16(val)),
@@ -376,5 +632,123 @@ fn ese_activity_history_record(
             _ => {}
         }
     }
-    true
+}
+
+// FieldReader over a single ESE record already read into memory,
+// fields are addressed by column name without the numeric prefix (e.g. "System_ItemUrl").
+pub struct EseRecord<'a> {
+    h: &'a HashMap<String, Vec<u8>>,
+    read: bool,
+}
+
+impl<'a> EseRecord<'a> {
+    pub fn new(h: &'a HashMap<String, Vec<u8>>) -> Self {
+        EseRecord { h, read: false }
+    }
+
+    fn value(&self, id: &str) -> Option<&Vec<u8>> {
+        self.h
+            .iter()
+            .find(|(col, _)| column_string_part(col) == id)
+            .map(|(_, val)| val)
+    }
+}
+
+impl FieldReader for EseRecord<'_> {
+    fn get_used_columns(
+        &mut self,
+        _columns: &[ColumnPair],
+    ) -> Result<Vec<ConstrainedField>, SidrError> {
+        Ok(Vec::new())
+    }
+
+    fn init(&mut self) -> Result<bool, SidrError> {
+        self.read = false;
+        Ok(true)
+    }
+
+    fn next(&mut self) -> Result<bool, SidrError> {
+        Ok(!std::mem::replace(&mut self.read, true))
+    }
+
+    fn get_int(&mut self, id: &String) -> Option<i64> {
+        self.value(id).and_then(|val| match val.len() {
+            1 => Some(u8::from_bytes(val) as i64),
+            2 => Some(i16::from_bytes(val) as i64),
+            4 => Some(i32::from_bytes(val) as i64),
+            8 => Some(i64::from_bytes(val)),
+            _ => None,
+        })
+    }
+
+    fn get_str(&mut self, id: &String) -> Option<String> {
+        self.value(id).map(|val| from_utf16(val))
+    }
+
+    fn get_guid(&mut self, id: &String) -> Option<String> {
+        self.get_str(id)
+            .map(|s| find_guid(&s, (id.to_owned() + "=").as_str()))
+    }
+
+    fn get_datetime(&mut self, id: &String) -> Option<DateTime<Utc>> {
+        self.value(id)
+            .filter(|val| val.len() == 8)
+            .map(|val| get_date_time_from_filetime(u64::from_bytes(val)))
+    }
+}
+
+#[test]
+fn test_ese_dump_all_properties() {
+    let tmp = tempdir::TempDir::new("sidr").unwrap();
+    let p = &tmp.path().join("test_ese_all_properties.json");
+    let utf16 = |s: &str| -> Vec<u8> { s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect() };
+    let h = HashMap::from([
+        (
+            "15F-System_DateModified".to_string(),
+            133203680386176459_u64.to_le_bytes().to_vec(),
+        ),
+        (
+            "27-System_VolumeId".to_string(),
+            vec![
+                0x6D, 0x8C, 0x04, 0xAC, 0x3C, 0x1E, 0x21, 0x4B, 0xB2, 0x0D, 0x75, 0x74, 0x5D, 0xD7,
+                0x88, 0xB3,
+            ],
+        ),
+        ("33-System_ItemUrl".to_string(), utf16("file:C:/Users")),
+        (
+            "4-System_Rating".to_string(),
+            (-1_i32).to_le_bytes().to_vec(),
+        ),
+        (
+            "4447-System_Size".to_string(),
+            174_u64.to_le_bytes().to_vec(),
+        ),
+        ("5-UserData".to_string(), vec![0xDE, 0xAD]),
+        // truncated integer
+        ("6-System_Priority".to_string(), vec![0x01, 0x02, 0x03]),
+    ]);
+    let col_types = HashMap::from([
+        ("15F-System_DateModified".to_string(), ESE_coltypBinary),
+        ("27-System_VolumeId".to_string(), ESE_coltypGUID),
+        ("33-System_ItemUrl".to_string(), ESE_coltypLongText),
+        ("4-System_Rating".to_string(), ESE_coltypLong),
+        ("4447-System_Size".to_string(), ESE_coltypCurrency),
+        ("5-UserData".to_string(), ESE_coltypLongBinary),
+        ("6-System_Priority".to_string(), ESE_coltypLong),
+    ]);
+    {
+        let mut r = ReportJson::new(p, ReportOutput::ToFile, None, JsonStyle::Lines).unwrap();
+        ese_dump_all_properties(&mut r, 1, &h, &col_types);
+    }
+    let data = std::fs::read_to_string(p).unwrap();
+    assert_eq!(
+        data,
+        concat!(
+            r#"{"WorkId":1,"System_DateModified":"2023-02-08T22:13:58.6176459Z","#,
+            r#""System_VolumeId":"{AC048C6D-1E3C-4B21-B20D-75745DD788B3}","#,
+            r#""System_ItemUrl":"file:C:/Users","System_Rating":-1,"System_Size":174,"#,
+            r#""UserData":"DEAD","System_Priority":"010203"}"#,
+            "\n"
+        )
+    );
 }
@@ -0,0 +1,1023 @@
+// Reading the Windows Search databases straight from disk images (raw/dd or E01),
+// without mounting them: the NTFS volumes of the image are walked to
+// \ProgramData\Microsoft\Search\Data\Applications\ and the files of its subdirectories
+// (Windows\ and the per-user S-1-*\) are extracted to a temporary directory,
+// together with their transaction logs and write-ahead logs.
+
+use crate::error::SidrError;
+use crate::report::DbSource;
+use flate2::read::ZlibDecoder;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const SEARCH_DATA_PATH: [&str; 5] = ["ProgramData", "Microsoft", "Search", "Data", "Applications"];
+
+const SECTOR_SIZE: u64 = 512;
+
+// MBR/GPT
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_PARTITION_TABLE: usize = 446;
+const MBR_PARTITION_TYPE_GPT: u8 = 0xEE;
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+
+// EWF (E01)
+const EWF_SIGNATURE: &[u8] = b"EVF\x09\x0D\x0A\xFF\x00";
+const EWF_FILE_HEADER_SIZE: u64 = 13;
+const EWF_SECTION_DESCRIPTOR_SIZE: usize = 76;
+const EWF_TABLE_HEADER_SIZE: usize = 24;
+const EWF_CHUNK_COMPRESSED: u32 = 0x8000_0000;
+
+// NTFS
+const NTFS_OEM_ID: &[u8] = b"NTFS    ";
+const NTFS_FIXUP_STRIDE: usize = 512;
+const MFT_RECORD_ROOT: u64 = 5;
+const MFT_REFERENCE_MASK: u64 = 0x0000_FFFF_FFFF_FFFF;
+const ATTR_ATTRIBUTE_LIST: u32 = 0x20;
+const ATTR_DATA: u32 = 0x80;
+const ATTR_INDEX_ROOT: u32 = 0x90;
+const ATTR_INDEX_ALLOCATION: u32 = 0xA0;
+const ATTR_END: u32 = 0xFFFF_FFFF;
+const ATTR_FLAG_COMPRESSED: u16 = 0x0001;
+const INDEX_ENTRY_SUBNODE: u16 = 0x01;
+const INDEX_ENTRY_LAST: u16 = 0x02;
+const FILE_NAME_DOS: u8 = 2;
+const FILE_NAME_FLAG_DIRECTORY: u32 = 0x1000_0000;
+
+pub trait Image {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), SidrError>;
+}
+
+fn read_exact_at(file: &mut File, offset: u64, buf: &mut [u8]) -> Result<(), SidrError> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)?;
+    Ok(())
+}
+
+fn le_u16(data: &[u8], offset: usize) -> u16 {
+    data.get(offset..offset + 2)
+        .map_or(0, |b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn le_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4)
+        .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn le_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8)
+        .map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+pub struct RawImage {
+    file: File,
+}
+
+impl Image for RawImage {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), SidrError> {
+        read_exact_at(&mut self.file, offset, buf)
+    }
+}
+
+struct EwfChunk {
+    segment: usize,
+    offset: u64,
+    size: u64,
+    compressed: bool,
+}
+
+pub struct EwfImage {
+    segments: Vec<File>,
+    chunks: Vec<EwfChunk>,
+    chunk_size: u64,
+    // last chunk read
+    cache: Option<(usize, Vec<u8>)>,
+}
+
+// image.E01, image.E02, ..., image.E99, image.EAA, ...
+fn ewf_segment_path(first: &Path, n: usize) -> Option<PathBuf> {
+    let first_ext = first.extension()?.to_str()?;
+    let ext = if n < 100 {
+        format!("E{n:02}")
+    } else if n - 100 < 26 * 26 {
+        let n = (n - 100) as u8;
+        format!("E{}{}", (b'A' + n / 26) as char, (b'A' + n % 26) as char)
+    } else {
+        return None;
+    };
+    if first_ext.starts_with('e') {
+        Some(first.with_extension(ext.to_lowercase()))
+    } else {
+        Some(first.with_extension(ext))
+    }
+}
+
+impl EwfImage {
+    pub fn open(first: &Path) -> Result<Self, SidrError> {
+        let mut img = EwfImage {
+            segments: Vec::new(),
+            chunks: Vec::new(),
+            chunk_size: 0,
+            cache: None,
+        };
+        let mut n = 1;
+        while let Some(path) = ewf_segment_path(first, n) {
+            if !path.exists() {
+                break;
+            }
+            img.segments.push(File::open(&path)?);
+            if img.read_sections(img.segments.len() - 1)? {
+                break;
+            }
+            n += 1;
+        }
+        if img.chunk_size == 0 || img.chunks.is_empty() {
+            return Err(SidrError::Corrupt(format!(
+                "{} has no volume or table section",
+                first.to_string_lossy()
+            )));
+        }
+        Ok(img)
+    }
+
+    // Collects the chunks of a segment file, returns true at the "done" section.
+    fn read_sections(&mut self, segment: usize) -> Result<bool, SidrError> {
+        let file = &mut self.segments[segment];
+        let mut header = [0u8; EWF_FILE_HEADER_SIZE as usize];
+        read_exact_at(file, 0, &mut header)?;
+        if !header.starts_with(EWF_SIGNATURE) {
+            return Err(SidrError::Corrupt("Invalid EWF signature".to_string()));
+        }
+        let mut offset = EWF_FILE_HEADER_SIZE;
+        let mut sectors_end = 0;
+        let mut visited = HashSet::new();
+        while visited.insert(offset) {
+            let mut desc = [0u8; EWF_SECTION_DESCRIPTOR_SIZE];
+            read_exact_at(file, offset, &mut desc)?;
+            let typ = String::from_utf8_lossy(&desc[..16])
+                .trim_end_matches('\0')
+                .to_string();
+            let next = le_u64(&desc, 16);
+            let size = le_u64(&desc, 24);
+            let data = offset + EWF_SECTION_DESCRIPTOR_SIZE as u64;
+            match typ.as_str() {
+                "volume" | "disk" => {
+                    let mut volume = [0u8; 16];
+                    read_exact_at(file, data, &mut volume)?;
+                    self.chunk_size = le_u32(&volume, 8) as u64 * le_u32(&volume, 12) as u64;
+                }
+                "sectors" => sectors_end = offset + size,
+                "table" => {
+                    let mut table = [0u8; EWF_TABLE_HEADER_SIZE];
+                    read_exact_at(file, data, &mut table)?;
+                    let max_entries = size.saturating_sub(data - offset) / 4;
+                    let count = (le_u32(&table, 0) as u64).min(max_entries) as usize;
+                    let base = le_u64(&table, 8);
+                    let mut entries = vec![0u8; count * 4];
+                    read_exact_at(file, data + EWF_TABLE_HEADER_SIZE as u64, &mut entries)?;
+                    let chunks: Vec<(u64, bool)> = entries
+                        .chunks_exact(4)
+                        .map(|e| {
+                            let raw = le_u32(e, 0);
+                            (
+                                base + (raw & !EWF_CHUNK_COMPRESSED) as u64,
+                                raw & EWF_CHUNK_COMPRESSED != 0,
+                            )
+                        })
+                        .collect();
+                    for (i, &(chunk_offset, compressed)) in chunks.iter().enumerate() {
+                        // the last chunk of a table ends with the sectors section
+                        let end = chunks.get(i + 1).map_or(sectors_end, |c| c.0);
+                        self.chunks.push(EwfChunk {
+                            segment,
+                            offset: chunk_offset,
+                            size: end.saturating_sub(chunk_offset),
+                            compressed,
+                        });
+                    }
+                }
+                "done" => return Ok(true),
+                "next" => return Ok(false),
+                _ => {}
+            }
+            if next <= offset {
+                break;
+            }
+            offset = next;
+        }
+        Ok(false)
+    }
+
+    fn read_chunk(&mut self, i: usize) -> Result<Vec<u8>, SidrError> {
+        let chunk = &self.chunks[i];
+        let mut data = vec![0u8; chunk.size as usize];
+        read_exact_at(&mut self.segments[chunk.segment], chunk.offset, &mut data)?;
+        if chunk.compressed {
+            let mut out = Vec::with_capacity(self.chunk_size as usize);
+            ZlibDecoder::new(&data[..]).read_to_end(&mut out)?;
+            Ok(out)
+        } else {
+            // uncompressed chunks are followed by their checksum
+            data.truncate(self.chunk_size as usize);
+            Ok(data)
+        }
+    }
+}
+
+impl Image for EwfImage {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), SidrError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let i = (pos / self.chunk_size) as usize;
+            if i >= self.chunks.len() {
+                return Err(SidrError::Corrupt(format!(
+                    "Read beyond the end of the image at {pos}"
+                )));
+            }
+            if !matches!(self.cache, Some((cached, _)) if cached == i) {
+                let data = self.read_chunk(i)?;
+                self.cache = Some((i, data));
+            }
+            let chunk = &self.cache.as_ref().unwrap().1;
+            let within = (pos % self.chunk_size) as usize;
+            if within >= chunk.len() {
+                return Err(SidrError::Corrupt(format!("Truncated chunk {i}")));
+            }
+            let n = (chunk.len() - within).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&chunk[within..within + n]);
+            done += n;
+        }
+        Ok(())
+    }
+}
+
+fn is_ewf(file: &mut File) -> bool {
+    let mut signature = [0u8; 8];
+    file.read_exact(&mut signature).is_ok() && signature == EWF_SIGNATURE
+}
+
+// The format of an image and its files, every segment of an E01 one, to be hashed.
+pub fn image_files(path: &Path) -> (&'static str, Vec<PathBuf>) {
+    if !File::open(path).is_ok_and(|mut file| is_ewf(&mut file)) {
+        return ("raw", vec![path.to_path_buf()]);
+    }
+    let segments: Vec<PathBuf> = (1..)
+        .map_while(|n| ewf_segment_path(path, n))
+        .take_while(|p| p.exists())
+        .collect();
+    // a first segment not named .E01
+    if segments.first().map(PathBuf::as_path) != Some(path) {
+        return ("E01", vec![path.to_path_buf()]);
+    }
+    ("E01", segments)
+}
+
+pub fn open_image(path: &Path) -> Result<Box<dyn Image>, SidrError> {
+    let mut file = File::open(path)?;
+    if is_ewf(&mut file) {
+        Ok(Box::new(EwfImage::open(path)?))
+    } else {
+        Ok(Box::new(RawImage { file }))
+    }
+}
+
+fn is_ntfs(img: &mut dyn Image, offset: u64) -> bool {
+    let mut boot = [0u8; 11];
+    img.read_at(offset, &mut boot).is_ok() && &boot[3..] == NTFS_OEM_ID
+}
+
+// Offsets of the NTFS volumes: the image itself or the MBR/GPT partitions.
+pub fn ntfs_volumes(img: &mut dyn Image) -> Result<Vec<u64>, SidrError> {
+    if is_ntfs(img, 0) {
+        return Ok(vec![0]);
+    }
+    let mut mbr = [0u8; SECTOR_SIZE as usize];
+    img.read_at(0, &mut mbr)?;
+    if mbr[510..] != MBR_SIGNATURE {
+        return Ok(Vec::new());
+    }
+    let mut candidates = Vec::new();
+    for entry in mbr[MBR_PARTITION_TABLE..510].chunks_exact(16) {
+        let start = le_u32(entry, 8) as u64 * SECTOR_SIZE;
+        match entry[4] {
+            0 => {}
+            MBR_PARTITION_TYPE_GPT => {
+                let mut gpt = [0u8; SECTOR_SIZE as usize];
+                img.read_at(SECTOR_SIZE, &mut gpt)?;
+                if !gpt.starts_with(GPT_SIGNATURE) {
+                    continue;
+                }
+                let entries_lba = le_u64(&gpt, 72);
+                let count = le_u32(&gpt, 80).min(256) as usize;
+                let entry_size = le_u32(&gpt, 84) as usize;
+                if entry_size < 128 {
+                    continue;
+                }
+                let mut entries = vec![0u8; count * entry_size];
+                img.read_at(entries_lba * SECTOR_SIZE, &mut entries)?;
+                for entry in entries.chunks_exact(entry_size) {
+                    if entry[..16].iter().any(|b| *b != 0) {
+                        candidates.push(le_u64(entry, 32) * SECTOR_SIZE);
+                    }
+                }
+            }
+            _ => candidates.push(start),
+        }
+    }
+    Ok(candidates
+        .into_iter()
+        .filter(|offset| *offset > 0 && is_ntfs(img, *offset))
+        .collect())
+}
+
+#[derive(Debug, PartialEq)]
+struct Run {
+    vcn: u64,
+    // None for sparse runs
+    lcn: Option<u64>,
+    len: u64,
+}
+
+fn decode_runs(data: &[u8], start_vcn: u64) -> Vec<Run> {
+    let mut runs = Vec::new();
+    let (mut i, mut vcn, mut lcn) = (0, start_vcn, 0i64);
+    while i < data.len() && data[i] != 0 {
+        let (len_size, off_size) = ((data[i] & 0x0F) as usize, (data[i] >> 4) as usize);
+        i += 1;
+        if len_size == 0 || len_size > 8 || off_size > 8 || i + len_size + off_size > data.len() {
+            break;
+        }
+        let mut len = [0u8; 8];
+        len[..len_size].copy_from_slice(&data[i..i + len_size]);
+        let len = u64::from_le_bytes(len);
+        i += len_size;
+        let run_lcn = if off_size == 0 {
+            None
+        } else {
+            // signed, relative to the previous run
+            let fill = if data[i + off_size - 1] & 0x80 != 0 {
+                0xFF
+            } else {
+                0
+            };
+            let mut delta = [fill; 8];
+            delta[..off_size].copy_from_slice(&data[i..i + off_size]);
+            lcn += i64::from_le_bytes(delta);
+            Some(lcn as u64)
+        };
+        i += off_size;
+        runs.push(Run {
+            vcn,
+            lcn: run_lcn,
+            len,
+        });
+        vcn += len;
+    }
+    runs
+}
+
+// Restores the last two bytes of every 512 byte block of a FILE/INDX record.
+fn apply_fixups(rec: &mut [u8]) -> Result<(), SidrError> {
+    let usa_offset = le_u16(rec, 4) as usize;
+    let usa_count = le_u16(rec, 6) as usize;
+    if usa_count == 0 || usa_offset + 2 * usa_count > rec.len() {
+        return Err(SidrError::Corrupt(
+            "Invalid update sequence array".to_string(),
+        ));
+    }
+    let usn = [rec[usa_offset], rec[usa_offset + 1]];
+    for i in 1..usa_count {
+        let end = i * NTFS_FIXUP_STRIDE;
+        if end > rec.len() {
+            break;
+        }
+        if rec[end - 2..end] != usn {
+            return Err(SidrError::Corrupt("Torn NTFS record".to_string()));
+        }
+        rec[end - 2] = rec[usa_offset + 2 * i];
+        rec[end - 1] = rec[usa_offset + 2 * i + 1];
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Attribute {
+    typ: u32,
+    name: String,
+    flags: u16,
+    // value of a resident attribute
+    value: Option<Vec<u8>>,
+    runs: Vec<Run>,
+    size: u64,
+}
+
+fn utf16_name(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn parse_attributes(rec: &[u8]) -> Vec<Attribute> {
+    let mut attrs = Vec::new();
+    let mut off = le_u16(rec, 0x14) as usize;
+    while off + 16 <= rec.len() {
+        let typ = le_u32(rec, off);
+        let len = le_u32(rec, off + 4) as usize;
+        if typ == ATTR_END || len < 16 || off + len > rec.len() {
+            break;
+        }
+        let attr = &rec[off..off + len];
+        let name_len = attr[9] as usize;
+        let name_off = le_u16(attr, 0x0A) as usize;
+        let name = attr
+            .get(name_off..name_off + 2 * name_len)
+            .map(utf16_name)
+            .unwrap_or_default();
+        let flags = le_u16(attr, 0x0C);
+        if attr[8] == 0 {
+            let value_len = le_u32(attr, 0x10) as usize;
+            let value_off = le_u16(attr, 0x14) as usize;
+            if let Some(value) = attr.get(value_off..value_off + value_len) {
+                attrs.push(Attribute {
+                    typ,
+                    name,
+                    flags,
+                    value: Some(value.to_vec()),
+                    runs: Vec::new(),
+                    size: value_len as u64,
+                });
+            }
+        } else {
+            let runs_off = le_u16(attr, 0x20) as usize;
+            attrs.push(Attribute {
+                typ,
+                name,
+                flags,
+                value: None,
+                runs: decode_runs(attr.get(runs_off..).unwrap_or_default(), le_u64(attr, 0x10)),
+                size: le_u64(attr, 0x30),
+            });
+        }
+        off += len;
+    }
+    attrs
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub record: u64,
+    pub is_dir: bool,
+}
+
+// Entries of an index node ($I30 of a directory), with the VCNs of the child nodes.
+fn index_entries(node: &[u8], entries: &mut Vec<DirEntry>, subnodes: &mut Vec<u64>) {
+    let mut off = le_u32(node, 0) as usize;
+    let end = (le_u32(node, 4) as usize).min(node.len());
+    while off + 16 <= end {
+        let len = le_u16(node, off + 8) as usize;
+        let key_len = le_u16(node, off + 10) as usize;
+        let flags = le_u16(node, off + 12);
+        if len < 16 || off + len > end {
+            break;
+        }
+        if flags & INDEX_ENTRY_SUBNODE != 0 {
+            subnodes.push(le_u64(node, off + len - 8));
+        }
+        if flags & INDEX_ENTRY_LAST != 0 {
+            break;
+        }
+        let key = &node[off + 16..off + 16 + key_len.min(len - 16)];
+        let name_len = key.get(0x40).copied().unwrap_or(0) as usize;
+        let namespace = key.get(0x41).copied().unwrap_or(FILE_NAME_DOS);
+        if namespace != FILE_NAME_DOS {
+            if let Some(name) = key.get(0x42..0x42 + 2 * name_len) {
+                entries.push(DirEntry {
+                    name: utf16_name(name),
+                    record: le_u64(node, off) & MFT_REFERENCE_MASK,
+                    is_dir: le_u32(key, 0x38) & FILE_NAME_FLAG_DIRECTORY != 0,
+                });
+            }
+        }
+        off += len;
+    }
+}
+
+pub struct NtfsVolume<'a> {
+    img: &'a mut dyn Image,
+    offset: u64,
+    cluster_size: u64,
+    record_size: u64,
+    index_record_size: u64,
+    // $DATA of $MFT
+    mft: Attribute,
+}
+
+// clusters per MFT/index record, negative values are a power of two in bytes
+fn ntfs_record_size(val: u8, cluster_size: u64) -> u64 {
+    match val as i8 {
+        n if n > 0 => n as u64 * cluster_size,
+        n => 1u64 << (-(n as i32)).clamp(0, 31),
+    }
+}
+
+impl<'a> NtfsVolume<'a> {
+    pub fn open(img: &'a mut dyn Image, offset: u64) -> Result<Self, SidrError> {
+        let mut boot = [0u8; SECTOR_SIZE as usize];
+        img.read_at(offset, &mut boot)?;
+        let bytes_per_sector = le_u16(&boot, 0x0B) as u64;
+        let sectors_per_cluster = match boot[0x0D] {
+            n if n > 0x80 => 1u64 << (256 - n as u32).min(31),
+            n => n as u64,
+        };
+        let cluster_size = bytes_per_sector * sectors_per_cluster;
+        if cluster_size == 0 {
+            return Err(SidrError::Corrupt("Invalid NTFS cluster size".to_string()));
+        }
+        let record_size = ntfs_record_size(boot[0x40], cluster_size);
+        let index_record_size = ntfs_record_size(boot[0x44], cluster_size);
+        if !(512..=65536).contains(&record_size) {
+            return Err(SidrError::Corrupt("Invalid MFT record size".to_string()));
+        }
+
+        // $MFT describes itself in its first record
+        let mft_offset = offset + le_u64(&boot, 0x30) * cluster_size;
+        let mut rec = vec![0u8; record_size as usize];
+        img.read_at(mft_offset, &mut rec)?;
+        apply_fixups(&mut rec)?;
+        let mft = parse_attributes(&rec)
+            .into_iter()
+            .find(|a| a.typ == ATTR_DATA && a.name.is_empty() && a.value.is_none())
+            .ok_or_else(|| SidrError::Corrupt("No $DATA in $MFT".to_string()))?;
+        let mut vol = NtfsVolume {
+            img,
+            offset,
+            cluster_size,
+            record_size,
+            index_record_size,
+            mft,
+        };
+        // a fragmented $MFT continues in extension records
+        let attrs = vol.attributes(0)?;
+        if let Some(mft) = attrs
+            .into_iter()
+            .find(|a| a.typ == ATTR_DATA && a.name.is_empty() && a.value.is_none())
+        {
+            vol.mft = mft;
+        }
+        Ok(vol)
+    }
+
+    // Reads buf.len() bytes of a non-resident attribute starting at pos.
+    fn read_runs(&mut self, attr: &Attribute, pos: u64, buf: &mut [u8]) -> Result<(), SidrError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let at = pos + done as u64;
+            let vcn = at / self.cluster_size;
+            let run = attr
+                .runs
+                .iter()
+                .find(|r| r.vcn <= vcn && vcn < r.vcn + r.len)
+                .ok_or_else(|| SidrError::Corrupt(format!("No run for VCN {vcn}")))?;
+            let within = at - run.vcn * self.cluster_size;
+            let n = ((run.len * self.cluster_size - within) as usize).min(buf.len() - done);
+            match run.lcn {
+                Some(lcn) => self.img.read_at(
+                    self.offset + lcn * self.cluster_size + within,
+                    &mut buf[done..done + n],
+                )?,
+                None => buf[done..done + n].fill(0),
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn read_attribute(&mut self, attr: &Attribute) -> Result<Vec<u8>, SidrError> {
+        match attr.value {
+            Some(ref value) => Ok(value.clone()),
+            None => {
+                let mut data = vec![0u8; attr.size as usize];
+                self.read_runs(attr, 0, &mut data)?;
+                Ok(data)
+            }
+        }
+    }
+
+    fn read_record(&mut self, n: u64) -> Result<Vec<u8>, SidrError> {
+        let mut rec = vec![0u8; self.record_size as usize];
+        let mft = std::mem::take(&mut self.mft);
+        let read = self.read_runs(&mft, n * self.record_size, &mut rec);
+        self.mft = mft;
+        read?;
+        if !rec.starts_with(b"FILE") {
+            return Err(SidrError::Corrupt(format!("Invalid MFT record {n}")));
+        }
+        apply_fixups(&mut rec)?;
+        Ok(rec)
+    }
+
+    // Attributes of a file, including the ones in the extension records of its attribute list.
+    // The extents of a non-resident attribute are merged.
+    fn attributes(&mut self, n: u64) -> Result<Vec<Attribute>, SidrError> {
+        let mut attrs = parse_attributes(&self.read_record(n)?);
+        let Some(list) = attrs.iter().position(|a| a.typ == ATTR_ATTRIBUTE_LIST) else {
+            return Ok(attrs);
+        };
+        let list = attrs.remove(list);
+        let list = self.read_attribute(&list)?;
+        let mut records = Vec::new();
+        let mut off = 0;
+        while off + 26 <= list.len() {
+            let len = le_u16(&list, off + 4) as usize;
+            if len == 0 {
+                break;
+            }
+            let record = le_u64(&list, off + 16) & MFT_REFERENCE_MASK;
+            if record != n && !records.contains(&record) {
+                records.push(record);
+            }
+            off += len;
+        }
+        for record in records {
+            for attr in parse_attributes(&self.read_record(record)?) {
+                let extent = attrs
+                    .iter_mut()
+                    .find(|a| a.typ == attr.typ && a.name == attr.name && a.value.is_none());
+                match extent {
+                    Some(first) if attr.value.is_none() => {
+                        // only the first extent holds the size
+                        if attr.runs.first().is_some_and(|r| r.vcn == 0) {
+                            first.size = attr.size;
+                        }
+                        first.runs.extend(attr.runs);
+                    }
+                    _ => attrs.push(attr),
+                }
+            }
+        }
+        for attr in &mut attrs {
+            attr.runs.sort_by_key(|r| r.vcn);
+        }
+        Ok(attrs)
+    }
+
+    pub fn list_dir(&mut self, n: u64) -> Result<Vec<DirEntry>, SidrError> {
+        let attrs = self.attributes(n)?;
+        let root = attrs
+            .iter()
+            .find(|a| a.typ == ATTR_INDEX_ROOT && a.name == "$I30")
+            .and_then(|a| a.value.as_ref())
+            .ok_or_else(|| SidrError::Corrupt(format!("MFT record {n} is not a directory")))?;
+        let mut entries = Vec::new();
+        let mut subnodes = Vec::new();
+        index_entries(
+            root.get(16..).unwrap_or_default(),
+            &mut entries,
+            &mut subnodes,
+        );
+
+        if let Some(alloc) = attrs
+            .iter()
+            .find(|a| a.typ == ATTR_INDEX_ALLOCATION && a.name == "$I30")
+        {
+            let vcn_size = if self.index_record_size >= self.cluster_size {
+                self.cluster_size
+            } else {
+                SECTOR_SIZE
+            };
+            let mut visited = HashSet::new();
+            while let Some(vcn) = subnodes.pop() {
+                if !visited.insert(vcn) {
+                    continue;
+                }
+                let mut block = vec![0u8; self.index_record_size as usize];
+                self.read_runs(alloc, vcn * vcn_size, &mut block)?;
+                if !block.starts_with(b"INDX") {
+                    continue;
+                }
+                apply_fixups(&mut block)?;
+                index_entries(&block[0x18..], &mut entries, &mut subnodes);
+            }
+        }
+        let mut seen = HashSet::new();
+        entries.retain(|e| seen.insert(e.record));
+        Ok(entries)
+    }
+
+    // MFT record of the directory at path (case insensitive), starting at the root directory.
+    pub fn find_dir(&mut self, path: &[&str]) -> Result<Option<u64>, SidrError> {
+        let mut dir = MFT_RECORD_ROOT;
+        for name in path {
+            let name = name.to_lowercase();
+            match self
+                .list_dir(dir)?
+                .into_iter()
+                .find(|e| e.is_dir && e.name.to_lowercase() == name)
+            {
+                Some(e) => dir = e.record,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(dir))
+    }
+
+    // Copies the unnamed $DATA stream of a file to dest.
+    pub fn extract(&mut self, n: u64, dest: &Path) -> Result<(), SidrError> {
+        let data = self
+            .attributes(n)?
+            .into_iter()
+            .find(|a| a.typ == ATTR_DATA && a.name.is_empty())
+            .ok_or_else(|| SidrError::Corrupt(format!("No $DATA in MFT record {n}")))?;
+        if data.flags & ATTR_FLAG_COMPRESSED != 0 {
+            return Err(SidrError::Corrupt(
+                "NTFS compressed files are not supported".to_string(),
+            ));
+        }
+        let mut out = File::create(dest)?;
+        if let Some(ref value) = data.value {
+            out.write_all(value)?;
+            return Ok(());
+        }
+        let mut buf = vec![0u8; 1 << 20];
+        let mut pos = 0;
+        while pos < data.size {
+            let n = ((data.size - pos) as usize).min(buf.len());
+            self.read_runs(&data, pos, &mut buf[..n])?;
+            out.write_all(&buf[..n])?;
+            pos += n as u64;
+        }
+        Ok(())
+    }
+}
+
+// Extracts the files of the Windows Search directories of every NTFS volume of the image into dir.
+pub fn extract_search_dbs(
+    image: &Path,
+    dir: &Path,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<HashMap<PathBuf, DbSource>, SidrError> {
+    let mut img = open_image(image)?;
+    let volumes = ntfs_volumes(&mut *img)?;
+    if volumes.is_empty() {
+        return Err(SidrError::Corrupt(format!(
+            "No NTFS volume found in {}",
+            image.to_string_lossy()
+        )));
+    }
+    let mut files = HashMap::new();
+    for offset in volumes {
+        let mut vol = NtfsVolume::open(&mut *img, offset)?;
+        let Some(apps) = vol.find_dir(&SEARCH_DATA_PATH)? else {
+            writeln!(
+                status_logger,
+                "No Windows Search directory in the volume at offset {offset}"
+            )?;
+            continue;
+        };
+        for sub in vol.list_dir(apps)?.into_iter().filter(|e| e.is_dir) {
+            let sub_dir = dir.join(format!("{offset}")).join(&sub.name);
+            for file in vol.list_dir(sub.record)?.into_iter().filter(|e| !e.is_dir) {
+                if file.name.contains(['/', '\\']) || file.name.starts_with('.') {
+                    continue;
+                }
+                std::fs::create_dir_all(&sub_dir)?;
+                let dest = sub_dir.join(&file.name);
+                let path = format!(
+                    "\\{}\\{}\\{}",
+                    SEARCH_DATA_PATH.join("\\"),
+                    sub.name,
+                    file.name
+                );
+                match vol.extract(file.record, &dest) {
+                    Ok(()) => {
+                        files.insert(
+                            dest,
+                            DbSource::Image {
+                                image: image.to_path_buf(),
+                                volume_offset: offset,
+                                path,
+                            },
+                        );
+                    }
+                    Err(e) => eprintln!("Could not extract {path} from the image: {e}"),
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_runs() {
+        // 0x30 clusters at 0x1000, 0x10 sparse clusters, 0x20 clusters at 0x1000 - 0x100
+        let data = [
+            0x21, 0x30, 0x00, 0x10, 0x01, 0x10, 0x21, 0x20, 0x00, 0xFF, 0x00,
+        ];
+        assert_eq!(
+            decode_runs(&data, 0),
+            vec![
+                Run {
+                    vcn: 0,
+                    lcn: Some(0x1000),
+                    len: 0x30
+                },
+                Run {
+                    vcn: 0x30,
+                    lcn: None,
+                    len: 0x10
+                },
+                Run {
+                    vcn: 0x40,
+                    lcn: Some(0x0F00),
+                    len: 0x20
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_fixups() {
+        let mut rec = vec![0u8; 1024];
+        rec[..4].copy_from_slice(b"FILE");
+        rec[4] = 0x30; // update sequence array offset
+        rec[6] = 3; // usn + 2 blocks
+        rec[0x30..0x36].copy_from_slice(&[0xAB, 0xCD, 0x11, 0x22, 0x33, 0x44]);
+        rec[510..512].copy_from_slice(&[0xAB, 0xCD]);
+        rec[1022..1024].copy_from_slice(&[0xAB, 0xCD]);
+        apply_fixups(&mut rec).unwrap();
+        assert_eq!(rec[510..512], [0x11, 0x22]);
+        assert_eq!(rec[1022..1024], [0x33, 0x44]);
+
+        // torn write
+        rec[510..512].copy_from_slice(&[0, 0]);
+        assert!(apply_fixups(&mut rec).is_err());
+    }
+
+    #[test]
+    fn test_index_entries() {
+        let entry = |record: u64, name: &str, flags: u16, dir: bool| {
+            let name: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+            let mut key = vec![0u8; 0x42];
+            if dir {
+                key[0x38..0x3C].copy_from_slice(&FILE_NAME_FLAG_DIRECTORY.to_le_bytes());
+            }
+            key[0x40] = (name.len() / 2) as u8;
+            key[0x41] = 1; // win32
+            key.extend(name);
+            let mut e = vec![0u8; 16];
+            e[..8].copy_from_slice(&record.to_le_bytes());
+            e[10..12].copy_from_slice(&(key.len() as u16).to_le_bytes());
+            e[12..14].copy_from_slice(&flags.to_le_bytes());
+            e.extend(key);
+            if flags & INDEX_ENTRY_SUBNODE != 0 {
+                e.extend(7u64.to_le_bytes());
+            }
+            let len = e.len() as u16;
+            e[8..10].copy_from_slice(&len.to_le_bytes());
+            e
+        };
+        let mut node = vec![0u8; 16];
+        node.extend(entry(0x0001_0000_0000_0040, "Windows", 0, true));
+        node.extend(entry(0x41, "Windows.edb", 0, false));
+        node.extend(entry(0, "", INDEX_ENTRY_SUBNODE | INDEX_ENTRY_LAST, false));
+        let len = node.len() as u32;
+        node[0..4].copy_from_slice(&16u32.to_le_bytes());
+        node[4..8].copy_from_slice(&len.to_le_bytes());
+
+        let (mut entries, mut subnodes) = (Vec::new(), Vec::new());
+        index_entries(&node, &mut entries, &mut subnodes);
+        assert_eq!(subnodes, [7]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (
+                entries[0].name.as_str(),
+                entries[0].record,
+                entries[0].is_dir
+            ),
+            ("Windows", 0x40, true)
+        );
+        assert_eq!(
+            (
+                entries[1].name.as_str(),
+                entries[1].record,
+                entries[1].is_dir
+            ),
+            ("Windows.edb", 0x41, false)
+        );
+    }
+
+    // A single segment E01 with a compressed and an uncompressed chunk.
+    #[test]
+    fn test_ewf_image() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+
+        let chunk_size = 64 * 512;
+        let first: Vec<u8> = (0..chunk_size).map(|i| (i % 251) as u8).collect();
+        let second = vec![0x5Au8; chunk_size];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&first).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let section = |typ: &str, next: u64, size: u64| {
+            let mut desc = vec![0u8; EWF_SECTION_DESCRIPTOR_SIZE];
+            desc[..typ.len()].copy_from_slice(typ.as_bytes());
+            desc[16..24].copy_from_slice(&next.to_le_bytes());
+            desc[24..32].copy_from_slice(&size.to_le_bytes());
+            desc
+        };
+        let mut data = EWF_SIGNATURE.to_vec();
+        data.extend([1, 1, 0, 0, 0]);
+        // volume
+        let volume_size = (EWF_SECTION_DESCRIPTOR_SIZE + 16) as u64;
+        data.extend(section(
+            "volume",
+            data.len() as u64 + volume_size,
+            volume_size,
+        ));
+        let mut volume = vec![0u8; 16];
+        volume[4..8].copy_from_slice(&2u32.to_le_bytes());
+        volume[8..12].copy_from_slice(&64u32.to_le_bytes());
+        volume[12..16].copy_from_slice(&512u32.to_le_bytes());
+        data.extend(volume);
+        // sectors
+        let sectors_start = data.len() as u64;
+        let sectors_size = (EWF_SECTION_DESCRIPTOR_SIZE + compressed.len() + chunk_size + 4) as u64;
+        data.extend(section(
+            "sectors",
+            sectors_start + sectors_size,
+            sectors_size,
+        ));
+        let first_offset = data.len() as u32;
+        data.extend(&compressed);
+        let second_offset = data.len() as u32;
+        data.extend(&second);
+        data.extend([0u8; 4]); // checksum
+                               // table
+        let table_size = (EWF_SECTION_DESCRIPTOR_SIZE + EWF_TABLE_HEADER_SIZE + 8) as u64;
+        let table_start = data.len() as u64;
+        data.extend(section("table", table_start + table_size, table_size));
+        let mut table = vec![0u8; EWF_TABLE_HEADER_SIZE];
+        table[0..4].copy_from_slice(&2u32.to_le_bytes());
+        data.extend(table);
+        data.extend((first_offset | EWF_CHUNK_COMPRESSED).to_le_bytes());
+        data.extend(second_offset.to_le_bytes());
+        // done
+        let done = data.len() as u64;
+        data.extend(section("done", done, EWF_SECTION_DESCRIPTOR_SIZE as u64));
+
+        let dir = tempdir::TempDir::new("sidr_ewf").unwrap();
+        let path = dir.path().join("image.E01");
+        std::fs::write(&path, &data).unwrap();
+
+        let mut img = open_image(&path).unwrap();
+        let mut buf = vec![0u8; 100];
+        img.read_at(chunk_size as u64 - 50, &mut buf).unwrap();
+        assert_eq!(buf[..50], first[chunk_size - 50..]);
+        assert_eq!(buf[50..], second[..50]);
+        assert!(img.read_at(2 * chunk_size as u64 - 10, &mut buf).is_err());
+        assert!(ntfs_volumes(&mut *img).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_image_files() {
+        let dir = tempdir::TempDir::new("sidr_image_files").unwrap();
+        for name in ["disk.E01", "disk.E02", "disk.E04"] {
+            std::fs::write(dir.path().join(name), EWF_SIGNATURE).unwrap();
+        }
+        std::fs::write(dir.path().join("disk.dd"), [0u8; 8]).unwrap();
+        let first = dir.path().join("disk.E01");
+        assert_eq!(
+            image_files(&first),
+            ("E01", vec![first.clone(), dir.path().join("disk.E02")])
+        );
+        let raw = dir.path().join("disk.dd");
+        assert_eq!(image_files(&raw), ("raw", vec![raw.clone()]));
+    }
+
+    #[test]
+    fn test_ewf_segment_path() {
+        let first = Path::new("case/disk.E01");
+        assert_eq!(
+            ewf_segment_path(first, 2),
+            Some(PathBuf::from("case/disk.E02"))
+        );
+        assert_eq!(
+            ewf_segment_path(first, 101),
+            Some(PathBuf::from("case/disk.EAB"))
+        );
+        assert_eq!(
+            ewf_segment_path(Path::new("disk.e01"), 10),
+            Some(PathBuf::from("disk.e10"))
+        );
+    }
+}
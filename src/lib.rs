@@ -1,13 +1,18 @@
 #![allow(non_upper_case_globals)]
+pub mod error;
 #[warn(non_camel_case_types)]
 pub mod report;
 #[allow(non_camel_case_types)]
 pub mod utils;
 
-use crate::utils::column_string_part;
+use crate::error::SidrError;
+use crate::utils::{
+    column_string_part, record_user_name, record_user_sid, report_host_name, sid_from_db_path,
+};
 use ::function_name::named;
 use log::{debug, error, info, trace};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::{collections::HashMap, str, string::String};
 
 macro_rules! function_path {
@@ -43,6 +48,8 @@ pub struct ColumnPair {
 pub enum OutputFormat {
     Csv,
     Json,
+    Parquet,
+    Sqlite,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -69,6 +76,21 @@ pub struct ReportsCfg {
     pub reports: Vec<ReportCfg>,
 }
 
+/// Built-in configuration describing the File, Internet History and Activity History reports.
+pub const DEFAULT_REPORTS_CFG: &str = include_str!("reports_cfg.yaml");
+
+impl ReportsCfg {
+    pub fn from_yaml(yaml: &str) -> Result<Self, SidrError> {
+        serde_yaml::from_str(yaml).map_err(|e| SidrError::Cfg(format!("{e}")))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SidrError> {
+        let yaml = std::fs::read_to_string(path)
+            .map_err(|e| SidrError::Cfg(format!("Can't read '{}': {e}", path.to_string_lossy())))?;
+        Self::from_yaml(&yaml)
+    }
+}
+
 //--------------------------------------------------------------------
 use chrono::{DateTime, TimeZone, Utc};
 
@@ -110,9 +132,12 @@ impl ConstrainedField {
 }
 
 pub trait FieldReader {
-    fn get_used_columns(&mut self, columns: &[ColumnPair]) -> Vec<ConstrainedField>;
-    fn init(&mut self) -> bool;
-    fn next(&mut self) -> bool;
+    fn get_used_columns(
+        &mut self,
+        columns: &[ColumnPair],
+    ) -> Result<Vec<ConstrainedField>, SidrError>;
+    fn init(&mut self) -> Result<bool, SidrError>;
+    fn next(&mut self) -> Result<bool, SidrError>;
     fn get_int(&mut self, id: &FldId) -> Option<i64>;
     fn get_str(&mut self, id: &FldId) -> Option<String>;
     fn get_guid(&mut self, id: &FldId) -> Option<String>;
@@ -128,8 +153,8 @@ use utils::{find_guid, from_utf16};
 
 const CACHE_SIZE_ENTRIES: usize = 10;
 
-fn field_size(col_type: u32, size: u32) -> u32 {
-    match col_type {
+fn field_size(col_type: u32, size: u32) -> Result<u32, SidrError> {
+    Ok(match col_type {
         ESE_coltypUnsignedByte => 1,
         ESE_coltypShort => 2,
         ESE_coltypLong => 4,
@@ -145,13 +170,16 @@ fn field_size(col_type: u32, size: u32) -> u32 {
         ESE_coltypLongLong => 8,
         ESE_coltypGUID => 16,
         ESE_coltypUnsignedShort => 2,
-        _ => panic!("{col_type} - unknown field type"),
-    }
+        _ => {
+            return Err(SidrError::Corrupt(format!(
+                "{col_type} - unknown field type"
+            )))
+        }
+    })
 }
 
 pub struct EseReader {
     pub jdb: Box<EseParser<BufReader<File>>>,
-    filename: String,
     table: u64,
     tablename: String,
     col_infos: HashMap<String, (u32, u32)>,
@@ -168,35 +196,40 @@ fn get_column<T: FromBytes + num::NumCast>(
             Some(v) => num::cast::<_, i64>(T::from_bytes(&v)),
             None => None,
         },
-        Err(_e) => panic!("Error: {_e}"),
+        Err(e) => {
+            error!("Error: {e}");
+            None
+        }
     }
 }
 
 impl EseReader {
     #[named]
-    pub fn new(filename: &str, tablename: &str) -> Self {
+    pub fn new(filename: &str, tablename: &str) -> Result<Self, SidrError> {
         info!("{}: {filename}/{tablename}", function_path!());
-        let jdb = Box::new(EseParser::load_from_path(CACHE_SIZE_ENTRIES, filename).unwrap());
-        let table = jdb.open_table(tablename).unwrap();
+        let jdb = Box::new(EseParser::load_from_path(CACHE_SIZE_ENTRIES, filename)?);
+        let table = jdb.open_table(tablename)?;
 
-        EseReader {
+        Ok(EseReader {
             jdb,
             table,
             tablename: tablename.to_string(),
-            filename: filename.to_string(),
             col_infos: HashMap::<String, (u32, u32)>::new(),
             rec_no: 0,
-        }
+        })
     }
 }
 
 impl FieldReader for EseReader {
     #[named]
-    fn get_used_columns(&mut self, columns: &[ColumnPair]) -> Vec<ConstrainedField> {
+    fn get_used_columns(
+        &mut self,
+        columns: &[ColumnPair],
+    ) -> Result<Vec<ConstrainedField>, SidrError> {
         trace!("{}", function_path!());
         let mut used_cols = Vec::<ConstrainedField>::with_capacity(columns.len());
         let tablename = &self.tablename;
-        let cols = self.jdb.get_columns(tablename).unwrap();
+        let cols = self.jdb.get_columns(tablename)?;
         let col_infos = &mut self.col_infos;
         let mut idx = 0_usize;
         for col_pair in columns {
@@ -210,7 +243,7 @@ impl FieldReader for EseReader {
                     Some(col_info) => {
                         col_infos.insert(
                             col_pair.title.clone(),
-                            (col_info.id, field_size(col_info.typ, col_info.cbmax)),
+                            (col_info.id, field_size(col_info.typ, col_info.cbmax)?),
                         );
                         used_cols.push(ConstrainedField::new(
                             &col_pair.title,
@@ -219,34 +252,36 @@ impl FieldReader for EseReader {
                         ));
                         idx += 1;
                     }
-                    None => panic!(
-                        "Could not find '{name}' column in '{tablename}' table in '{}'",
-                        self.filename
-                    ),
+                    None => {
+                        return Err(SidrError::MissingColumn {
+                            column: name,
+                            table: tablename.to_string(),
+                        })
+                    }
                 }
             }
         }
 
-        used_cols
+        Ok(used_cols)
     }
 
     #[named]
-    fn init(&mut self) -> bool {
+    fn init(&mut self) -> Result<bool, SidrError> {
         trace!("{}", function_path!());
         self.rec_no = 0;
-        self.jdb.move_row(self.table, ESE_MoveFirst).unwrap()
+        Ok(self.jdb.move_row(self.table, ESE_MoveFirst)?)
     }
 
     //#[named]
-    fn next(&mut self) -> bool {
+    fn next(&mut self) -> Result<bool, SidrError> {
         //trace!("{}", function_path!());
         let ok = if self.rec_no > 0 {
-            self.jdb.move_row(self.table, ESE_MoveNext).unwrap()
+            self.jdb.move_row(self.table, ESE_MoveNext)?
         } else {
             true
         };
         self.rec_no += 1;
-        ok
+        Ok(ok)
     }
 
     fn get_datetime(&mut self, id: &FldId) -> Option<DateTime<Utc>> {
@@ -254,10 +289,7 @@ impl FieldReader for EseReader {
             return None;
         }
 
-        let r = self
-            .jdb
-            .get_column(self.table, self.col_infos[id].0)
-            .unwrap();
+        let r = self.jdb.get_column(self.table, self.col_infos[id].0).ok()?;
         if let Some(v) = r {
             if let Ok(val) = v.clone().try_into() {
                 let vartime = f64::from_le_bytes(val);
@@ -272,11 +304,10 @@ impl FieldReader for EseReader {
                             st.wMinute as u32,
                             st.wSecond as u32,
                         )
-                        .single()
-                        .unwrap(); // this is obviously not the right function! I didn't know what the right one was off the top of my head. We need to include the time component. also needs to be something that returns a DateTime.
+                        .single()?; // this is obviously not the right function! I didn't know what the right one was off the top of my head. We need to include the time component. also needs to be something that returns a DateTime.
                     return Some(datetime);
                 } else {
-                    let filetime = u64::from_le_bytes(v.try_into().unwrap());
+                    let filetime = u64::from_le_bytes(v.try_into().ok()?);
                     let datetime = get_date_time_from_filetime(filetime);
                     return Some(datetime);
                 }
@@ -295,7 +326,10 @@ impl FieldReader for EseReader {
             2 => get_column::<i16>(&*self.jdb, self.table, fld_id),
             4 => get_column::<i32>(&*self.jdb, self.table, fld_id),
             8 => get_column::<i64>(&*self.jdb, self.table, fld_id),
-            _ => panic!("{id} - {fld_size} wrong size of int field"),
+            _ => {
+                error!("{id} - {fld_size} wrong size of int field");
+                None
+            }
         }
     }
 
@@ -305,7 +339,10 @@ impl FieldReader for EseReader {
         }
         match self.jdb.get_column(self.table, self.col_infos[id].0) {
             Ok(r) => r.map(|v| from_utf16(v.as_slice())),
-            Err(_e) => panic!("{id} - error: {_e}"),
+            Err(e) => {
+                error!("{id} - error: {e}");
+                None
+            }
         }
     }
 
@@ -332,38 +369,38 @@ type Session<'connection> = OwningHandle<Box<Connection>, Box<Statement<'connect
 
 pub struct SqlReader<'a> {
     last_work_id: u64,
+    // first row of the next WorkId, read by the previous call to next()
+    pending: Option<(i64, ColCode, sqlite::Value)>,
     code_col_dict: CodeColDict,
     row_values: RefCell<SqlRow>,
     session: Session<'a>,
 }
 
 impl SqlReader<'_> {
-    pub fn new(db_path: &str) -> Self {
-        let conn = Connection::open_with_flags(db_path, OpenFlags::new().with_read_only()).unwrap();
+    pub fn new(db_path: &str) -> Result<Self, SidrError> {
+        let conn = Connection::open_with_flags(db_path, OpenFlags::new().with_read_only())?;
         let sql = "select WorkId, * from SystemIndex_1_PropertyStore order by WorkId";
-        let session = Session::new_with_fn(Box::new(conn), unsafe {
-            |x| Box::new((*x).prepare(sql).unwrap())
-        });
+        let session = Session::try_new(Box::new(conn), |x| unsafe {
+            (*x).prepare(sql).map(Box::new)
+        })?;
 
-        SqlReader {
+        Ok(SqlReader {
             session,
             row_values: RefCell::new(SqlRow::new()),
             code_col_dict: CodeColDict::new(),
             last_work_id: 0,
-        }
+            pending: None,
+        })
     }
 
     fn first_row(&mut self) -> bool {
         self.last_work_id = 0;
+        self.pending = None;
         self.session.reset().is_ok()
     }
 
-    fn next_row(&mut self) -> bool {
-        if let Ok(result) = self.session.next() {
-            result == State::Row
-        } else {
-            false
-        }
+    fn next_row(&mut self) -> Result<bool, SidrError> {
+        Ok(self.session.next()? == State::Row)
     }
 
     fn read<T: sqlite::ReadableWithIndex, U: sqlite::ColumnIndex>(
@@ -373,16 +410,11 @@ impl SqlReader<'_> {
         self.session.read(index)
     }
 
-    fn store_value(&mut self, code: &ColCode) {
+    fn store_value(&mut self, code: &ColCode, value: sqlite::Value) {
         let code_col = &self.code_col_dict;
 
-        if code_col.contains_key(code) {
-            let value = match self.read::<sqlite::Value, _>("Value") {
-                Ok(x) => x,
-                Err(e) => panic!("{}", e),
-            };
-
-            for cc in code_col.get_vec(code).unwrap() {
+        if let Some(fields) = code_col.get_vec(code) {
+            for cc in fields {
                 let col_name = &cc.name;
                 debug!("{col_name} => {value:?}");
                 self.row_values
@@ -404,7 +436,10 @@ impl SqlReader<'_> {
 
 impl<'a> FieldReader for SqlReader<'a> {
     #[named]
-    fn get_used_columns(&mut self, columns: &[ColumnPair]) -> Vec<ConstrainedField> {
+    fn get_used_columns(
+        &mut self,
+        columns: &[ColumnPair],
+    ) -> Result<Vec<ConstrainedField>, SidrError> {
         trace!("{}", function_path!());
 
         let code_col_dict: CodeColDict = CodeColDict::from_iter(
@@ -435,24 +470,29 @@ impl<'a> FieldReader for SqlReader<'a> {
             .flat_iter()
             .for_each(|(k, v)| self.code_col_dict.insert(k.clone(), v.clone()));
 
-        used_cols
+        Ok(used_cols)
     }
 
     #[named]
-    fn init(&mut self) -> bool {
+    fn init(&mut self) -> Result<bool, SidrError> {
         trace!("{}", function_path!());
-        self.first_row()
+        Ok(self.first_row())
     }
 
     #[named]
-    fn next(&mut self) -> bool {
+    fn next(&mut self) -> Result<bool, SidrError> {
         let mut work_id = 0;
 
         self.row_values.borrow_mut().clear();
-        while self.next_row() {
-            let wi = match self.read::<i64, _>("WorkId") {
-                Ok(x) => x,
-                Err(e) => panic!("{}", e),
+        loop {
+            let (wi, code, value) = match self.pending.take() {
+                Some(row) => row,
+                None if self.next_row()? => (
+                    self.read::<i64, _>("WorkId")?,
+                    self.read::<ColName, _>("ColumnId")?,
+                    self.read::<sqlite::Value, _>("Value")?,
+                ),
+                None => break,
             };
             if work_id == 0 {
                 work_id = wi;
@@ -465,15 +505,12 @@ impl<'a> FieldReader for SqlReader<'a> {
                     .insert("WorkId".to_string(), sqlite::Value::Integer(work_id));
                 self.last_work_id = wi as u64;
             } else if wi != work_id {
+                // the row belongs to the next record
+                self.pending = Some((wi, code, value));
                 break;
             }
 
-            let code = match self.read::<ColName, _>("ColumnId") {
-                Ok(x) => x,
-                Err(e) => panic!("{}", e),
-            };
-
-            self.store_value(&code);
+            self.store_value(&code, value);
         }
 
         debug!(
@@ -482,7 +519,7 @@ impl<'a> FieldReader for SqlReader<'a> {
             self.row_values
         );
 
-        !self.row_values.borrow_mut().is_empty()
+        Ok(!self.row_values.borrow_mut().is_empty())
     }
 
     fn get_datetime(self: &mut SqlReader<'a>, id: &FldId) -> Option<DateTime<Utc>> {
@@ -496,7 +533,10 @@ impl<'a> FieldReader for SqlReader<'a> {
                     Some(get_date_time_from_filetime(u64::from_bytes(&vec)))
                 }
                 sqlite::Value::Null => None,
-                _ => panic!("unexpected {v:?} for {id}"),
+                _ => {
+                    error!("unexpected {v:?} for {id}");
+                    None
+                }
             };
         }
 
@@ -513,7 +553,10 @@ impl<'a> FieldReader for SqlReader<'a> {
                 sqlite::Value::Integer(x) => Some(x),
                 sqlite::Value::Binary(vec) => Some(i64::from_bytes(&vec)),
                 sqlite::Value::Null => None,
-                _ => panic!("unexpected {v:?} for {id}"),
+                _ => {
+                    error!("unexpected {v:?} for {id}");
+                    None
+                }
             };
         }
 
@@ -529,7 +572,10 @@ impl<'a> FieldReader for SqlReader<'a> {
             return match v {
                 sqlite::Value::String(x) => Some(x),
                 sqlite::Value::Null => None,
-                _ => panic!("unexpected {v:?} for {id}"),
+                _ => {
+                    error!("unexpected {v:?} for {id}");
+                    None
+                }
             };
         }
 
@@ -545,10 +591,9 @@ impl<'a> FieldReader for SqlReader<'a> {
 }
 
 //--------------------------------------------------------------------
-use crate::report::{ReportFormat, ReportOutput, ReportProducer};
+use crate::report::ReportProducer;
 use evalexpr::{Context, ContextWithMutableVariables, IterateVariablesContext, Value};
 use report::Report;
-use std::path::Path;
 
 #[derive(Debug)]
 struct ReportColumn {
@@ -561,6 +606,14 @@ struct ReportColumn {
 }
 
 impl<R: Report + ?Sized> Report for Box<R> {
+    fn footer(&mut self) {
+        (**self).footer()
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        (**self).finish()
+    }
+
     fn create_new_row(&mut self) {
         (**self).create_new_row()
     }
@@ -573,6 +626,34 @@ impl<R: Report + ?Sized> Report for Box<R> {
         (**self).insert_int_val(f, n)
     }
 
+    fn insert_i64(&self, f: &str, n: i64) {
+        (**self).insert_i64(f, n)
+    }
+
+    fn insert_f64(&self, f: &str, x: f64) {
+        (**self).insert_f64(f, x)
+    }
+
+    fn insert_bool(&self, f: &str, b: bool) {
+        (**self).insert_bool(f, b)
+    }
+
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        (**self).insert_datetime(f, t)
+    }
+
+    fn insert_bytes(&self, f: &str, b: &[u8]) {
+        (**self).insert_bytes(f, b)
+    }
+
+    fn insert_list(&self, f: &str, l: Vec<String>) {
+        (**self).insert_list(f, l)
+    }
+
+    fn set_field(&self, f: &str) {
+        (**self).set_field(f)
+    }
+
     fn is_some_val_in_record(&self) -> bool {
         (**self).is_some_val_in_record()
     }
@@ -582,8 +663,12 @@ impl<R: Report + ?Sized> Report for Box<R> {
 pub fn do_reports(
     cfg: &ReportsCfg,
     reader: &mut dyn FieldReader,
+    report_prod: &ReportProducer,
+    dbpath: &Path,
     edb_database_state: Option<DbState>,
-) {
+    name_suffix: &str,
+    user_names: &HashMap<String, String>,
+) -> Result<(), SidrError> {
     //println!("FileReport: {}", cfg.title);
     struct ReportDef {
         title: String,
@@ -595,19 +680,30 @@ pub fn do_reports(
     }
     let mut reports: Vec<ReportDef> = Vec::new();
 
-    let report_format = match cfg.output_format {
-        OutputFormat::Csv => ReportFormat::Csv,
-        OutputFormat::Json => ReportFormat::Json,
-    };
-
-    let report_type = match cfg.output_type {
-        OutputType::ToStdout => ReportOutput::ToStdout,
-        OutputType::ToFile => ReportOutput::ToFile,
-    };
-
-    let rep_factory = ReportProducer::new(cfg.output_dir.as_ref(), report_format, report_type);
     let mut cached = HashMap::<String, String>::new();
 
+    // the records are labeled with their user like the built-in reports (see record_user_sid),
+    // the columns of the configuration are read afterwards so they take precedence
+    let db_sid = sid_from_db_path(dbpath);
+    let user_cols =
+        [("System_ItemUrl", "39"), ("System_FileOwner", "93")].map(|(name, sql)| ColumnPair {
+            title: name.to_string(),
+            kind: ColumnType::String,
+            edb: Column {
+                name: name.to_string(),
+                constraint: None,
+            },
+            sql: Column {
+                name: sql.to_string(),
+                constraint: None,
+            },
+        });
+    for col in user_cols {
+        if let Err(e) = reader.get_used_columns(&[col]) {
+            debug!("the records are not labeled with their user: {e}");
+        }
+    }
+
     for report in &cfg.reports {
         let output_filename_title = &report.output_filename;
         let mut output_filename = "".to_string();
@@ -626,9 +722,11 @@ pub fn do_reports(
                     .columns
                     .iter()
                     .find(|col| col.title == *output_filename_title)
-                    .unwrap_or_else(|| {
-                        panic!("No column for output_filename '{output_filename_title}'")
-                    });
+                    .ok_or_else(|| {
+                        SidrError::Cfg(format!(
+                            "No column for output_filename '{output_filename_title}'"
+                        ))
+                    })?;
 
                 let col_for_itemtype = ColumnPair {
                     title: system_itemtype.clone(),
@@ -643,14 +741,16 @@ pub fn do_reports(
                     },
                 };
 
-                let _columns = reader
-                    .get_used_columns(&[(*col_for_computername).clone(), col_for_itemtype.clone()]);
+                let _columns = reader.get_used_columns(&[
+                    (*col_for_computername).clone(),
+                    col_for_itemtype.clone(),
+                ])?;
 
-                if !reader.init() {
-                    panic!("reader.init() failed");
+                if !reader.init()? {
+                    return Err(SidrError::Corrupt("reader.init() failed".to_string()));
                 }
 
-                while reader.next() {
+                while reader.next()? {
                     if let Some(ref str) = reader.get_str(output_filename_title) {
                         if !str.is_empty() {
                             if let Some(ref item_type) = reader.get_str(&system_itemtype) {
@@ -671,16 +771,18 @@ pub fn do_reports(
                     .columns
                     .iter()
                     .find(|col| col.title == *output_filename_title)
-                    .unwrap_or_else(|| {
-                        panic!("No column for output_filename '{output_filename_title}'")
-                    });
-                let _columns = reader.get_used_columns(&[(*col_for_filename).clone()]);
-
-                if !reader.init() {
-                    panic!("reader.init() failed");
+                    .ok_or_else(|| {
+                        SidrError::Cfg(format!(
+                            "No column for output_filename '{output_filename_title}'"
+                        ))
+                    })?;
+                let _columns = reader.get_used_columns(&[(*col_for_filename).clone()])?;
+
+                if !reader.init()? {
+                    return Err(SidrError::Corrupt("reader.init() failed".to_string()));
                 }
 
-                while reader.next() {
+                while reader.next()? {
                     if let Some(ref str) = reader.get_str(output_filename_title) {
                         if !str.is_empty() {
                             output_filename = str.clone();
@@ -700,17 +802,19 @@ pub fn do_reports(
                 output_filename.to_string(),
             );
         }
-        let (_out_path, reporter) = rep_factory
-            .new_report(
-                Path::new(""),
-                &output_filename,
-                &report.title,
-                edb_database_state,
-            )
-            .unwrap();
-
-        let columns = get_used_columns(report, reader, &*reporter);
+        let (_out_path, reporter) = report_prod.new_report(
+            dbpath,
+            &report_host_name(output_filename.clone(), db_sid.as_deref()),
+            &format!("{}{name_suffix}", report.title),
+            edb_database_state,
+        )?;
+
+        let columns = get_used_columns(report, reader, &*reporter)?;
         info!("{} columns: {columns:?}", report.title);
+        reporter.set_field("UserSID");
+        if !user_names.is_empty() {
+            reporter.set_field("UserName");
+        }
 
         let constrained_columns = get_constrained_cols(&columns);
         info!("constrained_columns: {constrained_columns:?}");
@@ -731,7 +835,9 @@ pub fn do_reports(
             constrain: if let Some(ref expr) = report.constraint {
                 match evalexpr::build_operator_tree(expr) {
                     Ok(node) => Some(node),
-                    Err(e) => panic!("failed parsing of '{expr}': {e}"),
+                    Err(e) => {
+                        return Err(SidrError::Cfg(format!("failed parsing of '{expr}': {e}")))
+                    }
                 }
             } else {
                 None
@@ -740,17 +846,17 @@ pub fn do_reports(
     }
 
     let mut context = evalexpr::HashMapContext::new();
-    if !reader.init() {
-        panic!("reader.init() failed");
+    if !reader.init()? {
+        return Err(SidrError::Corrupt("reader.init() failed".to_string()));
     }
 
-    while reader.next() {
-        reports.iter().for_each(|r| {
+    while reader.next()? {
+        let user_sid = record_user_sid(reader, db_sid.as_deref());
+        let user_name = record_user_name(reader, user_sid.as_deref(), user_names);
+        for r in &reports {
             debug!("flag {} -> false", r.title);
-            context
-                .set_value(r.title.clone(), Value::Boolean(false))
-                .unwrap()
-        });
+            set_report_flag(&mut context, &r.title, false)?;
+        }
 
         'report: for report in &mut reports {
             if let Some(ref constr) = report.constrain {
@@ -769,10 +875,12 @@ pub fn do_reports(
                             };
                         }
                     }
-                    Err(e) => panic!(
-                        "failed evaluation of '{}' for report {}: {e}",
-                        constr, report.title
-                    ),
+                    Err(e) => {
+                        return Err(SidrError::Cfg(format!(
+                            "failed evaluation of '{}' for report {}: {e}",
+                            constr, report.title
+                        )))
+                    }
                 }
             }
 
@@ -806,8 +914,12 @@ pub fn do_reports(
                             Err(e) => error!("Eval constraint '{expr}' failed: {e}"),
                         };
                     } else {
-                        let col = report.columns.iter().find(|c| c.title == *col_id).unwrap();
-                        if !col.optional {
+                        let optional = report
+                            .columns
+                            .iter()
+                            .find(|c| c.title == *col_id)
+                            .is_some_and(|c| c.optional);
+                        if !optional {
                             debug!("skip None '{col_id}' with constraint in {}", report.title);
                             continue 'report;
                         }
@@ -817,9 +929,7 @@ pub fn do_reports(
 
             report.reporter.create_new_row();
             debug!("flag {} -> true", report.title);
-            context
-                .set_value(report.title.clone(), Value::Boolean(true))
-                .unwrap();
+            set_report_flag(&mut context, &report.title, true)?;
 
             for col in &report.columns {
                 if col.hidden {
@@ -853,27 +963,43 @@ pub fn do_reports(
                     }
                     ColumnType::Integer => {
                         if let Some(v) = reader.get_int(col_id) {
-                            report.reporter.insert_int_val(col.title.as_str(), v as u64);
+                            report.reporter.insert_i64(col.title.as_str(), v);
                         }
                     }
                     ColumnType::DateTime => {
                         if let Some(dt) = reader.get_datetime(col_id) {
-                            report
-                                .reporter
-                                .insert_str_val(col.title.as_str(), utils::format_date_time(dt));
+                            report.reporter.insert_datetime(col.title.as_str(), dt);
                         }
                     }
                     ColumnType::GUID => {
-                        if let Some(guid) = reader.get_guid(col_id) {
+                        if let Some(guid) = reader.get_guid(col_id).filter(|g| !g.is_empty()) {
                             report.reporter.insert_str_val(col.title.as_str(), guid);
                         }
                     }
                 }
             }
-
-            report.reporter.footer();
+            if let Some(sid) = &user_sid {
+                report.reporter.insert_str_val("UserSID", sid.clone());
+            }
+            if let Some(name) = &user_name {
+                report.reporter.insert_str_val("UserName", name.clone());
+            }
         }
     }
+    // all the reports are closed, even if one fails
+    let results: Vec<_> = reports.iter_mut().map(|r| r.reporter.finish()).collect();
+    results.into_iter().collect()
+}
+
+// Whether a row was written to the report, for the constraints of the next reports.
+fn set_report_flag(
+    context: &mut evalexpr::HashMapContext,
+    title: &str,
+    flag: bool,
+) -> Result<(), SidrError> {
+    context
+        .set_value(title.to_string(), Value::Boolean(flag))
+        .map_err(|e| SidrError::Cfg(format!("report {title}: {e}")))
 }
 
 fn get_autofilled_cols(
@@ -907,14 +1033,8 @@ fn get_constrained_cols(columns: &[ReportColumn]) -> HashMap<String, String> {
     let constrained_columns: HashMap<String, String> = columns
         .iter()
         .filter_map(|fld| {
-            if fld.constraint.is_some() {
-                Some((
-                    fld.title.clone(),
-                    fld.constraint.as_ref().unwrap().to_string(),
-                ))
-            } else {
-                None
-            }
+            let constraint = fld.constraint.as_ref()?;
+            Some((fld.title.clone(), constraint.to_string()))
         })
         .filter(|(_, constraint)| {
             KNOWN_CONSTRS
@@ -929,14 +1049,21 @@ fn get_used_columns(
     cfg: &ReportCfg,
     reader: &mut dyn FieldReader,
     reporter: &dyn Report,
-) -> Vec<ReportColumn> {
-    let used_cols = reader.get_used_columns(&cfg.columns);
+) -> Result<Vec<ReportColumn>, SidrError> {
+    let used_cols = reader.get_used_columns(&cfg.columns)?;
 
     let mut columns = Vec::<ReportColumn>::with_capacity(used_cols.len());
 
-    used_cols.iter().for_each(|fld| {
+    for fld in &used_cols {
         let title = &fld.name;
-        let kind = cfg.columns.iter().find(|c| c.title == *title).unwrap().kind;
+        let kind = cfg
+            .columns
+            .iter()
+            .find(|c| c.title == *title)
+            .ok_or_else(|| {
+                SidrError::Cfg(format!("unknown column {title} in report {}", cfg.title))
+            })?
+            .kind;
 
         columns.push(ReportColumn {
             title: title.clone(),
@@ -946,7 +1073,10 @@ fn get_used_columns(
             optional: fld.optional,
             _idx: fld.idx,
         });
-    });
+    }
+
+    // the readers return the columns in any order, write them in the order of the cfg
+    columns.sort_by_key(|c| cfg.columns.iter().position(|cc| cc.title == c.title));
 
     // call set_field for all fields used in cfg (even empty one)
     cfg.columns.iter().for_each(|cc| {
@@ -957,5 +1087,25 @@ fn get_used_columns(
         }
     });
 
-    columns
+    Ok(columns)
+}
+
+#[test]
+fn test_default_reports_cfg() {
+    let cfg = ReportsCfg::from_yaml(DEFAULT_REPORTS_CFG).unwrap();
+    let titles: Vec<&str> = cfg.reports.iter().map(|r| r.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        [
+            "Internet_History_Report",
+            "Activity_History_Report",
+            "File_Report"
+        ]
+    );
+    for report in &cfg.reports {
+        assert!(report
+            .columns
+            .iter()
+            .any(|c| c.title == report.output_filename));
+    }
 }
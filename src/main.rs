@@ -2,86 +2,269 @@
 
 extern crate bitflags;
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
 
+use std::any::Any;
+use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
-
-use simple_error::SimpleError;
+use std::io::{Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
 
+pub mod archive;
 pub mod ese;
-pub mod report;
+pub mod image;
+pub mod manifest;
+pub mod recover;
+pub mod registry;
 pub mod shared;
 pub mod sqlite;
-pub mod utils;
+pub mod sqlite_recover;
+pub use wsa_lib::{error, report, utils};
 
+use crate::archive::{archive_kind, extract_archive_dbs, has_archive_extension, ArchiveKind};
+use crate::error::SidrError;
 use crate::ese::*;
+use crate::image::{extract_search_dbs, image_files};
+use crate::manifest::{record_container, record_input, write_manifest};
 use crate::report::*;
+use crate::shared::{ParseOptions, WalMode};
 use crate::sqlite::*;
+use wsa_lib::{OutputFormat, OutputType, ReportsCfg, DEFAULT_REPORTS_CFG};
+
+// Databases (or directories) which could not be processed, summarized at the end of the run.
+type Failures = Vec<(PathBuf, SidrError)>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DbKind {
+    Ese,
+    Sqlite,
+}
+
+// ESE database header: checksum, then the 0x89ABCDEF signature and the file type (0 - database)
+const ESE_SIGNATURE: [u8; 4] = [0xEF, 0xCD, 0xAB, 0x89];
+const SQLITE_SIGNATURE: &[u8] = b"SQLite format 3\0";
+
+// Detects the database type from the file header, whatever the file name or extension is.
+fn db_kind(p: &Path) -> Option<DbKind> {
+    let mut header = [0u8; 16];
+    let mut f = fs::File::open(p).ok()?;
+    f.read_exact(&mut header).ok()?;
+    if header == SQLITE_SIGNATURE {
+        Some(DbKind::Sqlite)
+    } else if header[4..8] == ESE_SIGNATURE && header[12..16] == [0, 0, 0, 0] {
+        Some(DbKind::Ese)
+    } else {
+        None
+    }
+}
 
 fn dump(
     input_dir: &PathBuf,
     report_prod: &ReportProducer,
+    cfg: Option<&ReportsCfg>,
+    opts: &ParseOptions,
     status_logger: &mut Box<dyn Write>,
-) -> Result<(), SimpleError> {
+    failures: &mut Failures,
+) -> Result<(), SidrError> {
     let mut processed = 0;
-    match fs::read_dir(input_dir) {
-        Ok(dir) => {
-            for entry in dir.flatten() {
-                let p = entry.path();
-                let metadata = fs::metadata(&p).unwrap();
-                if metadata.is_dir() {
-                    dump(&p, report_prod, status_logger)?;
-                } else if is_valid_file(&p) {
-                    processed += 1;
-                    let ext = p
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .map(|s| s.to_lowercase());
-                    let _ = match ext.as_deref() {
-                        Some("edb") => ese_generate_report(&p, report_prod, status_logger),
-                        Some("db") => sqlite_generate_report(&p, report_prod, status_logger),
-                        _ => continue,
-                    };
+    let dir = match fs::read_dir(input_dir) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Could not read dir '{}': {e}", input_dir.to_string_lossy());
+            failures.push((input_dir.clone(), e.into()));
+            return Ok(());
+        }
+    };
+    // in the same order from one run to the other, whatever the file system is
+    let mut paths: Vec<PathBuf> = dir.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for p in paths {
+        let metadata = match fs::metadata(&p) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                eprintln!("Could not read '{}': {e}", p.to_string_lossy());
+                failures.push((p, e.into()));
+                continue;
+            }
+        };
+        if metadata.is_dir() {
+            dump(&p, report_prod, cfg, opts, status_logger, failures)?;
+        } else if let Some(kind) = has_archive_extension(&p)
+            .then(|| archive_kind(&p))
+            .flatten()
+        {
+            if let Err(e) = dump_archive(&p, kind, report_prod, cfg, opts, status_logger, failures)
+            {
+                eprintln!("Failed to process archive '{}': {e}", p.to_string_lossy());
+                failures.push((p, e));
+            }
+        } else if opts.any_name || is_valid_name(&p) {
+            if let Some(kind) = db_kind(&p) {
+                // with --any-name, the other SQLite databases (Windows-gather.db, ...) are skipped
+                if kind == DbKind::Sqlite && !is_valid_name(&p) && !sqlite_is_search_db(&p) {
+                    continue;
                 }
+                processed += 1;
+                dump_db(&p, kind, report_prod, cfg, opts, status_logger, failures);
             }
         }
-        Err(e) => {
-            panic!(
-                "Could not read dir '{}': {e}",
-                input_dir
-                    .to_str()
-                    .unwrap_or_else(|| "Could not print dir name")
-            )
-        }
     }
     if processed > 0 {
         writeln!(
             status_logger,
             "\nFound {} Windows Search database(s)",
             &processed.to_string()
-        )
-        .map_err(|e| SimpleError::new(format!("{e}")))
-        .unwrap();
+        )?;
     }
 
     Ok(())
 }
 
-fn is_valid_file(p: &PathBuf) -> bool {
-    let is_valid_name = p
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .map_or(false, |name| {
-            let name = name.to_ascii_lowercase();
-            name == "windows" || name.starts_with("s-1-")
-        });
-    let is_valid_ext = p.extension().and_then(|e| e.to_str()).map_or(false, |ext| {
-        let ext = ext.to_ascii_lowercase();
-        ext == "edb" || ext == "db"
-    });
-    is_valid_name && is_valid_ext
+fn dump_db(
+    p: &Path,
+    kind: DbKind,
+    report_prod: &ReportProducer,
+    cfg: Option<&ReportsCfg>,
+    opts: &ParseOptions,
+    status_logger: &mut Box<dyn Write>,
+    failures: &mut Failures,
+) {
+    record_input(p, kind, report_prod);
+    // a corrupt database must not stop the processing of the other ones
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        process_db(p, kind, report_prod, cfg, opts, status_logger)
+    }))
+    .unwrap_or_else(|e| Err(SidrError::Panic(panic_message(e))));
+    if let Err(e) = result {
+        eprintln!("Failed to process '{}': {e}", p.to_string_lossy());
+        failures.push((p.to_path_buf(), e));
+    }
+}
+
+fn process_db(
+    p: &Path,
+    kind: DbKind,
+    report_prod: &ReportProducer,
+    cfg: Option<&ReportsCfg>,
+    opts: &ParseOptions,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    match (kind, cfg) {
+        (DbKind::Ese, Some(cfg)) => {
+            ese_generate_cfg_report(p, cfg, report_prod, opts, status_logger)
+        }
+        (DbKind::Ese, None) => ese_generate_report(p, report_prod, opts, status_logger),
+        (DbKind::Sqlite, Some(cfg)) => {
+            sqlite_generate_cfg_report(p, cfg, report_prod, opts, status_logger)
+        }
+        (DbKind::Sqlite, None) => sqlite_generate_report(p, report_prod, opts, status_logger),
+    }
+}
+
+// Extracts the Windows Search directories of the NTFS volumes of a disk image
+// to a temporary directory and reports the databases found there.
+fn dump_image(
+    image: &Path,
+    report_prod: &ReportProducer,
+    cfg: Option<&ReportsCfg>,
+    opts: &ParseOptions,
+    status_logger: &mut Box<dyn Write>,
+    failures: &mut Failures,
+) -> Result<(), SidrError> {
+    writeln!(
+        status_logger,
+        "Extracting Windows Search databases from {}",
+        image.to_string_lossy()
+    )?;
+    let (format, segments) = image_files(image);
+    record_container(&segments, format, report_prod);
+    let tmp = tempdir::TempDir::new("sidr_image")?;
+    let files = extract_search_dbs(image, tmp.path(), status_logger)?;
+    if files.is_empty() {
+        return Err(SidrError::Corrupt(
+            "No Windows Search directory found in the image".to_string(),
+        ));
+    }
+    let report_prod = report_prod.with_sources(files);
+    dump(
+        &tmp.path().to_path_buf(),
+        &report_prod,
+        cfg,
+        opts,
+        status_logger,
+        failures,
+    )
+}
+
+// Extracts the directories of a zip or tar(.gz) archive holding Windows Search databases
+// to a temporary directory and reports the databases found there.
+// Fails if the archive has no database.
+fn dump_archive(
+    archive: &Path,
+    kind: ArchiveKind,
+    report_prod: &ReportProducer,
+    cfg: Option<&ReportsCfg>,
+    opts: &ParseOptions,
+    status_logger: &mut Box<dyn Write>,
+    failures: &mut Failures,
+) -> Result<(), SidrError> {
+    let format = match kind {
+        ArchiveKind::Zip => "zip",
+        ArchiveKind::Tar => "tar",
+        ArchiveKind::TarGz => "tar.gz",
+    };
+    record_container(&[archive.to_path_buf()], format, report_prod);
+    let tmp = tempdir::TempDir::new("sidr_archive")?;
+    let is_db = |p: &Path| is_valid_name(p) && has_db_extension(p);
+    let files = extract_archive_dbs(archive, kind, tmp.path(), &is_db)?;
+    if files.is_empty() {
+        return Err(SidrError::Input(
+            "no Windows Search database in the archive".to_string(),
+        ));
+    }
+    writeln!(
+        status_logger,
+        "Extracted {} file(s) from {}",
+        files.len(),
+        archive.to_string_lossy()
+    )?;
+    let report_prod = report_prod.with_sources(files);
+    dump(
+        &tmp.path().to_path_buf(),
+        &report_prod,
+        cfg,
+        opts,
+        status_logger,
+        failures,
+    )
+}
+
+fn panic_message(e: Box<dyn Any + Send>) -> String {
+    match e.downcast::<String>() {
+        Ok(s) => *s,
+        Err(e) => match e.downcast::<&str>() {
+            Ok(s) => s.to_string(),
+            Err(_) => "panic".to_string(),
+        },
+    }
+}
+
+// Names of the databases looked for in the input directories (unless --any-name is given).
+fn is_valid_name(p: &Path) -> bool {
+    p.file_stem().and_then(|s| s.to_str()).is_some_and(|name| {
+        let name = name.to_ascii_lowercase();
+        name == "windows" || name.starts_with("s-1-")
+    })
+}
+
+// The databases are looked for in archives by their name, even with --any-name, since
+// telling them from their header would mean extracting everything.
+fn has_db_extension(p: &Path) -> bool {
+    p.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("edb") || e.eq_ignore_ascii_case("db"))
 }
 
 /// Copyright 2025, LevelBlue
@@ -105,14 +288,18 @@ fn is_valid_file(p: &PathBuf) -> bool {
 /// HOSTNAME_ReportName_DateTime.json|csv.
 ///
 /// HOSTNAME is extracted from the database.
+///
+/// The reports can also be described by a YAML configuration passed with --cfg
+/// (run `sidr --print-cfg` to get the built-in configuration as a starting point).
 
 #[derive(Parser)]
 #[command(author, version, about, long_about)]
 struct Cli {
-    /// Path to input directory (which will be recursively scanned for Windows.edb and Windows.db).
-    indir: PathBuf,
+    /// Paths to input database files, zip or tar(.gz) archives or directories (which will be recursively scanned for Windows.edb and Windows.db, including inside archives). Files are recognized by their header, whatever their name is.
+    #[arg(required_unless_present = "print_cfg", value_name = "INPUT")]
+    inputs: Vec<PathBuf>,
 
-    /// Output report format
+    /// Output report format. l2tcsv (log2timeline/Plaso CSV) has one line per date of each record, bodyfile (Sleuth Kit bodyfile, for mactime) one line per record with the accessed, modified and created dates. parquet (Apache Parquet, typed columns) is written to files only. sqlite appends the reports to sidr_reports.sqlite in the output directory, a table per report.
     #[arg(short, long, value_enum, default_value_t = ReportFormat::Json)]
     format: ReportFormat,
 
@@ -123,37 +310,275 @@ struct Cli {
     /// Path to the directory where reports will be created (will be created if not present). Default is the current directory.
     #[arg(short, long, value_name = "OUTPUT DIRECTORY")]
     outdir: Option<PathBuf>,
+
+    /// Path to a YAML report configuration. Output format, report type and output directory given on the command line take precedence over the ones in the configuration.
+    #[arg(short, long, value_name = "CONFIG FILE")]
+    cfg: Option<PathBuf>,
+
+    /// Report every property column of Windows.edb, not only the ones used by the built-in reports. The reports of Windows.db always hold every property.
+    #[arg(long, conflicts_with = "cfg")]
+    all_properties: bool,
+
+    /// Also report deleted and orphaned records recovered from the pages of Windows.edb and the freelist and unallocated space of Windows.db, marked with Recovered=true.
+    #[arg(long, conflicts_with = "cfg")]
+    recover_deleted: bool,
+
+    /// How the write-ahead log (Windows.db-wal) is handled: auto and apply report Windows.db with the committed WAL frames, ignore without them, both produces the two (the reports without WAL are suffixed with _without_WAL). Unless auto, a WAL Frames Report lists the property rows changed by the WAL frames.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = WalMode::Auto)]
+    wal: WalMode,
+
+    /// Process every ESE or SQLite database found in the input directories, not only the ones named Windows.edb, Windows.db or S-1-*.db (SQLite databases without the PropertyStore table, like Windows-gather.db, are skipped).
+    #[arg(long)]
+    any_name: bool,
+
+    /// The inputs are disk images (raw/dd or E01): the databases, logs and WAL files of \ProgramData\Microsoft\Search\Data\Applications\ are extracted from their NTFS volumes and reported with the image path.
+    #[arg(long)]
+    image: bool,
+
+    /// Directory with the offline SOFTWARE and/or SAM registry hives of the system, used to resolve the user SIDs to account names (UserName column).
+    #[arg(long, value_name = "DIR")]
+    registry: Option<PathBuf>,
+
+    /// Instead of the per-database reports, write a single Timeline report with one event per timestamp of every record (timestamp, source_db, host, report, WorkId, timestamp_kind, description), sorted chronologically across all the databases.
+    #[arg(long, conflicts_with = "cfg")]
+    timeline: bool,
+
+    /// Delimiter of the CSV reports: a single ASCII character, or "tab".
+    #[arg(long, value_name = "CHAR", default_value = ",", value_parser = CsvOptions::parse_delimiter, conflicts_with = "cfg")]
+    csv_delimiter: u8,
+
+    /// Which values of the CSV reports are quoted. necessary (RFC 4180) quotes the values holding a delimiter, a quote or a line break.
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = CsvQuoting::Necessary, conflicts_with = "cfg")]
+    csv_quoting: CsvQuoting,
+
+    /// Keep the line breaks of the values in the CSV reports (quoted, as RFC 4180 allows) instead of writing them as \n and \r.
+    #[arg(long, conflicts_with = "cfg")]
+    csv_preserve_newlines: bool,
+
+    /// Start the CSV reports with a UTF-8 byte order mark, for Excel to detect the encoding.
+    #[arg(long, conflicts_with = "cfg")]
+    csv_bom: bool,
+
+    /// Layout of the JSON reports: lines (a record per line), array (a JSON document per report, the array of its records) or pretty (a pretty-printed array).
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = JsonStyle::Lines, conflicts_with = "cfg")]
+    json_style: JsonStyle,
+
+    /// Reproducible reports: named HOSTNAME_ReportName_HASH.ext after the SHA-256 of the database (Timeline.ext with --timeline) instead of the time of the run, so that the same evidence gives byte-identical reports.
+    #[arg(long, conflicts_with = "cfg")]
+    deterministic: bool,
+
+    /// Print the built-in report configuration and exit.
+    #[arg(long)]
+    print_cfg: bool,
 }
 
-fn main() -> Result<(), SimpleError> {
-    let cli = Cli::parse();
+fn main() -> Result<(), SidrError> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).map_err(|e| SidrError::Cfg(format!("{e}")))?;
+
+    if cli.print_cfg {
+        print!("{DEFAULT_REPORTS_CFG}");
+        return Ok(());
+    }
+
+    let is_set = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let cfg = match cli.cfg {
+        Some(ref path) => {
+            let mut cfg = ReportsCfg::load(path)?;
+            if is_set("format") {
+                cfg.output_format = match cli.format {
+                    ReportFormat::Json => OutputFormat::Json,
+                    ReportFormat::Csv => OutputFormat::Csv,
+                    ReportFormat::Parquet => OutputFormat::Parquet,
+                    ReportFormat::Sqlite => OutputFormat::Sqlite,
+                    ReportFormat::L2tcsv | ReportFormat::Bodyfile => {
+                        return Err(SidrError::Cfg(
+                            "the l2tcsv and bodyfile formats can't be used with --cfg".to_string(),
+                        ))
+                    }
+                };
+            }
+            if is_set("report_type") {
+                cfg.output_type = match cli.report_type {
+                    ReportOutput::ToFile => OutputType::ToFile,
+                    ReportOutput::ToStdout => OutputType::ToStdout,
+                };
+            }
+            if let Some(ref outdir) = cli.outdir {
+                cfg.output_dir = outdir.to_string_lossy().into_owned();
+            }
+            Some(cfg)
+        }
+        None => None,
+    };
+
+    let output_dir = match (cli.outdir, &cfg) {
+        (Some(outdir), _) => outdir,
+        (None, Some(cfg)) => PathBuf::from(&cfg.output_dir),
+        (None, None) => std::env::current_dir()?,
+    };
+    let (format, report_type) = match cfg {
+        Some(ref cfg) => (
+            match cfg.output_format {
+                OutputFormat::Json => ReportFormat::Json,
+                OutputFormat::Csv => ReportFormat::Csv,
+                OutputFormat::Parquet => ReportFormat::Parquet,
+                OutputFormat::Sqlite => ReportFormat::Sqlite,
+            },
+            match cfg.output_type {
+                OutputType::ToFile => ReportOutput::ToFile,
+                OutputType::ToStdout => ReportOutput::ToStdout,
+            },
+        ),
+        None => (cli.format, cli.report_type),
+    };
+    if cli.timeline && matches!(format, ReportFormat::Bodyfile) {
+        return Err(SidrError::Cfg(
+            "--timeline can't be used with the bodyfile format, mactime sorts the bodyfiles"
+                .to_string(),
+        ));
+    }
 
-    let output_dir = match cli.outdir {
-        Some(outdir) => outdir,
-        None => std::env::current_dir().map_err(|e| SimpleError::new(format!("{e}")))?,
+    let user_names = match cli.registry {
+        Some(ref dir) => registry::load_user_names(dir)?,
+        None => HashMap::new(),
     };
 
-    write_reports(&output_dir, cli.format, cli.report_type, &cli.indir)?;
+    // inputs are required unless --print-cfg is present
+    let opts = ParseOptions {
+        all_properties: cli.all_properties,
+        recover_deleted: cli.recover_deleted,
+        wal: cli.wal,
+        any_name: cli.any_name,
+        images: cli.image,
+        user_names,
+        timeline: cli.timeline,
+        csv: CsvOptions {
+            delimiter: cli.csv_delimiter,
+            quoting: cli.csv_quoting,
+            preserve_newlines: cli.csv_preserve_newlines,
+            bom: cli.csv_bom,
+        },
+        json_style: cli.json_style,
+        deterministic: cli.deterministic,
+    };
+    let failed = write_reports(
+        &output_dir,
+        format,
+        report_type,
+        cfg.as_ref(),
+        &opts,
+        &cli.inputs,
+    )?;
+    if failed > 0 {
+        process::exit(exitcode::DATAERR);
+    }
     Ok(())
 }
 
 fn write_reports(
-    rep_dir: &PathBuf,
+    rep_dir: &Path,
     format: ReportFormat,
     report_type: ReportOutput,
-    input_dir: &PathBuf,
-) -> Result<(), SimpleError> {
-    let rep_producer = ReportProducer::new(rep_dir.as_path(), format, report_type);
+    cfg: Option<&ReportsCfg>,
+    opts: &ParseOptions,
+    inputs: &[PathBuf],
+) -> Result<usize, SidrError> {
+    let start_time = chrono::Utc::now();
+    let mut rep_producer = ReportProducer::new(rep_dir, format, report_type)?
+        .with_csv_options(opts.csv.clone())
+        .with_json_style(opts.json_style);
+    if opts.deterministic {
+        rep_producer = rep_producer.with_deterministic();
+    }
+    if opts.timeline {
+        rep_producer = rep_producer.with_timeline();
+    }
     let mut status_logger: Box<dyn std::io::Write> = match report_type {
         ReportOutput::ToStdout => Box::new(std::io::sink()),
         ReportOutput::ToFile => Box::new(std::io::stdout()),
     };
-    dump(&input_dir, &rep_producer, &mut status_logger)?;
-    Ok(())
+    let mut failures = Failures::new();
+    for input in inputs {
+        if opts.images {
+            if let Err(e) = dump_image(
+                input,
+                &rep_producer,
+                cfg,
+                opts,
+                &mut status_logger,
+                &mut failures,
+            ) {
+                eprintln!("Failed to process image '{}': {e}", input.to_string_lossy());
+                failures.push((input.clone(), e));
+            }
+        } else if input.is_dir() {
+            dump(
+                input,
+                &rep_producer,
+                cfg,
+                opts,
+                &mut status_logger,
+                &mut failures,
+            )?;
+        } else if let Some(kind) = archive_kind(input) {
+            if let Err(e) = dump_archive(
+                input,
+                kind,
+                &rep_producer,
+                cfg,
+                opts,
+                &mut status_logger,
+                &mut failures,
+            ) {
+                eprintln!(
+                    "Failed to process archive '{}': {e}",
+                    input.to_string_lossy()
+                );
+                failures.push((input.clone(), e));
+            }
+        } else {
+            // files given explicitly are processed whatever their name is
+            match db_kind(input) {
+                Some(kind) => dump_db(
+                    input,
+                    kind,
+                    &rep_producer,
+                    cfg,
+                    opts,
+                    &mut status_logger,
+                    &mut failures,
+                ),
+                None => {
+                    let e = if input.exists() {
+                        SidrError::Input("not an ESE or SQLite database".to_string())
+                    } else {
+                        std::io::Error::from(std::io::ErrorKind::NotFound).into()
+                    };
+                    eprintln!("Skipping '{}': {e}", input.to_string_lossy());
+                    failures.push((input.clone(), e));
+                }
+            }
+        }
+    }
+    if let Some(path) = rep_producer.write_timeline()? {
+        writeln!(status_logger, "{}", path.to_string_lossy())?;
+    }
+    if report_type == ReportOutput::ToFile {
+        let path = write_manifest(rep_dir, &rep_producer, start_time, &failures)?;
+        writeln!(status_logger, "{}", path.to_string_lossy())?;
+    }
+    if !failures.is_empty() {
+        eprintln!("\n{} database(s) could not be processed:", failures.len());
+        for (p, e) in &failures {
+            eprintln!("  {}: {e}", p.to_string_lossy());
+        }
+    }
+    Ok(failures.len())
 }
 
 #[test]
-fn warn_dirty() {
+fn warn_dirty() -> Result<(), SidrError> {
     use ese_parser_lib::ese_parser::EseParser;
 
     let report_dir = PathBuf::from("tests/testdata");
@@ -161,40 +586,77 @@ fn warn_dirty() {
         report_dir.as_path(),
         ReportFormat::Csv,
         ReportOutput::ToFile,
-    );
+    )?;
     let ese_path = PathBuf::from("tests/testdata/Windows.edb");
     assert!(ese_path.exists());
     let jdb = Box::new(EseParser::load_from_path(10, ese_path).unwrap());
     let edb_database_state = jdb.get_database_state();
     assert!(rep_producer.is_db_dirty(Some(edb_database_state)));
+    Ok(())
 }
 
 #[test]
 fn test_generate_reports() {
-    use glob::glob;
-    use goldenfile::Mint;
-
     let report_dir = PathBuf::from("tests/output");
     let input_dir = PathBuf::from("tests/testdata");
-    let goldenfiles_dir = PathBuf::from("tests/goldenfiles");
     let _ = write_reports(
         &report_dir,
         ReportFormat::Csv,
         ReportOutput::ToFile,
-        &input_dir,
+        None,
+        &ParseOptions::default(),
+        std::slice::from_ref(&input_dir),
     );
     let _ = write_reports(
         &report_dir,
         ReportFormat::Json,
         ReportOutput::ToFile,
-        &input_dir,
+        None,
+        &ParseOptions::default(),
+        std::slice::from_ref(&input_dir),
     );
+    check_goldenfiles(&report_dir, Path::new("tests/goldenfiles"));
+}
+
+// The built-in report configuration run through do_reports (--cfg).
+#[test]
+fn test_generate_cfg_reports() {
+    let report_dir = PathBuf::from("tests/output_cfg");
+    let input_dir = PathBuf::from("tests/testdata");
+    for (format, output_format) in [
+        (ReportFormat::Csv, OutputFormat::Csv),
+        (ReportFormat::Json, OutputFormat::Json),
+    ] {
+        let mut cfg = ReportsCfg::from_yaml(DEFAULT_REPORTS_CFG).unwrap();
+        cfg.output_format = output_format;
+        cfg.output_dir = report_dir.to_string_lossy().into_owned();
+        let _ = write_reports(
+            &report_dir,
+            format,
+            ReportOutput::ToFile,
+            Some(&cfg),
+            &ParseOptions::default(),
+            std::slice::from_ref(&input_dir),
+        );
+    }
+    check_goldenfiles(&report_dir, Path::new("tests/goldenfiles/cfg"));
+    let _ = fs::remove_dir(report_dir);
+}
 
-    match fs::read_dir(goldenfiles_dir.clone()) {
+// Compares the reports of report_dir with the goldenfiles named {ComputerName}_{ReportType}.{ext}.
+#[cfg(test)]
+fn check_goldenfiles(report_dir: &Path, goldenfiles_dir: &Path) {
+    use glob::glob;
+    use goldenfile::Mint;
+
+    match fs::read_dir(goldenfiles_dir) {
         Ok(ok_goldenfile_dir) => {
             let mut mint_dir = Mint::new(".");
             for goldenfile in ok_goldenfile_dir.flatten() {
                 let p = goldenfile.path();
+                if p.is_dir() {
+                    continue;
+                }
                 let ext: &str = p.extension().unwrap().to_str().unwrap();
                 if let Some(f) = p.file_name() {
                     if let Some(f) = f.to_str() {
@@ -202,7 +664,10 @@ fn test_generate_reports() {
                         let computerName = parts[0];
                         let reportType = parts[1];
                         for entry in glob(
-                            format!("tests/output/{computerName}_{reportType}*.{ext}").as_str(),
+                            report_dir
+                                .join(format!("{computerName}_{reportType}*.{ext}"))
+                                .to_str()
+                                .unwrap(),
                         )
                         .unwrap()
                         {
@@ -220,4 +685,406 @@ fn test_generate_reports() {
             panic!("Failed to read goldenfiles directory.")
         }
     }
+    let _ = fs::remove_file(report_dir.join(manifest::MANIFEST));
+}
+
+#[test]
+fn test_wal_reports() {
+    use glob::glob;
+
+    let report_dir = tempdir::TempDir::new("sidr_wal").unwrap();
+    let opts = ParseOptions {
+        wal: WalMode::Both,
+        ..Default::default()
+    };
+    write_reports(
+        report_dir.path(),
+        ReportFormat::Csv,
+        ReportOutput::ToFile,
+        None,
+        &opts,
+        &[PathBuf::from("tests/wal")],
+    )
+    .unwrap();
+    let read_report = |name: &str| {
+        let pattern = report_dir.path().join(format!("WAL-TEST_{name}_2*.csv"));
+        let paths: Vec<_> = glob(&pattern.to_string_lossy())
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(paths.len(), 1, "{name}");
+        fs::read_to_string(&paths[0]).unwrap()
+    };
+
+    let with_wal = read_report("File_Report");
+    assert!(with_wal.contains("C:\\newer.txt"));
+    assert!(with_wal.contains("C:\\wal_only.txt"));
+    assert!(!with_wal.contains("C:\\uncommit.txt"));
+    let without_wal = read_report("File_Report_without_WAL");
+    assert!(without_wal.contains("C:\\old.txt"));
+    assert!(!without_wal.contains("C:\\wal_only.txt"));
+
+    let frames = read_report("WAL_Frames_Report");
+    assert!(frames.contains("1,33,System.ItemPathDisplay,C:\\old.txt,db,,2,true,false,true"));
+    assert!(
+        frames.contains("3,33,System.ItemPathDisplay,C:\\uncommit.txt,wal,3,2,false,true,false")
+    );
+}
+
+// --cfg reports honor the WAL mode and are recorded in the manifest.
+#[test]
+fn test_wal_cfg_reports() {
+    use glob::glob;
+
+    let report_dir = tempdir::TempDir::new("sidr_wal_cfg").unwrap();
+    let opts = ParseOptions {
+        wal: WalMode::Both,
+        ..Default::default()
+    };
+    let cfg = ReportsCfg::from_yaml(DEFAULT_REPORTS_CFG).unwrap();
+    write_reports(
+        report_dir.path(),
+        ReportFormat::Csv,
+        ReportOutput::ToFile,
+        Some(&cfg),
+        &opts,
+        &[PathBuf::from("tests/wal")],
+    )
+    .unwrap();
+    let read_report = |name: &str| {
+        let pattern = report_dir.path().join(format!("WAL-TEST_{name}_2*.csv"));
+        let paths: Vec<_> = glob(&pattern.to_string_lossy())
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(paths.len(), 1, "{name}");
+        fs::read_to_string(&paths[0]).unwrap()
+    };
+
+    let with_wal = read_report("File_Report");
+    assert!(with_wal.contains("C:\\wal_only.txt"));
+    let without_wal = read_report("File_Report_without_WAL");
+    assert!(without_wal.contains("C:\\old.txt"));
+    assert!(!without_wal.contains("C:\\wal_only.txt"));
+
+    let manifest = fs::read_to_string(report_dir.path().join(manifest::MANIFEST)).unwrap();
+    assert!(manifest.contains("\"report\": \"File_Report\""));
+    assert!(manifest.contains("\"report\": \"File_Report_without_WAL\""));
+    assert!(read_report("WAL_Frames_Report").contains("C:\\wal_only.txt"));
+}
+
+// The built-in and the --cfg reports of a per-user database are labeled the same way.
+#[test]
+fn test_user_sid_reports() {
+    use glob::glob;
+
+    let sid = "S-1-5-21-1000";
+    let input_dir = tempdir::TempDir::new("sidr_user").unwrap();
+    fs::copy(
+        "tests/wal/Windows.db",
+        input_dir.path().join(format!("{sid}.db")),
+    )
+    .unwrap();
+    let cfg = ReportsCfg::from_yaml(DEFAULT_REPORTS_CFG).unwrap();
+    for cfg in [None, Some(&cfg)] {
+        let report_dir = tempdir::TempDir::new("sidr_user_reports").unwrap();
+        write_reports(
+            report_dir.path(),
+            ReportFormat::Csv,
+            ReportOutput::ToFile,
+            cfg,
+            &ParseOptions::default(),
+            &[input_dir.path().to_path_buf()],
+        )
+        .unwrap();
+        let pattern = report_dir
+            .path()
+            .join(format!("WAL-TEST_{sid}_File_Report_2*.csv"));
+        let paths: Vec<_> = glob(&pattern.to_string_lossy())
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(paths.len(), 1, "{pattern:?}");
+        let report = fs::read_to_string(&paths[0]).unwrap();
+        let header = report.lines().next().unwrap();
+        assert!(header.ends_with(",UserSID"), "{header}");
+        assert!(report.lines().skip(1).all(|l| l.ends_with(sid)));
+    }
+}
+
+// --registry names the users of the built-in and the --cfg reports alike.
+#[test]
+fn test_user_name_reports() {
+    use glob::glob;
+
+    let sid = "S-1-5-21-1000";
+    let input_dir = tempdir::TempDir::new("sidr_user_name").unwrap();
+    fs::copy(
+        "tests/wal/Windows.db",
+        input_dir.path().join(format!("{sid}.db")),
+    )
+    .unwrap();
+    let opts = ParseOptions {
+        user_names: [(sid.to_string(), "alice".to_string())]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    let cfg = ReportsCfg::from_yaml(DEFAULT_REPORTS_CFG).unwrap();
+    for cfg in [None, Some(&cfg)] {
+        let report_dir = tempdir::TempDir::new("sidr_user_name_reports").unwrap();
+        write_reports(
+            report_dir.path(),
+            ReportFormat::Csv,
+            ReportOutput::ToFile,
+            cfg,
+            &opts,
+            &[input_dir.path().to_path_buf()],
+        )
+        .unwrap();
+        let pattern = report_dir
+            .path()
+            .join(format!("WAL-TEST_{sid}_File_Report_2*.csv"));
+        let paths: Vec<_> = glob(&pattern.to_string_lossy())
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(paths.len(), 1, "{pattern:?}");
+        let report = fs::read_to_string(&paths[0]).unwrap();
+        let header = report.lines().next().unwrap();
+        assert!(header.ends_with(",UserSID,UserName"), "{header}");
+        assert!(report
+            .lines()
+            .skip(1)
+            .all(|l| l.ends_with(&format!("{sid},alice"))));
+    }
+}
+
+// --any-name picks the renamed databases up for the built-in and the --cfg reports alike.
+#[test]
+fn test_any_name_reports() {
+    use glob::glob;
+
+    let input_dir = tempdir::TempDir::new("sidr_any_name").unwrap();
+    fs::copy("tests/wal/Windows.db", input_dir.path().join("case123.bin")).unwrap();
+    let cfg = ReportsCfg::from_yaml(DEFAULT_REPORTS_CFG).unwrap();
+    for cfg in [None, Some(&cfg)] {
+        for any_name in [false, true] {
+            let report_dir = tempdir::TempDir::new("sidr_any_name_reports").unwrap();
+            let opts = ParseOptions {
+                any_name,
+                ..Default::default()
+            };
+            write_reports(
+                report_dir.path(),
+                ReportFormat::Csv,
+                ReportOutput::ToFile,
+                cfg,
+                &opts,
+                &[input_dir.path().to_path_buf()],
+            )
+            .unwrap();
+            let pattern = report_dir.path().join("WAL-TEST_File_Report_2*.csv");
+            let reports = glob(&pattern.to_string_lossy()).unwrap().flatten().count();
+            assert_eq!(reports, any_name as usize, "with cfg: {}", cfg.is_some());
+        }
+    }
+}
+
+#[test]
+fn test_db_kind() {
+    let dir = tempdir::TempDir::new("sidr_kind").unwrap();
+    let renamed = dir.path().join("case123.bin");
+    fs::copy("tests/wal/Windows.db", &renamed).unwrap();
+    assert_eq!(db_kind(&renamed), Some(DbKind::Sqlite));
+    assert!(!is_valid_name(&renamed));
+    assert!(sqlite_is_search_db(&renamed));
+    assert_eq!(db_kind(Path::new("tests/wal/Windows.db-wal")), None);
+
+    let gather = dir.path().join("Windows-gather.db");
+    ::sqlite::Connection::open(&gather)
+        .unwrap()
+        .execute("create table SystemIndex_1_Gather (WorkId integer)")
+        .unwrap();
+    assert_eq!(db_kind(&gather), Some(DbKind::Sqlite));
+    assert!(!sqlite_is_search_db(&gather));
+
+    let edb = dir.path().join("HOST42_Windows.edb");
+    let mut header = vec![0u8; 4096];
+    header[4..8].copy_from_slice(&ESE_SIGNATURE);
+    fs::write(&edb, &header).unwrap();
+    assert_eq!(db_kind(&edb), Some(DbKind::Ese));
+    // streaming file
+    header[12] = 1;
+    fs::write(&edb, &header).unwrap();
+    assert_eq!(db_kind(&edb), None);
+
+    assert!(is_valid_name(Path::new("Windows.edb")));
+    assert!(is_valid_name(Path::new("S-1-5-21-1000.db")));
+    // names of the databases in archives
+    assert!(has_db_extension(Path::new("S-1-5-21-1000.db")));
+    assert!(!is_valid_name(Path::new("thumbs.db")));
+    assert!(!has_db_extension(Path::new("Windows.xml")));
+}
+
+#[test]
+fn test_archive_reports() {
+    use glob::glob;
+
+    let dir = tempdir::TempDir::new("sidr_archive").unwrap();
+    let archive = dir.path().join("collection.zip");
+    let windows_dir = "uploads/auto/C%3A/ProgramData/Microsoft/Search/Data/Applications/Windows";
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    for name in ["Windows.db", "Windows.db-wal"] {
+        zip.start_file(format!("{windows_dir}/{name}"), Default::default())
+            .unwrap();
+        zip.write_all(&fs::read(Path::new("tests/wal").join(name)).unwrap())
+            .unwrap();
+    }
+    zip.finish().unwrap();
+
+    let report_dir = dir.path().join("reports");
+    let opts = ParseOptions {
+        wal: WalMode::Apply,
+        ..Default::default()
+    };
+    let failed = write_reports(
+        &report_dir,
+        ReportFormat::Json,
+        ReportOutput::ToFile,
+        None,
+        &opts,
+        std::slice::from_ref(&archive),
+    )
+    .unwrap();
+    assert_eq!(failed, 0);
+    let pattern = report_dir.join("WAL-TEST_File_Report_2*.json");
+    let paths: Vec<_> = glob(&pattern.to_string_lossy())
+        .unwrap()
+        .flatten()
+        .collect();
+    assert_eq!(paths.len(), 1);
+    let report = fs::read_to_string(&paths[0]).unwrap();
+    assert!(report.contains("C:\\\\wal_only.txt"));
+    assert!(report.contains(&format!(
+        "{{\"Archive\":\"{}\",\"ArchiveFile\":\"{windows_dir}/Windows.db\",",
+        archive.to_string_lossy()
+    )));
+    // the archive is hashed, then its databases where they come from
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(report_dir.join(manifest::MANIFEST)).unwrap()).unwrap();
+    let container = &manifest["inputs"][0];
+    assert_eq!(container["path"], archive.to_string_lossy().as_ref());
+    assert_eq!(container["format"], "zip");
+    assert_eq!(
+        container["sha256"],
+        utils::file_hashes(&archive).unwrap().sha256
+    );
+    let input = &manifest["inputs"][1];
+    let source = format!("{}!{windows_dir}/Windows.db", archive.to_string_lossy());
+    assert_eq!(input["path"], source.as_str());
+    assert_eq!(input["sidecars"][0]["path"], format!("{source}-wal"));
+    assert!(input["reports"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|r| r["report"] == "File_Report" && r["rows"].as_u64() > Some(0)));
+
+    // an archive without a database is a failure, in a directory too
+    let empty_dir = dir.path().join("collections");
+    fs::create_dir(&empty_dir).unwrap();
+    let mut zip = zip::ZipWriter::new(fs::File::create(empty_dir.join("empty.zip")).unwrap());
+    zip.start_file("readme.txt", Default::default()).unwrap();
+    zip.finish().unwrap();
+    let failed = write_reports(
+        &dir.path().join("empty_reports"),
+        ReportFormat::Json,
+        ReportOutput::ToFile,
+        None,
+        &opts,
+        &[empty_dir],
+    )
+    .unwrap();
+    assert_eq!(failed, 1);
+}
+
+#[test]
+fn test_timeline_report() {
+    use glob::glob;
+
+    let report_dir = tempdir::TempDir::new("sidr_timeline").unwrap();
+    let opts = ParseOptions {
+        timeline: true,
+        ..Default::default()
+    };
+    write_reports(
+        report_dir.path(),
+        ReportFormat::Csv,
+        ReportOutput::ToFile,
+        None,
+        &opts,
+        &[PathBuf::from("tests/testdata")],
+    )
+    .unwrap();
+    let paths: Vec<_> = glob(&report_dir.path().join("*.csv").to_string_lossy())
+        .unwrap()
+        .flatten()
+        .collect();
+    assert_eq!(paths.len(), 1);
+    assert!(paths[0]
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("Timeline_"));
+    let timeline = fs::read_to_string(&paths[0]).unwrap();
+    let mut lines = timeline.lines();
+    assert_eq!(
+        lines.next(),
+        Some("timestamp,source_db,host,report,WorkId,timestamp_kind,description")
+    );
+    let timestamps: Vec<&str> = lines.map(|l| l.split(',').next().unwrap()).collect();
+    assert!(!timestamps.is_empty());
+    assert!(timestamps.windows(2).all(|w| w[0] <= w[1]));
+    assert!(timeline.contains(
+        ",DESKTOP-O47KVAD,File_Report,1,System_DateModified,C:\\ProgramData\\Microsoft\\Windows\\Start Menu\n"
+    ));
+}
+
+#[test]
+fn test_deterministic_reports() {
+    let opts = ParseOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    let run = || {
+        let report_dir = tempdir::TempDir::new("sidr_deterministic").unwrap();
+        write_reports(
+            report_dir.path(),
+            ReportFormat::Json,
+            ReportOutput::ToFile,
+            None,
+            &opts,
+            &[PathBuf::from("tests/testdata")],
+        )
+        .unwrap();
+        let mut reports: Vec<(String, Vec<u8>)> = fs::read_dir(report_dir.path())
+            .unwrap()
+            .flatten()
+            // the manifest records when the run was
+            .filter(|e| e.file_name() != manifest::MANIFEST)
+            .map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                (name, fs::read(e.path()).unwrap())
+            })
+            .collect();
+        reports.sort();
+        reports
+    };
+    let reports = run();
+    let hash = utils::file_hashes(Path::new("tests/testdata/Windows.db"))
+        .unwrap()
+        .sha256;
+    let name = format!("DESKTOP-O47KVAD_File_Report_{}.json", &hash[..16]);
+    assert!(reports.iter().any(|(n, _)| *n == name), "{name}");
+    assert_eq!(reports, run());
 }
@@ -0,0 +1,302 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+
+use crate::error::SidrError;
+use crate::report::{InputRecord, ReportProducer};
+use crate::sqlite_recover::wal_path;
+use crate::utils::{file_hashes, format_date_time, FileHashes};
+use crate::{DbKind, Failures};
+
+// in the output directory, overwritten by the next runs
+pub const MANIFEST: &str = "manifest.json";
+
+// "MSS0001A.log" -> ("MSS", true), "MSS.log" -> ("MSS", false)
+fn parse_log_name(name: &str) -> Option<(&str, bool)> {
+    let stem = name
+        .strip_suffix(".log")
+        .or_else(|| name.strip_suffix(".LOG"))?;
+    if stem.len() < 3 || !stem.is_char_boundary(3) {
+        return None;
+    }
+    let (base, gen) = stem.split_at(3);
+    // MSStmp.log and the reserved logs are not part of the sequence
+    if !gen.is_empty() && (gen.len() < 5 || !gen.chars().all(|c| c.is_ascii_hexdigit())) {
+        return None;
+    }
+    Some((base, !gen.is_empty()))
+}
+
+// The checkpoint file (MSS.chk) and the transaction logs (MSS.log, MSS00001.log, ...)
+// of an ESE database. Windows Search uses the MSS base name, other ESE applications edb.
+fn ese_logs(f: &Path) -> Vec<PathBuf> {
+    let Some(Ok(entries)) = f.parent().map(std::fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    files.sort();
+    let name = |p: &PathBuf| p.file_name().map(|n| n.to_string_lossy().into_owned());
+    // the checkpoint file names the log sequence, otherwise take the one of the current log
+    let chk = files
+        .iter()
+        .find(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("chk")))
+        .cloned();
+    let base = match &chk {
+        Some(chk) => chk.file_stem().map(|s| s.to_string_lossy().into_owned()),
+        None => files.iter().filter_map(name).find_map(|n| {
+            parse_log_name(&n)
+                .filter(|(_, gen)| !gen)
+                .map(|(base, _)| base.to_string())
+        }),
+    };
+    let Some(base) = base else {
+        return Vec::new();
+    };
+    let logs = files.iter().filter(|p| {
+        name(p)
+            .is_some_and(|n| parse_log_name(&n).is_some_and(|(b, _)| b.eq_ignore_ascii_case(&base)))
+    });
+    chk.iter().chain(logs).cloned().collect()
+}
+
+// Files next to the database which its reports depend on: the checkpoint and transaction
+// logs of Windows.edb, the WAL, shared memory and gather database of Windows.db.
+fn sidecars(p: &Path, kind: DbKind) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    match kind {
+        DbKind::Ese => {
+            files.extend(ese_logs(p));
+        }
+        DbKind::Sqlite => {
+            let mut shm = p.as_os_str().to_owned();
+            shm.push("-shm");
+            files.push(wal_path(p));
+            files.push(shm.into());
+            if let Some(stem) = p.file_stem() {
+                files.push(p.with_file_name(format!("{}-gather.db", stem.to_string_lossy())));
+            }
+        }
+    }
+    files.retain(|f| f.is_file());
+    files
+}
+
+// Hashes the database and its sidecar files, before they are parsed,
+// and records the database as an input of the run.
+fn hash(f: &Path) -> Option<FileHashes> {
+    match file_hashes(f) {
+        Ok(hashes) => Some(hashes),
+        Err(e) => {
+            eprintln!("Could not hash '{}': {e}", f.to_string_lossy());
+            None
+        }
+    }
+}
+
+// Hashes an archive or a disk image, before the databases are extracted from it,
+// and records it as an input of the run, the other segments of an E01 as its sidecars.
+pub(crate) fn record_container(files: &[PathBuf], format: &str, report_prod: &ReportProducer) {
+    let Some((p, segments)) = files.split_first() else {
+        return;
+    };
+    let sidecars = segments
+        .iter()
+        .filter_map(|f| Some((report_prod.source_db(f), hash(f)?)))
+        .collect();
+    report_prod.add_input(p, format, hash(p), sidecars);
+}
+
+pub(crate) fn record_input(p: &Path, kind: DbKind, report_prod: &ReportProducer) {
+    let hashes = hash(p);
+    let sidecars = sidecars(p, kind)
+        .iter()
+        .filter_map(|f| Some((report_prod.source_db(f), hash(f)?)))
+        .collect();
+    let format = match kind {
+        DbKind::Ese => "ESE",
+        DbKind::Sqlite => "SQLite",
+    };
+    report_prod.add_input(p, format, hashes, sidecars);
+}
+
+// path, size and hashes of an input file
+fn file_json(path: &str, hashes: Option<&FileHashes>) -> Map<String, Value> {
+    let mut m = Map::new();
+    m.insert("path".to_string(), path.into());
+    m.insert("size".to_string(), hashes.map(|h| h.size).into());
+    m.insert("md5".to_string(), hashes.map(|h| h.md5.clone()).into());
+    m.insert("sha1".to_string(), hashes.map(|h| h.sha1.clone()).into());
+    m.insert(
+        "sha256".to_string(),
+        hashes.map(|h| h.sha256.clone()).into(),
+    );
+    m
+}
+
+fn input_json(input: &InputRecord, error: Option<&SidrError>) -> Value {
+    let mut m = file_json(&input.source_db, input.hashes.as_ref());
+    m.insert("format".to_string(), input.format.clone().into());
+    m.insert(
+        "db_state".to_string(),
+        input.db_state.map(|state| format!("{state:?}")).into(),
+    );
+    m.insert("hostname".to_string(), input.hostname.clone().into());
+    let sidecars: Vec<Value> = input
+        .sidecars
+        .iter()
+        .map(|(path, hashes)| file_json(path, Some(hashes)).into())
+        .collect();
+    m.insert("sidecars".to_string(), sidecars.into());
+    let reports: Vec<Value> = input
+        .reports
+        .iter()
+        .map(|r| {
+            json!({
+                "report": r.report,
+                "path": r.path.to_string_lossy(),
+                "rows": r.rows.get(),
+            })
+        })
+        .collect();
+    m.insert("reports".to_string(), reports.into());
+    m.insert("error".to_string(), error.map(|e| e.to_string()).into());
+    m.into()
+}
+
+// Writes the manifest of the run to the output directory, for the chain of custody
+// of the evidence: the inputs with their hashes and the reports made of them.
+pub fn write_manifest(
+    dir: &Path,
+    report_prod: &ReportProducer,
+    start_time: DateTime<Utc>,
+    failures: &Failures,
+) -> Result<PathBuf, SidrError> {
+    let inputs = report_prod.inputs();
+    let error = |p: &Path| failures.iter().find(|(f, _)| f == p).map(|(_, e)| e);
+    // directories, unreadable files, ... which are not an input
+    let other_failures: Vec<Value> = failures
+        .iter()
+        .filter(|(f, _)| !inputs.iter().any(|input| input.path == *f))
+        .map(|(f, e)| json!({"path": f.to_string_lossy(), "error": e.to_string()}))
+        .collect();
+    let manifest = json!({
+        "tool": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "command_line": std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>(),
+        "start_time": format_date_time(start_time),
+        "end_time": format_date_time(Utc::now()),
+        "inputs": inputs
+            .iter()
+            .map(|input| input_json(input, error(&input.path)))
+            .collect::<Vec<_>>(),
+        "failures": other_failures,
+    });
+    let path = dir.join(MANIFEST);
+    let mut data = serde_json::to_string_pretty(&manifest).map_err(std::io::Error::from)?;
+    data.push('\n');
+    std::fs::write(&path, data)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{ReportFormat, ReportOutput};
+
+    #[test]
+    fn test_ese_logs() {
+        let dir = tempdir::TempDir::new("sidr_logs").unwrap();
+        for name in [
+            "Windows.edb",
+            "MSS.chk",
+            "MSS.log",
+            "MSS0000A.log",
+            "MSS0000B.log",
+            "MSStmp.log",
+            "MSSres00001.jrs",
+            "other.log",
+        ] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let names: Vec<_> = ese_logs(&dir.path().join("Windows.edb"))
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["MSS.chk", "MSS.log", "MSS0000A.log", "MSS0000B.log"]
+        );
+
+        assert_eq!(parse_log_name("edb.log"), Some(("edb", false)));
+        assert_eq!(parse_log_name("edb00001.log"), Some(("edb", true)));
+        assert_eq!(parse_log_name("edbtmp.log"), None);
+        assert_eq!(parse_log_name("Windows.edb"), None);
+    }
+
+    #[test]
+    fn test_write_manifest() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_manifest").unwrap();
+        let db = dir.path().join("Windows.db");
+        std::fs::write(&db, b"abc").unwrap();
+        std::fs::write(wal_path(&db), b"").unwrap();
+        let out = dir.path().join("reports");
+        let rp = ReportProducer::new(&out, ReportFormat::Csv, ReportOutput::ToFile)?;
+        record_input(&db, DbKind::Sqlite, &rp);
+        {
+            let (_, mut r) = rp.new_report(&db, "host", "File_Report", None).unwrap();
+            r.insert_int_val("WorkId", 1);
+            r.create_new_row();
+        }
+        let failures: Failures = vec![(dir.path().join("x.zip"), SidrError::Cfg("x".into()))];
+        let path = write_manifest(&out, &rp, Utc::now(), &failures).unwrap();
+        assert_eq!(path, out.join(MANIFEST));
+
+        let manifest: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(manifest["version"], env!("CARGO_PKG_VERSION"));
+        let input = &manifest["inputs"][0];
+        assert_eq!(input["path"], db.to_string_lossy().as_ref());
+        assert_eq!(input["size"], 3);
+        assert_eq!(input["md5"], "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(input["format"], "SQLite");
+        assert_eq!(input["hostname"], "host");
+        assert_eq!(input["db_state"], Value::Null);
+        assert_eq!(input["error"], Value::Null);
+        assert_eq!(
+            input["sidecars"][0]["path"],
+            wal_path(&db).to_string_lossy().as_ref()
+        );
+        assert_eq!(input["sidecars"][0]["size"], 0);
+        assert_eq!(input["reports"][0]["report"], "File_Report");
+        assert_eq!(input["reports"][0]["rows"], 1);
+        assert_eq!(manifest["failures"][0]["error"], failures[0].1.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_container() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_container").unwrap();
+        let segments = [dir.path().join("disk.E01"), dir.path().join("disk.E02")];
+        std::fs::write(&segments[0], b"abc").unwrap();
+        std::fs::write(&segments[1], b"").unwrap();
+        let out = dir.path().join("reports");
+        let rp = ReportProducer::new(&out, ReportFormat::Csv, ReportOutput::ToFile)?;
+        record_container(&segments, "E01", &rp);
+        let failures: Failures = vec![(segments[0].clone(), SidrError::Cfg("x".into()))];
+        let path = write_manifest(&out, &rp, Utc::now(), &failures).unwrap();
+
+        let manifest: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let input = &manifest["inputs"][0];
+        assert_eq!(input["path"], segments[0].to_string_lossy().as_ref());
+        assert_eq!(input["format"], "E01");
+        assert_eq!(input["size"], 3);
+        assert_eq!(
+            input["sidecars"][0]["path"],
+            segments[1].to_string_lossy().as_ref()
+        );
+        assert_eq!(input["error"], failures[0].1.to_string());
+        assert_eq!(manifest["failures"], Value::Array(Vec::new()));
+        Ok(())
+    }
+}
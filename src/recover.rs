@@ -0,0 +1,670 @@
+// Recovery of deleted/orphaned records from the raw pages of an ESE database.
+//
+// The EseDb trait only exposes live rows, so the pages are read straight from the file.
+// The column catalog still comes from EseParser (jdb.get_columns()).
+//
+// Records come from the page tags (live and flagged as deleted) and are carved from the
+// unallocated space of the leaf pages, between the record data and the tag array, where
+// the records moved or deleted by page reorganizations are left.
+
+use crate::error::SidrError;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use ese_parser_lib::ese_trait::*;
+
+// offset of cbPageSize in the database header
+const DB_HEADER_PAGE_SIZE_OFFSET: usize = 236;
+const DEFAULT_PAGE_SIZE: u32 = 4096;
+// pages >= 16KB use an extended page header and 15 bit page tags
+const LARGE_PAGE_SIZE: u32 = 16384;
+
+const PAGE_FLAG_LEAF: u32 = 0x0002;
+const PAGE_FLAG_EMPTY: u32 = 0x0008;
+const PAGE_FLAG_SPACE_TREE: u32 = 0x0020;
+const PAGE_FLAG_INDEX: u32 = 0x0040;
+const PAGE_FLAG_LONG_VALUE: u32 = 0x0080;
+
+const TAG_FLAG_DELETED: u16 = 0x2;
+const TAG_FLAG_COMPRESSED: u16 = 0x4;
+
+// MSysObjects
+const CATALOG_OBJID: u32 = 2;
+const CATALOG_TYPE_TABLE: u16 = 1;
+
+const TAGGED_FLAG_COMPRESSED: u8 = 0x02;
+const TAGGED_FLAG_LONG_VALUE: u8 = 0x04;
+const TAGGED_FLAG_MULTI_VALUE: u8 = 0x08;
+
+#[derive(Debug)]
+pub struct RecoveredRecord {
+    pub page: u32,
+    // the page tag was flagged as deleted
+    pub deleted: bool,
+    // column name -> raw value, same as EseDb::get_column() returns
+    pub values: HashMap<String, Vec<u8>>,
+}
+
+struct PageHeader {
+    objid_fdp: u32,
+    // start of the unallocated space, from the end of the page header
+    mic_free: u16,
+    tags: u16,
+    flags: u32,
+}
+
+// A record of a leaf page.
+struct LeafRecord {
+    page: u32,
+    deleted: bool,
+    // key of a record carved from the unallocated space, None for the page tags
+    carved_key: Option<Vec<u8>>,
+    data: Vec<u8>,
+}
+
+pub struct EsePages {
+    file: File,
+    page_size: u32,
+    pages: u32,
+}
+
+impl EsePages {
+    pub fn open(f: &Path) -> Result<Self, SidrError> {
+        let mut file = File::open(f)?;
+        let mut header = [0u8; DB_HEADER_PAGE_SIZE_OFFSET + 4];
+        file.read_exact(&mut header)?;
+        let page_size = match read_u32(&header, DB_HEADER_PAGE_SIZE_OFFSET) {
+            Some(0) | None => DEFAULT_PAGE_SIZE,
+            Some(size) => size,
+        };
+        let len = file.metadata()?.len();
+        // the database header and its shadow copy take the first two pages
+        let pages = (len / page_size as u64).saturating_sub(2) as u32;
+        Ok(EsePages {
+            file,
+            page_size,
+            pages,
+        })
+    }
+
+    fn read_page(&mut self, pgno: u32) -> Result<Vec<u8>, SidrError> {
+        let mut page = vec![0u8; self.page_size as usize];
+        self.file
+            .seek(SeekFrom::Start((pgno as u64 + 1) * self.page_size as u64))?;
+        self.file.read_exact(&mut page)?;
+        Ok(page)
+    }
+
+    // Leaf records of every page which belongs (or used to belong) to the objid tree,
+    // and with carve set the records left in their unallocated space.
+    fn leaf_records(&mut self, objid: u32, carve: bool) -> Result<Vec<LeafRecord>, SidrError> {
+        let mut records = Vec::new();
+        for pgno in 1..=self.pages {
+            let page = self.read_page(pgno)?;
+            let header = match page_header(&page) {
+                Some(h) => h,
+                None => continue,
+            };
+            let skip = PAGE_FLAG_SPACE_TREE | PAGE_FLAG_INDEX | PAGE_FLAG_LONG_VALUE;
+            if header.objid_fdp != objid
+                || header.flags & PAGE_FLAG_LEAF == 0
+                || header.flags & skip != 0
+            {
+                continue;
+            }
+            for (deleted, data) in leaf_entries(&page, self.page_size, &header) {
+                records.push(LeafRecord {
+                    page: pgno,
+                    deleted,
+                    carved_key: None,
+                    data,
+                });
+            }
+            if carve {
+                let large_page = self.page_size >= LARGE_PAGE_SIZE;
+                let region = unallocated_region(&page, self.page_size, &header);
+                for (key, data) in carve_entries(region, large_page) {
+                    records.push(LeafRecord {
+                        page: pgno,
+                        deleted: true,
+                        carved_key: Some(key),
+                        data,
+                    });
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    // objid of the table, from the catalog records
+    fn table_objid(&mut self, table: &str) -> Result<u32, SidrError> {
+        for LeafRecord { deleted, data, .. } in self.leaf_records(CATALOG_OBJID, false)? {
+            if deleted {
+                continue;
+            }
+            // fixed columns: ObjidTable (Long), Type (Short), variable column 128: Name
+            let (Some(objid), Some(typ)) = (read_u32(&data, 4), read_u16(&data, 8)) else {
+                continue;
+            };
+            if typ != CATALOG_TYPE_TABLE {
+                continue;
+            }
+            if let Some(name) = variable_columns(&data).and_then(|v| v.get(&128).cloned()) {
+                if name == table.as_bytes() {
+                    return Ok(objid);
+                }
+            }
+        }
+        Err(SidrError::Corrupt(format!(
+            "Can't find table {table} in the catalog"
+        )))
+    }
+}
+
+// Decodes every record of the table found in the raw pages (live, deleted and orphaned ones).
+// The carved records are reported as deleted.
+pub fn ese_recover_records(
+    f: &Path,
+    table: &str,
+    cols: &[ColumnInfo],
+) -> Result<Vec<RecoveredRecord>, SidrError> {
+    let mut pages = EsePages::open(f)?;
+    let objid = pages.table_objid(table)?;
+    let large_page = pages.page_size >= LARGE_PAGE_SIZE;
+    let records = pages
+        .leaf_records(objid, true)?
+        .into_iter()
+        .filter_map(|r| {
+            let values = decode_record(&r.data, cols, large_page)?;
+            // a carved record must hold the WorkID of its key
+            if let Some(key) = r.carved_key {
+                let work_id = values.get("WorkID").filter(|v| v.len() == 4)?;
+                if key != work_id_key(u32::from_le_bytes(work_id[..].try_into().ok()?)) {
+                    return None;
+                }
+            }
+            Some(RecoveredRecord {
+                page: r.page,
+                deleted: r.deleted,
+                values,
+            })
+        })
+        .filter(|r| !r.values.is_empty())
+        .collect();
+    Ok(records)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn page_header(page: &[u8]) -> Option<PageHeader> {
+    Some(PageHeader {
+        objid_fdp: read_u32(page, 24)?,
+        mic_free: read_u16(page, 32)?,
+        tags: read_u16(page, 34)?,
+        flags: read_u32(page, 36)?,
+    })
+}
+
+// (deleted, record data) of the leaf page entries, tag 0 is the page's common key.
+fn leaf_entries(page: &[u8], page_size: u32, header: &PageHeader) -> Vec<(bool, Vec<u8>)> {
+    let mut entries = Vec::new();
+    if header.flags & PAGE_FLAG_EMPTY != 0 {
+        return entries;
+    }
+    let large_page = page_size >= LARGE_PAGE_SIZE;
+    let header_size = page_header_size(large_page);
+    let mask = if large_page { 0x7FFF } else { 0x1FFF };
+    for i in 1..header.tags as usize {
+        let tag_offset = match (page_size as usize).checked_sub(4 * (i + 1)) {
+            Some(o) => o,
+            None => break,
+        };
+        let (Some(size), Some(offset)) =
+            (read_u16(page, tag_offset), read_u16(page, tag_offset + 2))
+        else {
+            break;
+        };
+        let start = header_size + (offset & mask) as usize;
+        let Some(entry) = page.get(start..start + (size & mask) as usize) else {
+            continue;
+        };
+        // small pages keep the flags in the tag, large pages in the first word of the entry
+        let flags = if large_page {
+            read_u16(entry, 0).unwrap_or(0) >> 13
+        } else {
+            offset >> 13
+        };
+        if let Some(data) = entry_data(entry, flags, large_page) {
+            entries.push((flags & TAG_FLAG_DELETED != 0, data.to_vec()));
+        }
+    }
+    entries
+}
+
+fn page_header_size(large_page: bool) -> usize {
+    if large_page {
+        80
+    } else {
+        40
+    }
+}
+
+// The space between the record data and the tag array, the whole page for an emptied page.
+fn unallocated_region<'a>(page: &'a [u8], page_size: u32, header: &PageHeader) -> &'a [u8] {
+    let header_size = page_header_size(page_size >= LARGE_PAGE_SIZE);
+    let (start, end) = if header.flags & PAGE_FLAG_EMPTY != 0 {
+        (header_size, page.len())
+    } else {
+        (
+            header_size + header.mic_free as usize,
+            page.len().saturating_sub(4 * header.tags as usize),
+        )
+    };
+    page.get(start..end).unwrap_or_default()
+}
+
+// Normalized key of the PropertyStore primary index: a Long with its sign bit flipped,
+// big endian, after the 0x7F prefix of non NULL values.
+fn work_id_key(work_id: u32) -> Vec<u8> {
+    let mut key = vec![0x7F];
+    key.extend((work_id ^ 0x8000_0000).to_be_bytes());
+    key
+}
+
+// (key, data) of the leaf entries left in an unallocated region, found by their WorkID key.
+// The data of an entry runs up to the next one, so the last tagged value may keep some
+// of the bytes which follow the record.
+fn carve_entries(region: &[u8], large_page: bool) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let key_mask = if large_page { 0x1FFF } else { 0xFFFF };
+    let key_size = work_id_key(0).len();
+    let starts: Vec<usize> = (0..region.len().saturating_sub(2 + key_size))
+        .filter(|&i| {
+            read_u16(region, i).is_some_and(|size| (size & key_mask) as usize == key_size)
+                && region[i + 2] == 0x7F
+        })
+        .collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &i)| {
+            let end = starts.get(n + 1).copied().unwrap_or(region.len());
+            let data_start = (i + 2 + key_size).min(end);
+            (
+                region[i + 2..i + 2 + key_size].to_vec(),
+                region[data_start..end].to_vec(),
+            )
+        })
+        .collect()
+}
+
+// Skips the key of a leaf entry.
+fn entry_data(entry: &[u8], flags: u16, large_page: bool) -> Option<&[u8]> {
+    let key_mask = if large_page { 0x1FFF } else { 0xFFFF };
+    let mut pos = 0;
+    if flags & TAG_FLAG_COMPRESSED != 0 {
+        // common key size
+        pos += 2;
+    }
+    let first = read_u16(entry, pos)?;
+    let local_key = if pos == 0 { first & key_mask } else { first };
+    pos += 2 + local_key as usize;
+    entry.get(pos..)
+}
+
+fn fixed_size(col: &ColumnInfo) -> Option<usize> {
+    match col.typ {
+        ESE_coltypBit | ESE_coltypUnsignedByte => Some(1),
+        ESE_coltypShort | ESE_coltypUnsignedShort => Some(2),
+        ESE_coltypLong | ESE_coltypUnsignedLong | ESE_coltypIEEESingle => Some(4),
+        ESE_coltypCurrency | ESE_coltypIEEEDouble | ESE_coltypDateTime | ESE_coltypLongLong => {
+            Some(8)
+        }
+        ESE_coltypGUID => Some(16),
+        ESE_coltypBinary | ESE_coltypText => Some(col.cbmax as usize),
+        _ => None,
+    }
+}
+
+// Variable size columns (128..=last variable column id) of a record.
+fn variable_columns(data: &[u8]) -> Option<HashMap<u32, Vec<u8>>> {
+    let last_var = *data.get(1)? as u32;
+    let var_offset = read_u16(data, 2)? as usize;
+    let mut values = HashMap::new();
+    if last_var < 128 {
+        return Some(values);
+    }
+    let count = (last_var - 127) as usize;
+    let var_data = var_offset + 2 * count;
+    let mut prev_end = 0usize;
+    for i in 0..count {
+        let end = read_u16(data, var_offset + 2 * i)?;
+        let is_null = end & 0x8000 != 0;
+        let end = (end & 0x7FFF) as usize;
+        if !is_null && end > prev_end {
+            let value = data.get(var_data + prev_end..var_data + end)?;
+            values.insert(128 + i as u32, value.to_vec());
+        }
+        prev_end = end;
+    }
+    Some(values)
+}
+
+// Decodes a PropertyStore record, returns None for data which doesn't look like a record.
+pub fn decode_record(
+    data: &[u8],
+    cols: &[ColumnInfo],
+    large_page: bool,
+) -> Option<HashMap<String, Vec<u8>>> {
+    let last_fixed = *data.first()? as u32;
+    let last_var = *data.get(1)? as u32;
+    let var_offset = read_u16(data, 2)? as usize;
+    if last_fixed > 127 || (last_var != 127 && !(128..=255).contains(&last_var)) {
+        return None;
+    }
+    let mut values = HashMap::new();
+    let by_id: HashMap<u32, &ColumnInfo> = cols.iter().map(|c| (c.id, c)).collect();
+
+    // fixed columns are stored back to back, followed by the NULL bitmap
+    let mut pos = 4;
+    let mut fixed = Vec::new();
+    for id in 1..=last_fixed {
+        let size = fixed_size(by_id.get(&id)?)?;
+        fixed.push((id, pos, size));
+        pos += size;
+    }
+    let null_bitmap = pos;
+    if null_bitmap + (last_fixed as usize).div_ceil(8) != var_offset {
+        return None;
+    }
+    for (i, (id, pos, size)) in fixed.into_iter().enumerate() {
+        let is_null = data.get(null_bitmap + i / 8)? & (1 << (i % 8)) != 0;
+        if !is_null {
+            values.insert(by_id[&id].name.clone(), data.get(pos..pos + size)?.to_vec());
+        }
+    }
+
+    for (id, value) in variable_columns(data)? {
+        if let Some(col) = by_id.get(&id) {
+            values.insert(col.name.clone(), value);
+        }
+    }
+
+    // tagged columns follow the variable ones: (id, offset) pairs, then the data
+    let var_count = last_var.saturating_sub(127) as usize;
+    let var_end = match var_count {
+        0 => 0,
+        n => (read_u16(data, var_offset + 2 * (n - 1))? & 0x7FFF) as usize,
+    };
+    let tagged = data.get(var_offset + 2 * var_count + var_end..)?;
+    let offset_mask = if large_page { 0x7FFF } else { 0x3FFF };
+    let dir_size = match read_u16(tagged, 2) {
+        Some(o) => (o & offset_mask) as usize,
+        None => return Some(values),
+    };
+    let count = dir_size / 4;
+    for i in 0..count {
+        let id = read_u16(tagged, 4 * i)? as u32;
+        let raw_offset = read_u16(tagged, 4 * i + 2)?;
+        let start = (raw_offset & offset_mask) as usize;
+        let end = if i + 1 < count {
+            (read_u16(tagged, 4 * i + 6)? & offset_mask) as usize
+        } else {
+            tagged.len()
+        };
+        let Some(col) = by_id.get(&id) else {
+            continue;
+        };
+        let value = tagged.get(start..end)?;
+        let has_flags = large_page || raw_offset & 0x4000 != 0;
+        let (flags, value) = match (has_flags, value.split_first()) {
+            (true, Some((flags, value))) => (*flags, value),
+            _ => (0, value),
+        };
+        if flags & TAGGED_FLAG_LONG_VALUE != 0 {
+            // the value is kept in the long value tree
+            continue;
+        }
+        let value = if flags & TAGGED_FLAG_MULTI_VALUE != 0 {
+            first_multi_value(value)?
+        } else {
+            value.to_vec()
+        };
+        let value = if flags & TAGGED_FLAG_COMPRESSED != 0 {
+            decompress(&value).unwrap_or(value)
+        } else {
+            value
+        };
+        values.insert(col.name.clone(), value);
+    }
+    Some(values)
+}
+
+// Multi-valued columns start with an array of value offsets.
+fn first_multi_value(value: &[u8]) -> Option<Vec<u8>> {
+    let first = (read_u16(value, 0)? & 0x7FFF) as usize;
+    let end = match read_u16(value, 2) {
+        Some(o) if first > 2 => (o & 0x7FFF) as usize,
+        _ => value.len(),
+    };
+    Some(value.get(first..end)?.to_vec())
+}
+
+// 7-bit compressed ASCII (1) and UTF-16 (2) values, the other schemes are left as is.
+pub fn decompress(value: &[u8]) -> Option<Vec<u8>> {
+    let (&header, packed) = value.split_first()?;
+    let wide = match header >> 3 {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    // number of used bits in the last byte
+    let last_bits = (header & 0x7) as usize + 1;
+    let bits = packed.len().checked_sub(1)? * 8 + last_bits;
+    let mut out = Vec::with_capacity(bits / 7 * 2);
+    for i in 0..bits / 7 {
+        let bit = i * 7;
+        let word = packed[bit / 8] as u16 | (*packed.get(bit / 8 + 1).unwrap_or(&0) as u16) << 8;
+        let c = ((word >> (bit % 8)) & 0x7F) as u8;
+        out.push(c);
+        if wide {
+            out.push(0);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn col(id: u32, name: &str, typ: u32, cbmax: u32) -> ColumnInfo {
+        ColumnInfo {
+            name: name.to_string(),
+            id,
+            typ,
+            cbmax,
+            cp: 1200,
+        }
+    }
+
+    fn columns() -> Vec<ColumnInfo> {
+        vec![
+            col(1, "WorkID", ESE_coltypLong, 4),
+            col(2, "4-System_Rating", ESE_coltypLong, 4),
+            col(128, "5-UserData", ESE_coltypBinary, 255),
+            col(256, "33-System_ItemUrl", ESE_coltypLongText, 0),
+        ]
+    }
+
+    // WorkID = 42, System_Rating NULL, UserData = DEAD, System_ItemUrl = "ab" (UTF-16)
+    fn record() -> Vec<u8> {
+        record_with_work_id(42)
+    }
+
+    fn record_with_work_id(work_id: u32) -> Vec<u8> {
+        let mut r = vec![2u8, 128, 13, 0];
+        r.extend(work_id.to_le_bytes());
+        r.extend(0u32.to_le_bytes());
+        r.push(0b10); // System_Rating is NULL
+        r.extend(2u16.to_le_bytes()); // UserData ends at 2
+        r.extend([0xDE, 0xAD]);
+        r.extend(256u16.to_le_bytes());
+        r.extend(4u16.to_le_bytes());
+        r.push(0); // tagged data flags
+        r.extend([b'a', 0, b'b', 0]);
+        r
+    }
+
+    #[test]
+    fn test_decode_record() {
+        let values = decode_record(&record(), &columns(), true).unwrap();
+        assert_eq!(values["WorkID"], 42u32.to_le_bytes());
+        assert!(!values.contains_key("4-System_Rating"));
+        assert_eq!(values["5-UserData"], [0xDE, 0xAD]);
+        assert_eq!(values["33-System_ItemUrl"], [b'a', 0, b'b', 0]);
+        // garbage
+        assert!(decode_record(&[200, 1, 0, 0], &columns(), true).is_none());
+    }
+
+    #[test]
+    fn test_leaf_entries() {
+        // synthetic 16KB leaf page with a live and a deleted record
+        let page_size = LARGE_PAGE_SIZE;
+        let mut page = vec![0u8; page_size as usize];
+        page[24..28].copy_from_slice(&5u32.to_le_bytes()); // objidFDP
+        page[34..36].copy_from_slice(&3u16.to_le_bytes()); // tags
+        page[36..40].copy_from_slice(&PAGE_FLAG_LEAF.to_le_bytes());
+        let mut offset = 0u16;
+        for (i, deleted) in [(1usize, false), (2, true)] {
+            let mut entry = Vec::new();
+            let key_size = 4u16 | if deleted { TAG_FLAG_DELETED << 13 } else { 0 };
+            entry.extend(key_size.to_le_bytes());
+            entry.extend((i as u32).to_be_bytes());
+            entry.extend(record());
+            let start = 80 + offset as usize;
+            page[start..start + entry.len()].copy_from_slice(&entry);
+            let tag = page_size as usize - 4 * (i + 1);
+            page[tag..tag + 2].copy_from_slice(&(entry.len() as u16).to_le_bytes());
+            page[tag + 2..tag + 4].copy_from_slice(&offset.to_le_bytes());
+            offset += entry.len() as u16;
+        }
+        let header = page_header(&page).unwrap();
+        let entries = leaf_entries(&page, page_size, &header);
+        assert_eq!(entries.len(), 2);
+        assert!(!entries[0].0);
+        assert!(entries[1].0);
+        for (_, data) in entries {
+            let values = decode_record(&data, &columns(), true).unwrap();
+            assert_eq!(values["WorkID"], 42u32.to_le_bytes());
+        }
+    }
+
+    // Leaf entry of a 16KB page: key size and tag flags, key, record data.
+    fn large_page_entry(key: &[u8], flags: u16, data: &[u8]) -> Vec<u8> {
+        let mut entry = (key.len() as u16 | flags << 13).to_le_bytes().to_vec();
+        entry.extend(key);
+        entry.extend(data);
+        entry
+    }
+
+    // Writes the entries at the start of the page data and their tags (tag 0 is left empty).
+    fn large_page(objid: u32, entries: &[Vec<u8>]) -> (Vec<u8>, usize) {
+        let page_size = LARGE_PAGE_SIZE as usize;
+        let mut page = vec![0u8; page_size];
+        page[24..28].copy_from_slice(&objid.to_le_bytes());
+        page[34..36].copy_from_slice(&(entries.len() as u16 + 1).to_le_bytes());
+        page[36..40].copy_from_slice(&PAGE_FLAG_LEAF.to_le_bytes());
+        let mut offset = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            page[80 + offset..80 + offset + entry.len()].copy_from_slice(entry);
+            let tag = page_size - 4 * (i + 2);
+            page[tag..tag + 2].copy_from_slice(&(entry.len() as u16).to_le_bytes());
+            page[tag + 2..tag + 4].copy_from_slice(&(offset as u16).to_le_bytes());
+            offset += entry.len();
+        }
+        page[32..34].copy_from_slice(&(offset as u16).to_le_bytes()); // ibMicFree
+        (page, 80 + offset)
+    }
+
+    #[test]
+    fn test_recover_synthetic_db() {
+        let page_size = LARGE_PAGE_SIZE as usize;
+        let table = "SystemIndex_PropertyStore";
+        let objid = 5u32;
+
+        // MSysObjects record: ObjidTable, Type, Name
+        let mut catalog_record = vec![2u8, 128, 11, 0];
+        catalog_record.extend(objid.to_le_bytes());
+        catalog_record.extend(CATALOG_TYPE_TABLE.to_le_bytes());
+        catalog_record.push(0);
+        catalog_record.extend((table.len() as u16).to_le_bytes());
+        catalog_record.extend(table.as_bytes());
+        let (catalog, _) = large_page(
+            CATALOG_OBJID,
+            &[large_page_entry(&[0x7F, 1], 0, &catalog_record)],
+        );
+
+        // WorkID 1 is live, 2 is deleted, 3 is left in the unallocated space
+        // next to garbage which looks like the start of an entry
+        let (mut leaf, free) = large_page(
+            objid,
+            &[
+                large_page_entry(&work_id_key(1), 0, &record_with_work_id(1)),
+                large_page_entry(&work_id_key(2), TAG_FLAG_DELETED, &record_with_work_id(2)),
+            ],
+        );
+        let mut leftover = large_page_entry(&work_id_key(3), 0, &record_with_work_id(3));
+        leftover.extend(large_page_entry(&work_id_key(4), 0, &[0xFF; 8]));
+        // a record whose key doesn't match its WorkID
+        leftover.extend(large_page_entry(
+            &work_id_key(6),
+            0,
+            &record_with_work_id(5),
+        ));
+        leaf[free..free + leftover.len()].copy_from_slice(&leftover);
+
+        let mut db = vec![0u8; 2 * page_size];
+        db[DB_HEADER_PAGE_SIZE_OFFSET..DB_HEADER_PAGE_SIZE_OFFSET + 4]
+            .copy_from_slice(&LARGE_PAGE_SIZE.to_le_bytes());
+        db.extend(catalog);
+        db.extend(leaf);
+        let tmp = tempdir::TempDir::new("sidr").unwrap();
+        let f = tmp.path().join("Windows.edb");
+        std::fs::write(&f, db).unwrap();
+
+        let records = ese_recover_records(&f, table, &columns()).unwrap();
+        let found: Vec<(u32, bool)> = records
+            .iter()
+            .map(|r| {
+                let work_id = u32::from_le_bytes(r.values["WorkID"][..].try_into().unwrap());
+                (work_id, r.deleted)
+            })
+            .collect();
+        assert_eq!(found, [(1, false), (2, true), (3, true)]);
+        assert!(records.iter().all(|r| r.page == 2));
+        assert_eq!(records[2].values["5-UserData"], [0xDE, 0xAD]);
+    }
+
+    #[test]
+    fn test_decompress() {
+        // "ab" packed in 7 bit chars: 1100001 1100010
+        let packed = [0x08 | 5, 0x61, 0x31];
+        assert_eq!(decompress(&packed).unwrap(), b"ab");
+        let packed = [0x10 | 5, 0x61, 0x31];
+        assert_eq!(decompress(&packed).unwrap(), [b'a', 0, b'b', 0]);
+        assert!(decompress(&[0x18, 0x61]).is_none());
+    }
+}
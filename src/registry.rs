@@ -0,0 +1,448 @@
+// Minimal reader of offline registry hives (regf), just enough to resolve the user SIDs
+// found in the databases to account names: the ProfileList of the SOFTWARE hive
+// (SID -> profile path) and the local accounts of the SAM hive (name -> RID).
+
+use crate::error::SidrError;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+
+const REGF_SIGNATURE: &[u8] = b"regf";
+const REGF_ROOT_CELL: usize = 0x24;
+const HBIN_START: usize = 0x1000;
+const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+const VALUE_DATA_INLINE: u32 = 0x8000_0000;
+const REG_SZ: u32 = 1;
+const REG_EXPAND_SZ: u32 = 2;
+const BIG_DATA_SEGMENT: usize = 16344;
+// subkey lists pointing to other lists
+const MAX_LIST_DEPTH: usize = 4;
+
+const PROFILE_LIST_PATH: [&str; 4] = ["Microsoft", "Windows NT", "CurrentVersion", "ProfileList"];
+const SAM_ACCOUNT_PATH: [&str; 3] = ["SAM", "Domains", "Account"];
+
+fn le_u16(data: &[u8], offset: usize) -> u16 {
+    data.get(offset..offset + 2)
+        .map_or(0, |b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn le_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4)
+        .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+fn latin1_string(data: &[u8]) -> String {
+    data.iter().map(|b| *b as char).collect()
+}
+
+pub struct Hive {
+    data: Vec<u8>,
+}
+
+pub struct Key<'a> {
+    hive: &'a Hive,
+    // nk cell
+    nk: &'a [u8],
+}
+
+pub struct Value {
+    pub name: String,
+    pub typ: u32,
+    pub data: Vec<u8>,
+}
+
+impl Hive {
+    pub fn open(p: &Path) -> Result<Self, SidrError> {
+        Self::from_bytes(std::fs::read(p)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, SidrError> {
+        if !data.starts_with(REGF_SIGNATURE) || data.len() < HBIN_START {
+            return Err(SidrError::Corrupt("Not a registry hive".to_string()));
+        }
+        Ok(Hive { data })
+    }
+
+    // Data of the cell at offset (relative to the first hive bin).
+    fn cell(&self, offset: u32) -> Option<&[u8]> {
+        let pos = HBIN_START.checked_add(offset as usize)?;
+        let size = i32::from_le_bytes(self.data.get(pos..pos + 4)?.try_into().ok()?);
+        let size = size.unsigned_abs() as usize;
+        self.data.get(pos + 4..pos + size.max(4))
+    }
+
+    fn key(&self, offset: u32) -> Option<Key<'_>> {
+        let nk = self.cell(offset)?;
+        (nk.starts_with(b"nk") && nk.len() >= 0x4C).then_some(Key { hive: self, nk })
+    }
+
+    pub fn root(&self) -> Option<Key<'_>> {
+        self.key(le_u32(&self.data, REGF_ROOT_CELL))
+    }
+}
+
+impl<'a> Key<'a> {
+    pub fn name(&self) -> String {
+        let len = le_u16(self.nk, 0x48) as usize;
+        let name = self.nk.get(0x4C..0x4C + len).unwrap_or_default();
+        if le_u16(self.nk, 2) & KEY_COMP_NAME != 0 {
+            latin1_string(name)
+        } else {
+            utf16_string(name)
+        }
+    }
+
+    fn collect_subkeys(&self, list: u32, depth: usize, keys: &mut Vec<Key<'a>>) {
+        let Some(list) = self.hive.cell(list) else {
+            return;
+        };
+        let count = le_u16(list, 2) as usize;
+        // lf/lh entries have a name hash after the offset
+        let (stride, nested) = match list.get(..2) {
+            Some(b"lf") | Some(b"lh") => (8, false),
+            Some(b"li") => (4, false),
+            Some(b"ri") => (4, true),
+            _ => return,
+        };
+        for i in 0..count {
+            let offset = le_u32(list, 4 + i * stride);
+            if nested {
+                if depth < MAX_LIST_DEPTH {
+                    self.collect_subkeys(offset, depth + 1, keys);
+                }
+            } else if let Some(key) = self.hive.key(offset) {
+                keys.push(key);
+            }
+        }
+    }
+
+    pub fn subkeys(&self) -> Vec<Key<'a>> {
+        let mut keys = Vec::new();
+        if le_u32(self.nk, 0x14) > 0 {
+            self.collect_subkeys(le_u32(self.nk, 0x1C), 0, &mut keys);
+        }
+        keys
+    }
+
+    // Subkey at path, names are case insensitive.
+    pub fn subkey(&self, path: &[&str]) -> Option<Key<'a>> {
+        let Some((name, rest)) = path.split_first() else {
+            return Some(Key {
+                hive: self.hive,
+                nk: self.nk,
+            });
+        };
+        let name = name.to_lowercase();
+        self.subkeys()
+            .into_iter()
+            .find(|k| k.name().to_lowercase() == name)?
+            .subkey(rest)
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        let count = le_u32(self.nk, 0x24) as usize;
+        let Some(list) = self.hive.cell(le_u32(self.nk, 0x28)).filter(|_| count > 0) else {
+            return Vec::new();
+        };
+        (0..count.min(list.len() / 4))
+            .filter_map(|i| self.hive.cell(le_u32(list, i * 4)))
+            .filter(|vk| vk.starts_with(b"vk") && vk.len() >= 0x14)
+            .map(|vk| {
+                let name_len = le_u16(vk, 2) as usize;
+                let name = vk.get(0x14..0x14 + name_len).unwrap_or_default();
+                Value {
+                    name: if le_u16(vk, 0x10) & VALUE_COMP_NAME != 0 {
+                        latin1_string(name)
+                    } else {
+                        utf16_string(name)
+                    },
+                    typ: le_u32(vk, 0x0C),
+                    data: self.value_data(vk),
+                }
+            })
+            .collect()
+    }
+
+    fn value_data(&self, vk: &[u8]) -> Vec<u8> {
+        let size = le_u32(vk, 4);
+        if size & VALUE_DATA_INLINE != 0 {
+            let size = ((size & !VALUE_DATA_INLINE) as usize).min(4);
+            return vk[8..8 + size].to_vec();
+        }
+        let size = size as usize;
+        let Some(data) = self.hive.cell(le_u32(vk, 8)) else {
+            return Vec::new();
+        };
+        if data.starts_with(b"db") && size > data.len() {
+            // big data: list of segments
+            let count = le_u16(data, 2) as usize;
+            let mut value = Vec::with_capacity(size);
+            if let Some(segments) = self.hive.cell(le_u32(data, 4)) {
+                for i in 0..count.min(segments.len() / 4) {
+                    if let Some(segment) = self.hive.cell(le_u32(segments, i * 4)) {
+                        // cells are padded
+                        value.extend_from_slice(&segment[..segment.len().min(BIG_DATA_SEGMENT)]);
+                    }
+                }
+            }
+            value.truncate(size);
+            return value;
+        }
+        data[..size.min(data.len())].to_vec()
+    }
+
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.values()
+            .into_iter()
+            .find(|v| v.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn string_value(&self, name: &str) -> Option<String> {
+        self.value(name)
+            .filter(|v| v.typ == REG_SZ || v.typ == REG_EXPAND_SZ)
+            .map(|v| utf16_string(&v.data))
+    }
+}
+
+// SOFTWARE: SID -> ProfileImagePath
+pub fn profile_list(hive: &Hive) -> HashMap<String, String> {
+    let Some(profiles) = hive.root().and_then(|r| r.subkey(&PROFILE_LIST_PATH)) else {
+        return HashMap::new();
+    };
+    profiles
+        .subkeys()
+        .iter()
+        .filter_map(|k| {
+            Some((
+                k.name().to_ascii_uppercase(),
+                k.string_value("ProfileImagePath")?,
+            ))
+        })
+        .collect()
+}
+
+// SAM: SID -> account name, the SID being the machine SID (end of the V value of the
+// Account key) followed by the RID (type of the default value of Users\Names\<name>).
+pub fn sam_accounts(hive: &Hive) -> HashMap<String, String> {
+    let Some(account) = hive.root().and_then(|r| r.subkey(&SAM_ACCOUNT_PATH)) else {
+        return HashMap::new();
+    };
+    let Some(v) = account.value("V").filter(|v| v.data.len() >= 12) else {
+        return HashMap::new();
+    };
+    let machine = &v.data[v.data.len() - 12..];
+    let machine_sid = format!(
+        "S-1-5-21-{}-{}-{}",
+        le_u32(machine, 0),
+        le_u32(machine, 4),
+        le_u32(machine, 8)
+    );
+    let Some(names) = account.subkey(&["Users", "Names"]) else {
+        return HashMap::new();
+    };
+    names
+        .subkeys()
+        .iter()
+        .filter_map(|k| {
+            let rid = k.value("")?.typ;
+            Some((format!("{machine_sid}-{rid}"), k.name()))
+        })
+        .collect()
+}
+
+fn find_hive(dir: &Path, name: &str) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .find(|p| p.is_file() && p.file_name().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+}
+
+// Maps the user SIDs to account names with the SOFTWARE and SAM hives found in dir.
+// Local accounts get their SAM name, the others the name of their profile directory.
+pub fn load_user_names(dir: &Path) -> Result<HashMap<String, String>, SidrError> {
+    let software = find_hive(dir, "SOFTWARE");
+    let sam = find_hive(dir, "SAM");
+    if software.is_none() && sam.is_none() {
+        return Err(SidrError::Cfg(format!(
+            "No SOFTWARE or SAM hive in {}",
+            dir.to_string_lossy()
+        )));
+    }
+    let mut names = HashMap::new();
+    if let Some(p) = software {
+        for (sid, path) in profile_list(&Hive::open(&p)?) {
+            if let Some(name) = path.rsplit(['\\', '/']).find(|s| !s.is_empty()) {
+                names.insert(sid, name.to_string());
+            }
+        }
+    }
+    if let Some(p) = sam {
+        names.extend(sam_accounts(&Hive::open(&p)?));
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a hive bottom-up, cells are appended to a single hive bin.
+    struct HiveBuilder {
+        data: Vec<u8>,
+    }
+
+    impl HiveBuilder {
+        fn new() -> Self {
+            let mut data = vec![0u8; HBIN_START + 0x20];
+            data[..4].copy_from_slice(REGF_SIGNATURE);
+            data[HBIN_START..HBIN_START + 4].copy_from_slice(b"hbin");
+            HiveBuilder { data }
+        }
+
+        fn cell(&mut self, payload: &[u8]) -> u32 {
+            let offset = (self.data.len() - HBIN_START) as u32;
+            let size = (payload.len() + 4 + 7) & !7;
+            self.data.extend((-(size as i32)).to_le_bytes());
+            self.data.extend(payload);
+            self.data.resize(HBIN_START + offset as usize + size, 0);
+            offset
+        }
+
+        fn value(&mut self, name: &str, typ: u32, data: &[u8]) -> u32 {
+            let mut vk = vec![0u8; 0x14];
+            vk[..2].copy_from_slice(b"vk");
+            vk[2..4].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            if data.len() <= 4 {
+                vk[4..8].copy_from_slice(&(data.len() as u32 | VALUE_DATA_INLINE).to_le_bytes());
+                vk[8..8 + data.len()].copy_from_slice(data);
+            } else {
+                let cell = self.cell(data);
+                vk[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+                vk[8..12].copy_from_slice(&cell.to_le_bytes());
+            }
+            vk[0x0C..0x10].copy_from_slice(&typ.to_le_bytes());
+            vk[0x10..0x12].copy_from_slice(&VALUE_COMP_NAME.to_le_bytes());
+            vk.extend(name.as_bytes());
+            self.cell(&vk)
+        }
+
+        fn key(&mut self, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+            let mut nk = vec![0u8; 0x4C];
+            nk[..2].copy_from_slice(b"nk");
+            nk[2..4].copy_from_slice(&KEY_COMP_NAME.to_le_bytes());
+            if !subkeys.is_empty() {
+                let mut lf = b"lf".to_vec();
+                lf.extend((subkeys.len() as u16).to_le_bytes());
+                for k in subkeys {
+                    lf.extend(k.to_le_bytes());
+                    lf.extend([0u8; 4]);
+                }
+                let list = self.cell(&lf);
+                nk[0x14..0x18].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+                nk[0x1C..0x20].copy_from_slice(&list.to_le_bytes());
+            }
+            if !values.is_empty() {
+                let list: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                let list = self.cell(&list);
+                nk[0x24..0x28].copy_from_slice(&(values.len() as u32).to_le_bytes());
+                nk[0x28..0x2C].copy_from_slice(&list.to_le_bytes());
+            }
+            nk[0x48..0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend(name.as_bytes());
+            self.cell(&nk)
+        }
+
+        // nested keys along path, the last one having the given subkeys and values
+        fn path(&mut self, path: &[&str], subkeys: &[u32], values: &[u32]) -> u32 {
+            let (last, parents) = path.split_last().unwrap();
+            let mut key = self.key(last, subkeys, values);
+            for name in parents.iter().rev() {
+                key = self.key(name, &[key], &[]);
+            }
+            key
+        }
+
+        fn finish(mut self, root: u32) -> Hive {
+            self.data[REGF_ROOT_CELL..REGF_ROOT_CELL + 4].copy_from_slice(&root.to_le_bytes());
+            Hive::from_bytes(self.data).unwrap()
+        }
+    }
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .chain([0])
+            .flat_map(|c| c.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_profile_list() {
+        let mut b = HiveBuilder::new();
+        let sid = "S-1-5-21-4268361623-692440835-3372367631-1001";
+        let path = b.value(
+            "ProfileImagePath",
+            REG_EXPAND_SZ,
+            &utf16("C:\\Users\\fisft"),
+        );
+        let user = b.key(sid, &[], &[path]);
+        let path = b.value(
+            "ProfileImagePath",
+            REG_EXPAND_SZ,
+            &utf16("%systemroot%\\system32\\config\\systemprofile"),
+        );
+        let system = b.key("S-1-5-18", &[], &[path]);
+        let mut software = vec!["ROOT"];
+        software.extend(PROFILE_LIST_PATH);
+        let root = b.path(&software, &[user, system], &[]);
+        let hive = b.finish(root);
+
+        let profiles = profile_list(&hive);
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[sid], "C:\\Users\\fisft");
+        assert_eq!(
+            profiles["S-1-5-18"],
+            "%systemroot%\\system32\\config\\systemprofile"
+        );
+    }
+
+    #[test]
+    fn test_sam_accounts() {
+        let mut b = HiveBuilder::new();
+        let rid = b.value("", 1001, &[]);
+        let fisher = b.key("Fisher", &[], &[rid]);
+        let rid = b.value("", 500, &[]);
+        let admin = b.key("Administrator", &[], &[rid]);
+        let names = b.path(&["Users", "Names"], &[fisher, admin], &[]);
+        let mut v = vec![0u8; 0x40];
+        for n in [4268361623u32, 692440835, 3372367631] {
+            v.extend(n.to_le_bytes());
+        }
+        let v = b.value("V", 3, &v);
+        let account = b.key("Account", &[names], &[v]);
+        let root = b.path(&["CMI-CreateHive", "SAM", "Domains"], &[account], &[]);
+        let hive = b.finish(root);
+
+        let accounts = sam_accounts(&hive);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(
+            accounts["S-1-5-21-4268361623-692440835-3372367631-1001"],
+            "Fisher"
+        );
+        assert_eq!(
+            accounts["S-1-5-21-4268361623-692440835-3372367631-500"],
+            "Administrator"
+        );
+    }
+}
@@ -1,14 +1,26 @@
+use crate::error::SidrError;
 use chrono::prelude::*;
 use clap::ValueEnum;
 use ese_parser_lib::parser::jet::DbState;
-use serde_json;
-use simple_error::SimpleError;
+use once_cell::unsync::OnceCell;
+use owning_ref::OwningHandle;
+use parquet::basic::{Compression, LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use serde_json::{self, Map, Value};
+use sqlite::State;
 use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
-use std::ops::IndexMut;
+use std::iter;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Once};
 
 use crate::utils::*;
 
@@ -16,6 +28,13 @@ use crate::utils::*;
 pub enum ReportFormat {
     Json,
     Csv,
+    // log2timeline/Plaso CSV, one line per date
+    L2tcsv,
+    // Sleuth Kit bodyfile, for mactime
+    Bodyfile,
+    Parquet,
+    // a single SQLite database with a table per report
+    Sqlite,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
@@ -29,15 +48,22 @@ pub enum ReportSuffix {
     FileReport,
     ActivityHistory,
     InternetHistory,
+    GatherHistory,
+    WalFrames,
+    Timeline,
     Unknown,
 }
 
 impl ReportSuffix {
     pub fn get_match(output_type: &str) -> Option<ReportSuffix> {
-        match output_type {
+        // reports of Windows.db without its write-ahead log have the same layout
+        match output_type.trim_end_matches("_without_WAL") {
             "File_Report" => Some(ReportSuffix::FileReport),
             "Activity_History_Report" => Some(ReportSuffix::ActivityHistory),
             "Internet_History_Report" => Some(ReportSuffix::InternetHistory),
+            "Gather_History_Report" => Some(ReportSuffix::GatherHistory),
+            "WAL_Frames_Report" => Some(ReportSuffix::WalFrames),
+            "Timeline" => Some(ReportSuffix::Timeline),
             &_ => Some(ReportSuffix::Unknown),
         }
     }
@@ -45,12 +71,42 @@ impl ReportSuffix {
     // Autogenerating the names from the enum values by deriving Debug is another option.
     // However, if someone decided to change the name of one of these enums,
     // it could break downstream processing.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::FileReport => "file_report",
+            Self::ActivityHistory => "activity_history",
+            Self::InternetHistory => "internet_history",
+            Self::GatherHistory => "gather_history",
+            Self::WalFrames => "wal_frames",
+            Self::Timeline => "timeline",
+            Self::Unknown => "",
+        }
+    }
+
     pub fn message(&self) -> String {
+        serde_json::to_string(self.name()).unwrap()
+    }
+
+    // Columns describing the item of a record on the timeline, the first one present is used.
+    // None for the reports which are not part of the timeline.
+    fn timeline_description(&self) -> Option<&'static [&'static str]> {
         match self {
-            Self::FileReport => serde_json::to_string("file_report").unwrap(),
-            Self::ActivityHistory => serde_json::to_string("activity_history").unwrap(),
-            Self::InternetHistory => serde_json::to_string("internet_history").unwrap(),
-            Self::Unknown => serde_json::to_string("").unwrap(),
+            Self::InternetHistory => {
+                Some(&["System_Link_TargetUrl", "System_Title", "System_ItemUrl"])
+            }
+            Self::ActivityHistory => Some(&[
+                "System_Activity_DisplayText",
+                "System_Activity_ContentUri",
+                "System_ItemUrl",
+            ]),
+            Self::GatherHistory => Some(&["FullPath"]),
+            // the frames list the changed values, not events
+            Self::WalFrames | Self::Timeline => None,
+            Self::FileReport | Self::Unknown => Some(&[
+                "System_ItemPathDisplay",
+                "System_ItemUrl",
+                "System_ItemNameDisplay",
+            ]),
         }
     }
 }
@@ -61,23 +117,207 @@ impl Display for ReportSuffix {
     }
 }
 
+// Where a database extracted from a disk image or an archive comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DbSource {
+    Image {
+        image: PathBuf,
+        volume_offset: u64,
+        // path of the database inside the volume
+        path: String,
+    },
+    Archive {
+        archive: PathBuf,
+        // path of the database inside the archive
+        path: String,
+    },
+}
+
+impl Display for DbSource {
+    // "case/disk.E01!\ProgramData\...\Windows.edb"
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Image { image, path, .. } => write!(f, "{}!{path}", image.to_string_lossy()),
+            Self::Archive { archive, path } => {
+                write!(f, "{}!{path}", archive.to_string_lossy())
+            }
+        }
+    }
+}
+
+// One timestamp of a record, see ReportProducer::with_timeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelineEvent {
+    pub timestamp: DateTime<Utc>,
+    pub source_db: String,
+    pub host: String,
+    pub report: String,
+    pub work_id: Option<u64>,
+    // UserName, or else UserSID of the record
+    pub user: Option<String>,
+    // column the timestamp comes from
+    pub timestamp_kind: String,
+    pub description: String,
+}
+
+pub type TimelineEvents = Rc<RefCell<Vec<TimelineEvent>>>;
+
+// A report of an input database, with the number of records written to it.
+#[derive(Clone, Debug)]
+pub struct ReportRecord {
+    pub report: String,
+    pub path: PathBuf,
+    pub rows: Rc<Cell<u64>>,
+}
+
+// An input database of the run, hashed before it is parsed, and the reports made of it.
+#[derive(Clone, Debug)]
+pub struct InputRecord {
+    pub path: PathBuf,
+    // the path, or where the database was extracted from
+    pub source_db: String,
+    pub format: String,
+    pub hashes: Option<FileHashes>,
+    // transaction logs, WAL, ... of the database
+    pub sidecars: Vec<(String, FileHashes)>,
+    pub hostname: Option<String>,
+    pub db_state: Option<DbState>,
+    pub reports: Vec<ReportRecord>,
+}
+
 pub struct ReportProducer {
     dir: PathBuf,
     format: ReportFormat,
     report_type: ReportOutput,
+    // databases extracted from disk images or archives, by extracted path
+    sources: HashMap<PathBuf, DbSource>,
+    // the records are collected here instead of being written to per-database reports
+    timeline: Option<(PathBuf, TimelineEvents)>,
+    // the output database of ReportFormat::Sqlite, opened with the first report
+    sqlite: Rc<OnceCell<Rc<SqliteOutput>>>,
+    csv: CsvOptions,
+    json_style: JsonStyle,
+    // the reports are named after the database hash instead of the time they are created
+    deterministic: bool,
+    // SHA-256 of the databases not recorded by add_input, by path, so that each one is hashed once
+    db_hashes: Rc<RefCell<HashMap<PathBuf, String>>>,
+    // paths of the reports created by the run, to number the ones with the same name
+    paths: Rc<RefCell<HashSet<PathBuf>>>,
+    // the databases processed by the run, for its manifest
+    inputs: Rc<RefCell<Vec<InputRecord>>>,
 }
 
+// in the output directory, the reports of the next runs are appended to it
+const SQLITE_OUTPUT: &str = "sidr_reports.sqlite";
+
 impl ReportProducer {
-    pub fn new(dir: &Path, format: ReportFormat, report_type: ReportOutput) -> Self {
+    pub fn new(
+        dir: &Path,
+        format: ReportFormat,
+        report_type: ReportOutput,
+    ) -> Result<Self, SidrError> {
         if !dir.exists() {
-            std::fs::create_dir(dir)
-                .unwrap_or_else(|_| panic!("Can't create directory \"{}\"", dir.to_string_lossy()));
+            std::fs::create_dir(dir).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("Can't create directory \"{}\": {e}", dir.to_string_lossy()),
+                )
+            })?;
         }
-        ReportProducer {
+        Ok(ReportProducer {
             dir: dir.to_path_buf(),
             format,
             report_type,
+            sources: HashMap::new(),
+            timeline: None,
+            sqlite: Rc::default(),
+            csv: CsvOptions::default(),
+            json_style: JsonStyle::default(),
+            deterministic: false,
+            db_hashes: Rc::default(),
+            paths: Rc::default(),
+            inputs: Rc::default(),
+        })
+    }
+
+    // A producer whose reports are the same from one run to the other: named
+    // {hostname}_{report}_{database hash}.{ext} and without the time of the run.
+    pub fn with_deterministic(self) -> Self {
+        ReportProducer {
+            deterministic: true,
+            ..self
+        }
+    }
+
+    // A producer writing the CSV reports with the given delimiter, quoting, ...
+    pub fn with_csv_options(self, csv: CsvOptions) -> Self {
+        ReportProducer { csv, ..self }
+    }
+
+    // A producer writing the JSON reports as JSON lines, arrays or pretty-printed arrays.
+    pub fn with_json_style(self, json_style: JsonStyle) -> Self {
+        ReportProducer { json_style, ..self }
+    }
+
+    // A producer merging the records of all the reports into a single timeline,
+    // written by write_timeline once all the databases are processed.
+    pub fn with_timeline(self) -> Self {
+        let path = match self.format {
+            // the timeline table of the output database
+            ReportFormat::Sqlite => self.dir.join(SQLITE_OUTPUT),
+            _ if self.deterministic => self.dir.join(format!("Timeline.{}", self.ext())),
+            _ => self.dir.join(format!(
+                "Timeline_{}.{}",
+                Utc::now().format("%Y%m%d_%H%M%S%.f"),
+                self.ext()
+            )),
+        };
+        ReportProducer {
+            timeline: Some((path, TimelineEvents::default())),
+            ..self
+        }
+    }
+
+    // A producer for the databases extracted from a disk image or an archive,
+    // their records start with where the database comes from.
+    pub fn with_sources(&self, sources: HashMap<PathBuf, DbSource>) -> Self {
+        ReportProducer {
+            dir: self.dir.clone(),
+            format: self.format.clone(),
+            report_type: self.report_type,
+            sources,
+            timeline: self.timeline.clone(),
+            sqlite: self.sqlite.clone(),
+            csv: self.csv.clone(),
+            json_style: self.json_style,
+            deterministic: self.deterministic,
+            db_hashes: self.db_hashes.clone(),
+            paths: self.paths.clone(),
+            inputs: self.inputs.clone(),
+        }
+    }
+
+    fn ext(&self) -> &'static str {
+        match self.format {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv | ReportFormat::L2tcsv => "csv",
+            ReportFormat::Bodyfile => "body",
+            ReportFormat::Parquet => "parquet",
+            ReportFormat::Sqlite => "sqlite",
+        }
+    }
+
+    fn sqlite_output(&self) -> Result<Rc<SqliteOutput>, SidrError> {
+        if self.report_type == ReportOutput::ToStdout {
+            return Err(SidrError::Cfg(
+                "sqlite reports can't be written to stdout".to_string(),
+            ));
         }
+        self.sqlite
+            .get_or_try_init(|| {
+                SqliteOutput::open(&self.dir.join(SQLITE_OUTPUT), self.deterministic).map(Rc::new)
+            })
+            .cloned()
     }
 
     pub fn get_report_type(&self) -> ReportOutput {
@@ -114,53 +354,1512 @@ impl ReportProducer {
         ))
     }
 
-    pub fn new_report(
+    // Path of a report of a deterministic producer, named after the SHA-256 of the database.
+    // The reports of identical databases are numbered in the order they are created.
+    fn get_path_db_hash(
         &self,
-        _dbpath: &Path,
+        dbpath: &Path,
         recovered_hostname: &str,
         report_suffix: &str,
         edb_database_state: Option<DbState>,
-    ) -> Result<(PathBuf, Box<dyn Report>), SimpleError> {
-        let ext = match self.format {
-            ReportFormat::Json => "json",
-            ReportFormat::Csv => "csv",
+    ) -> Result<PathBuf, SidrError> {
+        let hash = self.db_sha256(dbpath)?;
+        let status = if self.is_db_dirty(edb_database_state) {
+            "_dirty"
+        } else {
+            ""
         };
-        let date_time_now: DateTime<Utc> = Utc::now();
-        let path = self.get_path_db_status(
+        let name = format!(
+            "{recovered_hostname}_{report_suffix}_{}{status}",
+            &hash[..16]
+        );
+        let mut paths = self.paths.borrow_mut();
+        let mut path = self.dir.join(format!("{name}.{}", self.ext()));
+        let mut n = 1;
+        while paths.contains(&path) {
+            n += 1;
+            path = self.dir.join(format!("{name}_{n}.{}", self.ext()));
+        }
+        paths.insert(path.clone());
+        Ok(path)
+    }
+
+    // SHA-256 of a database, from its input record if it was hashed by add_input.
+    fn db_sha256(&self, dbpath: &Path) -> std::io::Result<String> {
+        let recorded = self.inputs.borrow().iter().rev().find_map(|input| {
+            let hashes = input.hashes.as_ref().filter(|_| input.path == dbpath)?;
+            Some(hashes.sha256.clone())
+        });
+        if let Some(hash) = recorded {
+            return Ok(hash);
+        }
+        let mut db_hashes = self.db_hashes.borrow_mut();
+        if let Some(hash) = db_hashes.get(dbpath) {
+            return Ok(hash.clone());
+        }
+        let hash = file_hashes(dbpath)?.sha256;
+        db_hashes.insert(dbpath.to_path_buf(), hash.clone());
+        Ok(hash)
+    }
+
+    // The path of a database, or where it was extracted from.
+    pub fn source_db(&self, dbpath: &Path) -> String {
+        match self.sources.get(dbpath) {
+            Some(source) => source.to_string(),
+            None => dbpath.to_string_lossy().into_owned(),
+        }
+    }
+
+    // Records an input database of the run, the records of its reports are counted from then on.
+    pub fn add_input(
+        &self,
+        dbpath: &Path,
+        format: &str,
+        hashes: Option<FileHashes>,
+        sidecars: Vec<(String, FileHashes)>,
+    ) {
+        self.inputs.borrow_mut().push(InputRecord {
+            path: dbpath.to_path_buf(),
+            source_db: self.source_db(dbpath),
+            format: format.to_string(),
+            hashes,
+            sidecars,
+            hostname: None,
+            db_state: None,
+            reports: Vec::new(),
+        });
+    }
+
+    // The input databases recorded by add_input and their reports.
+    pub fn inputs(&self) -> Vec<InputRecord> {
+        self.inputs.borrow().clone()
+    }
+
+    pub fn new_report(
+        &self,
+        dbpath: &Path,
+        recovered_hostname: &str,
+        report_suffix: &str,
+        edb_database_state: Option<DbState>,
+    ) -> Result<(PathBuf, Box<dyn Report>), SidrError> {
+        let (path, rep) = self.create_report(
+            dbpath,
             recovered_hostname,
             report_suffix,
-            date_time_now,
-            ext,
             edb_database_state,
-        );
+        )?;
+        let mut inputs = self.inputs.borrow_mut();
+        let Some(input) = inputs.iter_mut().rev().find(|input| input.path == dbpath) else {
+            return Ok((path, rep));
+        };
+        input.hostname = Some(recovered_hostname.to_string());
+        input.db_state = edb_database_state.or(input.db_state);
+        let rows = Rc::new(Cell::new(0));
+        input.reports.push(ReportRecord {
+            report: report_suffix.to_string(),
+            path: path.clone(),
+            rows: rows.clone(),
+        });
+        Ok((path, Box::new(ReportCount::new(rep, rows))))
+    }
+
+    fn create_report(
+        &self,
+        dbpath: &Path,
+        recovered_hostname: &str,
+        report_suffix: &str,
+        edb_database_state: Option<DbState>,
+    ) -> Result<(PathBuf, Box<dyn Report>), SidrError> {
+        let source_db = self.source_db(dbpath);
+        let event_source = EventSource::new(source_db, recovered_hostname, report_suffix);
+        if let Some((path, events)) = &self.timeline {
+            let rep = ReportTimeline::new(events.clone(), event_source);
+            return Ok((path.clone(), Box::new(rep)));
+        }
+        // the records are tied to their database by the sources table
+        if let ReportFormat::Sqlite = self.format {
+            let output = self.sqlite_output()?;
+            // no file behind the reports of a configuration
+            let sha256 = self.db_sha256(dbpath).ok();
+            let source_id = output.source_id(
+                sha256.as_deref(),
+                &event_source.source_db,
+                recovered_hostname,
+                edb_database_state,
+            )?;
+            let rep = ReportSqlite::new(output.clone(), report_suffix, Some(source_id))?;
+            return Ok((output.path.clone(), Box::new(rep)));
+        }
+
+        let path = if self.deterministic && self.report_type == ReportOutput::ToFile {
+            self.get_path_db_hash(
+                dbpath,
+                recovered_hostname,
+                report_suffix,
+                edb_database_state,
+            )?
+        } else {
+            let date_time_now: DateTime<Utc> = Utc::now();
+            self.get_path_db_status(
+                recovered_hostname,
+                report_suffix,
+                date_time_now,
+                self.ext(),
+                edb_database_state,
+            )
+        };
         let report_suffix = ReportSuffix::get_match(report_suffix);
         let rep: Box<dyn Report> = match self.format {
             ReportFormat::Json => {
-                ReportJson::new(&path, self.report_type, report_suffix).map(Box::new)?
+                ReportJson::new(&path, self.report_type, report_suffix, self.json_style)
+                    .map(Box::new)?
             }
             ReportFormat::Csv => {
-                ReportCsv::new(&path, self.report_type, report_suffix).map(Box::new)?
+                ReportCsv::new(&path, self.report_type, report_suffix, self.csv.clone())
+                    .map(Box::new)?
+            }
+            // the source of the database is in the filename column
+            ReportFormat::L2tcsv => {
+                return ReportL2t::new(&path, self.report_type, event_source)
+                    .map(|rep| (path, Box::new(rep) as Box<dyn Report>));
             }
+            ReportFormat::Sqlite => unreachable!("the sqlite reports are created above"),
+            ReportFormat::Bodyfile => {
+                ReportBodyfile::new(&path, self.report_type, report_suffix).map(Box::new)?
+            }
+            ReportFormat::Parquet => ReportParquet::new(&path, self.report_type).map(Box::new)?,
+        };
+        let rep: Box<dyn Report> = match self.sources.get(dbpath) {
+            Some(source) => Box::new(ReportSource::new(rep, source.clone())),
+            None => rep,
         };
         Ok((path, rep))
     }
+
+    // Writes the events collected by the reports of a timeline producer, sorted by timestamp.
+    // Returns the path of the timeline, None if the producer is not a timeline one.
+    pub fn write_timeline(&self) -> Result<Option<PathBuf>, SidrError> {
+        let Some((path, events)) = &self.timeline else {
+            return Ok(None);
+        };
+        let mut events = events.take();
+        // stable, the events of the same time stay in the order of the databases
+        events.sort_by_key(|e| e.timestamp);
+        let report_suffix = Some(ReportSuffix::Timeline);
+        let mut rep: Box<dyn Report> = match self.format {
+            ReportFormat::Json => {
+                ReportJson::new(path, self.report_type, report_suffix, self.json_style)
+                    .map(Box::new)?
+            }
+            ReportFormat::Csv => {
+                ReportCsv::new(path, self.report_type, report_suffix, self.csv.clone())
+                    .map(Box::new)?
+            }
+            ReportFormat::Parquet => ReportParquet::new(path, self.report_type).map(Box::new)?,
+            ReportFormat::Sqlite => {
+                let output = self.sqlite_output()?;
+                Box::new(ReportSqlite::new(output, "Timeline", None)?)
+            }
+            ReportFormat::L2tcsv => {
+                let mut w = L2tWriter::new(path, self.report_type)?;
+                for e in &events {
+                    w.write_event(e)?;
+                }
+                w.f.flush()?;
+                return Ok(Some(path.clone()));
+            }
+            // mactime sorts the bodyfiles itself
+            ReportFormat::Bodyfile => {
+                return Err(SidrError::Cfg(
+                    "a timeline can't be written as a bodyfile".to_string(),
+                ))
+            }
+        };
+        for f in TIMELINE_FIELDS {
+            rep.set_field(f);
+        }
+        for e in events {
+            rep.create_new_row();
+            rep.insert_datetime("timestamp", e.timestamp);
+            rep.insert_str_val("source_db", e.source_db);
+            rep.insert_str_val("host", e.host);
+            rep.insert_str_val("report", e.report);
+            if let Some(work_id) = e.work_id {
+                rep.insert_int_val("WorkId", work_id);
+            }
+            rep.insert_str_val("timestamp_kind", e.timestamp_kind);
+            rep.insert_str_val("description", e.description);
+        }
+        rep.finish()?;
+        Ok(Some(path.clone()))
+    }
 }
 
+const TIMELINE_FIELDS: [&str; 7] = [
+    "timestamp",
+    "source_db",
+    "host",
+    "report",
+    "WorkId",
+    "timestamp_kind",
+    "description",
+];
+
 pub trait Report {
     fn footer(&mut self) {}
+    // Writes the last record and closes the report. Dropping the report does it too,
+    // but can only log the errors.
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.footer();
+        Ok(())
+    }
     fn create_new_row(&mut self);
     fn insert_str_val(&self, f: &str, s: String);
     fn insert_int_val(&self, f: &str, n: u64);
+
+    // Typed values, by default written as the text the reports always had.
+    // Negative numbers are strings, the int values being unsigned.
+    fn insert_i64(&self, f: &str, n: i64) {
+        match u64::try_from(n) {
+            Ok(n) => self.insert_int_val(f, n),
+            Err(_) => self.insert_str_val(f, n.to_string()),
+        }
+    }
+    fn insert_f64(&self, f: &str, x: f64) {
+        self.insert_str_val(f, x.to_string());
+    }
+    fn insert_bool(&self, f: &str, b: bool) {
+        self.insert_str_val(f, b.to_string());
+    }
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        self.insert_str_val(f, format_date_time(t));
+    }
+    fn insert_bytes(&self, f: &str, b: &[u8]) {
+        self.insert_str_val(f, to_hex(b));
+    }
+    // the values of a multi-valued property
+    fn insert_list(&self, f: &str, l: Vec<String>) {
+        self.insert_str_val(f, l.join("; "));
+    }
+
     fn set_field(&self, _: &str) {} // used in csv to generate header
     fn is_some_val_in_record(&self) -> bool;
 }
 
+// Prepends the source of the database to every record of the wrapped report.
+pub struct ReportSource {
+    rep: Box<dyn Report>,
+    source: DbSource,
+    // the source is not inserted yet in the current record
+    new_row: Cell<bool>,
+}
+
+impl ReportSource {
+    pub fn new(rep: Box<dyn Report>, source: DbSource) -> Self {
+        ReportSource {
+            rep,
+            source,
+            new_row: Cell::new(true),
+        }
+    }
+
+    // inserted with the first value of a record, so that empty records stay empty
+    fn insert_source(&self) {
+        if !self.new_row.replace(false) {
+            return;
+        }
+        match &self.source {
+            DbSource::Image {
+                image,
+                volume_offset,
+                path,
+            } => {
+                self.rep
+                    .insert_str_val("Image", image.to_string_lossy().into_owned());
+                self.rep.insert_int_val("ImageVolumeOffset", *volume_offset);
+                self.rep.insert_str_val("ImageFile", path.clone());
+            }
+            DbSource::Archive { archive, path } => {
+                self.rep
+                    .insert_str_val("Archive", archive.to_string_lossy().into_owned());
+                self.rep.insert_str_val("ArchiveFile", path.clone());
+            }
+        }
+    }
+}
+
+impl Report for ReportSource {
+    fn footer(&mut self) {
+        self.rep.footer();
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.rep.finish()
+    }
+
+    fn create_new_row(&mut self) {
+        self.rep.create_new_row();
+        self.new_row.set(true);
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.insert_source();
+        self.rep.insert_str_val(f, s);
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.insert_source();
+        self.rep.insert_int_val(f, n);
+    }
+
+    fn insert_i64(&self, f: &str, n: i64) {
+        self.insert_source();
+        self.rep.insert_i64(f, n);
+    }
+
+    fn insert_f64(&self, f: &str, x: f64) {
+        self.insert_source();
+        self.rep.insert_f64(f, x);
+    }
+
+    fn insert_bool(&self, f: &str, b: bool) {
+        self.insert_source();
+        self.rep.insert_bool(f, b);
+    }
+
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        self.insert_source();
+        self.rep.insert_datetime(f, t);
+    }
+
+    fn insert_bytes(&self, f: &str, b: &[u8]) {
+        self.insert_source();
+        self.rep.insert_bytes(f, b);
+    }
+
+    fn insert_list(&self, f: &str, l: Vec<String>) {
+        self.insert_source();
+        self.rep.insert_list(f, l);
+    }
+
+    fn set_field(&self, f: &str) {
+        self.rep.set_field(f);
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        self.rep.is_some_val_in_record()
+    }
+}
+
+// Counts the records written to the wrapped report.
+pub struct ReportCount {
+    rep: Box<dyn Report>,
+    rows: Rc<Cell<u64>>,
+}
+
+impl ReportCount {
+    pub fn new(rep: Box<dyn Report>, rows: Rc<Cell<u64>>) -> Self {
+        ReportCount { rep, rows }
+    }
+
+    // empty records are not written
+    fn count_row(&self) {
+        if self.rep.is_some_val_in_record() {
+            self.rows.set(self.rows.get() + 1);
+        }
+    }
+}
+
+impl Report for ReportCount {
+    fn footer(&mut self) {
+        self.count_row();
+        self.rep.footer();
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.count_row();
+        self.rep.finish()
+    }
+
+    fn create_new_row(&mut self) {
+        self.count_row();
+        self.rep.create_new_row();
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.rep.insert_str_val(f, s);
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.rep.insert_int_val(f, n);
+    }
+
+    fn insert_i64(&self, f: &str, n: i64) {
+        self.rep.insert_i64(f, n);
+    }
+
+    fn insert_f64(&self, f: &str, x: f64) {
+        self.rep.insert_f64(f, x);
+    }
+
+    fn insert_bool(&self, f: &str, b: bool) {
+        self.rep.insert_bool(f, b);
+    }
+
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        self.rep.insert_datetime(f, t);
+    }
+
+    fn insert_bytes(&self, f: &str, b: &[u8]) {
+        self.rep.insert_bytes(f, b);
+    }
+
+    fn insert_list(&self, f: &str, l: Vec<String>) {
+        self.rep.insert_list(f, l);
+    }
+
+    fn set_field(&self, f: &str) {
+        self.rep.set_field(f);
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        self.rep.is_some_val_in_record()
+    }
+}
+
+// the last record is written when the wrapped report is dropped
+impl Drop for ReportCount {
+    fn drop(&mut self) {
+        self.count_row();
+    }
+}
+
+// The values of a record, for the reports written a record at a time.
+#[derive(Default)]
+struct Record(Vec<(String /*field*/, String /*value*/)>);
+
+impl Record {
+    fn get(&self, f: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| k == f).map(|(_, v)| v.as_str())
+    }
+
+    fn first_of(&self, fields: &[&str]) -> Option<&str> {
+        fields.iter().find_map(|f| self.get(f))
+    }
+
+    // the gather history is keyed by DocumentID, the other reports by WorkId
+    fn work_id(&self) -> Option<u64> {
+        self.first_of(&["WorkId", "DocumentID"])?.parse().ok()
+    }
+}
+
+// Dates are reported as RFC 3339 UTC strings (see format_date_time), the zero FILETIMEs
+// of the unset dates are left out.
+fn parse_report_date(s: &str) -> Option<DateTime<Utc>> {
+    if !s.ends_with('Z') {
+        return None;
+    }
+    let t = DateTime::parse_from_rfc3339(s).ok()?.with_timezone(&Utc);
+    (t.year() > 1601).then_some(t)
+}
+
+// Where the records of a report come from, to turn them into timeline events.
+pub struct EventSource {
+    source_db: String,
+    host: String,
+    report: String,
+    description_fields: Option<&'static [&'static str]>,
+}
+
+impl EventSource {
+    pub fn new(source_db: String, host: &str, report: &str) -> Self {
+        EventSource {
+            source_db,
+            host: host.to_string(),
+            report: report.to_string(),
+            description_fields: ReportSuffix::get_match(report)
+                .and_then(|suffix| suffix.timeline_description()),
+        }
+    }
+
+    // One event per date of the record.
+    fn events(&self, record: &Record) -> Vec<TimelineEvent> {
+        let Some(description_fields) = self.description_fields else {
+            return Vec::new();
+        };
+        let description = record.first_of(description_fields).unwrap_or_default();
+        let user = record.first_of(&["UserName", "UserSID"]);
+        record
+            .0
+            .iter()
+            .filter_map(|(f, v)| {
+                Some(TimelineEvent {
+                    timestamp: parse_report_date(v)?,
+                    source_db: self.source_db.clone(),
+                    host: self.host.clone(),
+                    report: self.report.clone(),
+                    work_id: record.work_id(),
+                    user: user.map(str::to_string),
+                    timestamp_kind: f.clone(),
+                    description: description.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+// Collects one timeline event per timestamp of each record, instead of writing the records.
+pub struct ReportTimeline {
+    events: TimelineEvents,
+    source: EventSource,
+    record: RefCell<Record>,
+}
+
+impl ReportTimeline {
+    pub fn new(events: TimelineEvents, source: EventSource) -> Self {
+        ReportTimeline {
+            events,
+            source,
+            record: RefCell::default(),
+        }
+    }
+}
+
+impl Report for ReportTimeline {
+    fn footer(&mut self) {
+        self.create_new_row();
+    }
+
+    fn create_new_row(&mut self) {
+        let record = self.record.take();
+        self.events.borrow_mut().extend(self.source.events(&record));
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.record.borrow_mut().0.push((f.to_string(), s));
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.record
+            .borrow_mut()
+            .0
+            .push((f.to_string(), n.to_string()));
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        !self.record.borrow().0.is_empty()
+    }
+}
+
+impl Drop for ReportTimeline {
+    fn drop(&mut self) {
+        self.footer();
+    }
+}
+
+// footer of the reports written to files, which must not panic when they are dropped
+fn finish_or_log(r: &mut dyn Report) {
+    if let Err(e) = r.finish() {
+        eprintln!("Could not write the report: {e}");
+    }
+}
+
+// The first error of writing the rows, returned by finish rather than panicking in
+// create_new_row, which can't return it.
+#[derive(Default)]
+struct RowError(RefCell<Option<SidrError>>);
+
+impl RowError {
+    fn keep(&self, r: Result<(), impl Into<SidrError>>) {
+        if let Err(e) = r {
+            self.0.borrow_mut().get_or_insert(e.into());
+        }
+    }
+
+    fn take(&self) -> Result<(), SidrError> {
+        match self.0.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+fn report_output(path: &Path, report_output: ReportOutput) -> Result<Box<dyn Write>, SidrError> {
+    Ok(match report_output {
+        ReportOutput::ToFile => Box::new(File::create(path)?),
+        ReportOutput::ToStdout => Box::new(BufWriter::new(io::stdout())),
+    })
+}
+
+const L2T_HEADER: &str = "date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra";
+
+// the reports written to stdout share a single header
+static L2T_STDOUT_HEADER: Once = Once::new();
+
+// Writes timeline events in the log2timeline/Plaso l2tcsv format.
+pub struct L2tWriter {
+    f: Box<dyn Write>,
+}
+
+impl L2tWriter {
+    pub fn new(path: &Path, output: ReportOutput) -> Result<Self, SidrError> {
+        let mut f = report_output(path, output)?;
+        match output {
+            ReportOutput::ToFile => writeln!(f, "{L2T_HEADER}")?,
+            ReportOutput::ToStdout => L2T_STDOUT_HEADER.call_once(|| {
+                writeln!(f, "{L2T_HEADER}").ok();
+            }),
+        }
+        Ok(L2tWriter { f })
+    }
+
+    // the fields are not quoted, l2tcsv readers split the lines on the commas
+    fn field(s: &str) -> String {
+        let s = s.replace([',', '\n', '\r'], " ");
+        if s.is_empty() {
+            "-".to_string()
+        } else {
+            s
+        }
+    }
+
+    fn macb(timestamp_kind: &str) -> &'static str {
+        if timestamp_kind.ends_with("DateModified") || timestamp_kind == "LastModified" {
+            "M..."
+        } else if timestamp_kind.ends_with("DateAccessed") {
+            ".A.."
+        } else if timestamp_kind.ends_with("DateCreated") {
+            "...B"
+        } else {
+            "...."
+        }
+    }
+
+    fn source(report: &str) -> &'static str {
+        match ReportSuffix::get_match(report) {
+            Some(ReportSuffix::InternetHistory) => "WEBHIST",
+            Some(ReportSuffix::ActivityHistory) => "LOG",
+            _ => "FILE",
+        }
+    }
+
+    pub fn write_event(&mut self, e: &TimelineEvent) -> Result<(), SidrError> {
+        let fields = [
+            e.timestamp.format("%m/%d/%Y").to_string(),
+            e.timestamp.format("%H:%M:%S").to_string(),
+            "UTC".to_string(),
+            Self::macb(&e.timestamp_kind).to_string(),
+            Self::source(&e.report).to_string(),
+            format!("Windows Search {}", e.report.replace('_', " ")),
+            e.timestamp_kind.clone(),
+            e.user.clone().unwrap_or_default(),
+            e.host.clone(),
+            e.description.clone(),
+            format!("{}: {}", e.timestamp_kind, e.description),
+            "2".to_string(),
+            e.source_db.clone(),
+            e.work_id.map(|n| n.to_string()).unwrap_or_default(),
+            String::new(),
+            "sidr".to_string(),
+            String::new(),
+        ];
+        let line: Vec<String> = fields.iter().map(|s| Self::field(s)).collect();
+        writeln!(self.f, "{}", line.join(","))?;
+        Ok(())
+    }
+}
+
+// report l2tcsv, one line per date of each record
+pub struct ReportL2t {
+    w: L2tWriter,
+    source: EventSource,
+    record: RefCell<Record>,
+    error: RowError,
+}
+
+impl ReportL2t {
+    pub fn new(path: &Path, output: ReportOutput, source: EventSource) -> Result<Self, SidrError> {
+        Ok(ReportL2t {
+            w: L2tWriter::new(path, output)?,
+            source,
+            record: RefCell::default(),
+            error: RowError::default(),
+        })
+    }
+}
+
+impl ReportL2t {
+    fn write_record(&mut self) -> Result<(), SidrError> {
+        let record = self.record.take();
+        for e in self.source.events(&record) {
+            self.w.write_event(&e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Report for ReportL2t {
+    fn footer(&mut self) {
+        finish_or_log(self);
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.error.take()?;
+        self.write_record()?;
+        Ok(self.w.f.flush()?)
+    }
+
+    fn create_new_row(&mut self) {
+        let written = self.write_record();
+        self.error.keep(written);
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.record.borrow_mut().0.push((f.to_string(), s));
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.record
+            .borrow_mut()
+            .0
+            .push((f.to_string(), n.to_string()));
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        !self.record.borrow().0.is_empty()
+    }
+}
+
+impl Drop for ReportL2t {
+    fn drop(&mut self) {
+        self.footer();
+    }
+}
+
+// report bodyfile (Sleuth Kit 3.x, for mactime), one line per record with a date:
+// MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+pub struct ReportBodyfile {
+    f: Box<dyn Write>,
+    name_fields: Option<&'static [&'static str]>,
+    record: RefCell<Record>,
+    error: RowError,
+}
+
+impl ReportBodyfile {
+    pub fn new(
+        path: &Path,
+        output: ReportOutput,
+        report_suffix: Option<ReportSuffix>,
+    ) -> Result<Self, SidrError> {
+        Ok(ReportBodyfile {
+            f: report_output(path, output)?,
+            name_fields: report_suffix.and_then(|suffix| suffix.timeline_description()),
+            record: RefCell::default(),
+            error: RowError::default(),
+        })
+    }
+
+    // Unix time of the first of fields set.
+    fn time(record: &Record, fields: &[&str]) -> Option<i64> {
+        parse_report_date(record.first_of(fields)?).map(|t| t.timestamp())
+    }
+
+    fn line(&self, record: &Record) -> Option<String> {
+        let name = record.first_of(self.name_fields?)?;
+        let atime = Self::time(record, &["System_DateAccessed"]);
+        let mtime = Self::time(record, &["System_DateModified", "LastModified"]);
+        let crtime = Self::time(record, &["System_DateCreated"]);
+        if atime.is_none() && mtime.is_none() && crtime.is_none() {
+            return None;
+        }
+        let mode = match record.get("System_IsFolder") {
+            Some("true") => "d/d---------",
+            _ => "r/r---------",
+        };
+        Some(format!(
+            "0|{}|{}|{mode}|0|0|{}|{}|{}|0|{}",
+            name.replace(['|', '\n', '\r'], "_"),
+            record.work_id().unwrap_or_default(),
+            record.get("System_Size").unwrap_or("0"),
+            atime.unwrap_or_default(),
+            mtime.unwrap_or_default(),
+            crtime.unwrap_or_default(),
+        ))
+    }
+}
+
+impl ReportBodyfile {
+    fn write_record(&mut self) -> io::Result<()> {
+        let record = self.record.take();
+        match self.line(&record) {
+            Some(line) => writeln!(self.f, "{line}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Report for ReportBodyfile {
+    fn footer(&mut self) {
+        finish_or_log(self);
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.error.take()?;
+        self.write_record()?;
+        Ok(self.f.flush()?)
+    }
+
+    fn create_new_row(&mut self) {
+        let written = self.write_record();
+        self.error.keep(written);
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.record.borrow_mut().0.push((f.to_string(), s));
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.record
+            .borrow_mut()
+            .0
+            .push((f.to_string(), n.to_string()));
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        !self.record.borrow().0.is_empty()
+    }
+}
+
+impl Drop for ReportBodyfile {
+    fn drop(&mut self) {
+        self.footer();
+    }
+}
+
+// A value of a report, typed by the method inserting it.
+enum ReportValue {
+    Int(u64),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    DateTime(DateTime<Utc>),
+    Str(String),
+}
+
+impl ReportValue {
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(n) => i64::try_from(*n).ok(),
+            Self::I64(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::F64(x) => Some(*x),
+            v => v.as_i64().map(|n| n as f64),
+        }
+    }
+
+    // as the text reports write it
+    fn text(&self) -> String {
+        match self {
+            Self::Int(n) => n.to_string(),
+            Self::I64(n) => n.to_string(),
+            Self::F64(x) => x.to_string(),
+            Self::Bool(b) => b.to_string(),
+            Self::DateTime(t) => format_date_time(*t),
+            Self::Str(s) => s.clone(),
+        }
+    }
+
+    // the dates, and the strings they are reported as (see format_date_time)
+    fn date(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::DateTime(t) => Some(*t),
+            Self::Str(s) if s.ends_with('Z') => {
+                Some(DateTime::parse_from_rfc3339(s).ok()?.with_timezone(&Utc))
+            }
+            _ => None,
+        }
+    }
+}
+
+type ParquetValues = Vec<(usize /*row*/, ReportValue)>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ParquetColumn {
+    Int64,
+    Double,
+    Boolean,
+    Timestamp,
+    String,
+}
+
+impl ParquetColumn {
+    // Nanoseconds since the Unix epoch, Some(None) for the zero FILETIMEs of the unset dates
+    // (written as nulls) and None for the dates out of the range of the nanoseconds (1677-2262).
+    fn timestamp(v: &ReportValue) -> Option<Option<i64>> {
+        let t = v.date()?;
+        if t.year() <= 1601 {
+            return Some(None);
+        }
+        t.timestamp_nanos_opt().map(Some)
+    }
+
+    // Integer, float and boolean columns keep their type and date columns become timestamps
+    // as long as all their values can be (integers and floats make a double column),
+    // the other ones (and the mixed ones) are strings.
+    fn of(values: &[(usize, ReportValue)]) -> Self {
+        let all = |f: &dyn Fn(&ReportValue) -> bool| values.iter().all(|(_, v)| f(v));
+        if values.is_empty() {
+            Self::String
+        } else if all(&|v| v.as_i64().is_some()) {
+            Self::Int64
+        } else if all(&|v| v.as_f64().is_some()) {
+            Self::Double
+        } else if all(&|v| matches!(v, ReportValue::Bool(_))) {
+            Self::Boolean
+        } else if all(&|v| Self::timestamp(v).is_some()) {
+            Self::Timestamp
+        } else {
+            Self::String
+        }
+    }
+
+    fn schema_type(self, name: &str) -> Result<Type, SidrError> {
+        let (physical_type, logical_type) = match self {
+            Self::Int64 => (PhysicalType::INT64, None),
+            Self::Double => (PhysicalType::DOUBLE, None),
+            Self::Boolean => (PhysicalType::BOOLEAN, None),
+            Self::Timestamp => (
+                PhysicalType::INT64,
+                Some(LogicalType::Timestamp {
+                    is_adjusted_to_u_t_c: true,
+                    unit: TimeUnit::NANOS(Default::default()),
+                }),
+            ),
+            Self::String => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        };
+        Ok(Type::primitive_type_builder(name, physical_type)
+            .with_repetition(Repetition::OPTIONAL)
+            .with_logical_type(logical_type)
+            .build()?)
+    }
+}
+
+// report parquet
+// The columns and their types are only known once all the records are inserted (set_field is
+// not called for all of them), so the records are kept in memory and written by footer.
+pub struct ReportParquet {
+    path: PathBuf,
+    // values by column, in the order of set_field or of their first value, with their row
+    columns: RefCell<Vec<(String, ParquetValues)>>,
+    // the current row
+    row: Cell<usize>,
+    row_has_value: Cell<bool>,
+    written: bool,
+}
+
+impl ReportParquet {
+    pub fn new(path: &Path, report_output: ReportOutput) -> Result<Self, SidrError> {
+        if report_output == ReportOutput::ToStdout {
+            return Err(SidrError::Cfg(
+                "parquet reports can't be written to stdout".to_string(),
+            ));
+        }
+        // fail now rather than after all the records
+        File::create(path)?;
+        Ok(ReportParquet {
+            path: path.to_path_buf(),
+            columns: RefCell::new(Vec::new()),
+            row: Cell::new(0),
+            row_has_value: Cell::new(false),
+            written: false,
+        })
+    }
+
+    fn column_index(&self, f: &str) -> usize {
+        let mut columns = self.columns.borrow_mut();
+        match columns.iter().position(|(name, _)| name == f) {
+            Some(i) => i,
+            None => {
+                columns.push((f.to_string(), Vec::new()));
+                columns.len() - 1
+            }
+        }
+    }
+
+    fn insert(&self, f: &str, v: ReportValue) {
+        let i = self.column_index(f);
+        let row = self.row.get();
+        let values = &mut self.columns.borrow_mut()[i].1;
+        match values.last_mut() {
+            Some(last) if last.0 == row => last.1 = v,
+            _ => values.push((row, v)),
+        }
+        self.row_has_value.set(true);
+    }
+
+    pub fn write(&self) -> Result<(), SidrError> {
+        let columns = self.columns.borrow();
+        let rows = self.row.get();
+        let kinds: Vec<ParquetColumn> = columns
+            .iter()
+            .map(|(_, values)| ParquetColumn::of(values))
+            .collect();
+        let fields = columns
+            .iter()
+            .zip(&kinds)
+            .map(|((name, _), kind)| kind.schema_type(name).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let f = File::create(&self.path)?;
+        let mut writer = SerializedFileWriter::new(f, Arc::new(schema), Arc::new(props))?;
+        let mut row_group = writer.next_row_group()?;
+        for ((_, values), kind) in columns.iter().zip(kinds) {
+            let Some(mut column) = row_group.next_column()? else {
+                break;
+            };
+            let mut def_levels = vec![0; rows];
+            for (row, _) in values {
+                def_levels[*row] = 1;
+            }
+            match kind {
+                ParquetColumn::Int64 => {
+                    let ints: Vec<i64> = values
+                        .iter()
+                        .map(|(_, v)| v.as_i64().unwrap_or(0))
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&ints, Some(&def_levels), None)?;
+                }
+                ParquetColumn::Timestamp => {
+                    let mut timestamps = Vec::new();
+                    for (row, v) in values {
+                        match ParquetColumn::timestamp(v).flatten() {
+                            Some(t) => timestamps.push(t),
+                            None => def_levels[*row] = 0,
+                        }
+                    }
+                    column.typed::<Int64Type>().write_batch(
+                        &timestamps,
+                        Some(&def_levels),
+                        None,
+                    )?;
+                }
+                ParquetColumn::Double => {
+                    let floats: Vec<f64> = values
+                        .iter()
+                        .map(|(_, v)| v.as_f64().unwrap_or(0.0))
+                        .collect();
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&floats, Some(&def_levels), None)?;
+                }
+                ParquetColumn::Boolean => {
+                    let bools: Vec<bool> = values
+                        .iter()
+                        .map(|(_, v)| matches!(v, ReportValue::Bool(true)))
+                        .collect();
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&bools, Some(&def_levels), None)?;
+                }
+                ParquetColumn::String => {
+                    let strings: Vec<ByteArray> = values
+                        .iter()
+                        .map(|(_, v)| v.text().into_bytes().into())
+                        .collect();
+                    column.typed::<ByteArrayType>().write_batch(
+                        &strings,
+                        Some(&def_levels),
+                        None,
+                    )?;
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+impl Report for ReportParquet {
+    fn footer(&mut self) {
+        finish_or_log(self);
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.create_new_row();
+        if std::mem::replace(&mut self.written, true) {
+            return Ok(());
+        }
+        self.write()
+    }
+
+    fn create_new_row(&mut self) {
+        if self.row_has_value.replace(false) {
+            self.row.set(self.row.get() + 1);
+        }
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.insert(f, ReportValue::Str(s));
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.insert(f, ReportValue::Int(n));
+    }
+
+    fn insert_i64(&self, f: &str, n: i64) {
+        self.insert(f, ReportValue::I64(n));
+    }
+
+    fn insert_f64(&self, f: &str, x: f64) {
+        self.insert(f, ReportValue::F64(x));
+    }
+
+    fn insert_bool(&self, f: &str, b: bool) {
+        self.insert(f, ReportValue::Bool(b));
+    }
+
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        self.insert(f, ReportValue::DateTime(t));
+    }
+
+    fn set_field(&self, f: &str) {
+        self.column_index(f);
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        self.row_has_value.get()
+    }
+}
+
+impl Drop for ReportParquet {
+    fn drop(&mut self) {
+        self.footer();
+    }
+}
+
+// The output database of ReportFormat::Sqlite, shared by the reports of all the databases.
+pub struct SqliteOutput {
+    path: PathBuf,
+    conn: sqlite::Connection,
+    // sources.id of the databases, by source_db
+    sources: RefCell<HashMap<String, i64>>,
+    // the rows of the reports being written are inserted in a single transaction
+    open_reports: Cell<usize>,
+    // the processed time of the sources is left out
+    deterministic: bool,
+}
+
+impl SqliteOutput {
+    pub fn open(path: &Path, deterministic: bool) -> Result<Self, SidrError> {
+        let conn = sqlite::Connection::open(path)?;
+        conn.execute(
+            "create table if not exists sources (
+                id integer primary key,
+                path text,
+                sha256 text,
+                hostname text,
+                db_state text,
+                processed text
+            )",
+        )?;
+        Ok(SqliteOutput {
+            path: path.to_path_buf(),
+            conn,
+            sources: RefCell::new(HashMap::new()),
+            open_reports: Cell::new(0),
+            deterministic,
+        })
+    }
+
+    // Adds the database to the sources table, once per run.
+    fn source_id(
+        &self,
+        sha256: Option<&str>,
+        source_db: &str,
+        hostname: &str,
+        db_state: Option<DbState>,
+    ) -> Result<i64, SidrError> {
+        if let Some(id) = self.sources.borrow().get(source_db) {
+            return Ok(*id);
+        }
+        let mut s = self.conn.prepare(
+            "insert into sources (path, sha256, hostname, db_state, processed)
+             values (?, ?, ?, ?, ?)",
+        )?;
+        s.bind((1, source_db))?;
+        s.bind((2, sha256))?;
+        s.bind((3, hostname))?;
+        s.bind((4, db_state.map(|state| format!("{state:?}")).as_deref()))?;
+        let processed = (!self.deterministic).then(|| format_date_time(Utc::now()));
+        s.bind((5, processed.as_deref()))?;
+        s.next()?;
+        let mut s = self.conn.prepare("select last_insert_rowid() as id")?;
+        s.next()?;
+        let id = s.read::<i64, _>("id")?;
+        self.sources.borrow_mut().insert(source_db.to_string(), id);
+        Ok(id)
+    }
+
+    fn begin_report(&self) -> Result<(), SidrError> {
+        if self.open_reports.get() == 0 {
+            self.conn.execute("begin")?;
+        }
+        self.open_reports.set(self.open_reports.get() + 1);
+        Ok(())
+    }
+
+    fn end_report(&self) -> Result<(), SidrError> {
+        self.open_reports.set(self.open_reports.get() - 1);
+        if self.open_reports.get() == 0 {
+            self.conn.execute("commit")?;
+        }
+        Ok(())
+    }
+}
+
+fn quote_identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+// An INSERT statement, keeping the output database it is prepared on open.
+type InsertStatement = OwningHandle<Rc<SqliteOutput>, Box<sqlite::Statement<'static>>>;
+
+// report sqlite, a table per report, with a column per field added as the fields show up
+pub struct ReportSqlite {
+    output: Rc<SqliteOutput>,
+    table: String,
+    source_id: Option<i64>,
+    columns: RefCell<Vec<String>>,
+    // by the fields of the records they insert
+    inserts: RefCell<HashMap<Vec<String>, InsertStatement>>,
+    record: RefCell<Vec<(String, ReportValue)>>,
+    error: RowError,
+    closed: bool,
+}
+
+impl ReportSqlite {
+    pub fn new(
+        output: Rc<SqliteOutput>,
+        report_suffix: &str,
+        source_id: Option<i64>,
+    ) -> Result<Self, SidrError> {
+        let table = Self::table_name(report_suffix);
+        output.conn.execute(format!(
+            "create table if not exists {} (source_id integer references sources(id))",
+            quote_identifier(&table)
+        ))?;
+        // the table may come from a previous run
+        let mut columns = Vec::new();
+        {
+            let mut s = output
+                .conn
+                .prepare(format!("pragma table_info({})", quote_identifier(&table)))?;
+            while let State::Row = s.next()? {
+                columns.push(s.read::<String, _>("name")?);
+            }
+        }
+        output.begin_report()?;
+        Ok(ReportSqlite {
+            output,
+            table,
+            source_id,
+            columns: RefCell::new(columns),
+            inserts: RefCell::new(HashMap::new()),
+            record: RefCell::new(Vec::new()),
+            error: RowError::default(),
+            closed: false,
+        })
+    }
+
+    // "File_Report" -> "file_report", "File_Report_without_WAL" -> "file_report_without_wal"
+    fn table_name(report_suffix: &str) -> String {
+        let name = match ReportSuffix::get_match(report_suffix) {
+            Some(suffix) if suffix != ReportSuffix::Unknown => suffix.name().to_string(),
+            // the titles of the reports of a configuration
+            _ => report_suffix
+                .trim_end_matches("_without_WAL")
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+                .to_ascii_lowercase(),
+        };
+        if report_suffix.ends_with("_without_WAL") {
+            format!("{name}_without_wal")
+        } else {
+            name
+        }
+    }
+
+    // the column names are case insensitive
+    fn insert(&self, f: &str, v: ReportValue) {
+        let mut record = self.record.borrow_mut();
+        match record
+            .iter_mut()
+            .find(|(name, _)| name.eq_ignore_ascii_case(f))
+        {
+            Some(found) => found.1 = v,
+            None => record.push((f.to_string(), v)),
+        }
+    }
+
+    fn add_column(&self, f: &str) -> Result<(), SidrError> {
+        if !self
+            .columns
+            .borrow()
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(f))
+        {
+            self.output.conn.execute(format!(
+                "alter table {} add column {}",
+                quote_identifier(&self.table),
+                quote_identifier(f)
+            ))?;
+            self.columns.borrow_mut().push(f.to_string());
+        }
+        Ok(())
+    }
+
+    fn write_record(&self) -> Result<(), SidrError> {
+        let record = self.record.take();
+        if record.is_empty() {
+            return Ok(());
+        }
+        let fields: Vec<String> = record.iter().map(|(f, _)| f.clone()).collect();
+        let mut inserts = self.inserts.borrow_mut();
+        let s = match inserts.entry(fields) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let mut names = vec![quote_identifier("source_id")];
+                for f in e.key() {
+                    self.add_column(f)?;
+                    names.push(quote_identifier(f));
+                }
+                let q = format!(
+                    "insert into {} ({}) values ({})",
+                    quote_identifier(&self.table),
+                    names.join(","),
+                    vec!["?"; names.len()].join(",")
+                );
+                // the statement borrows the connection held by the Rc it is kept with
+                let s = OwningHandle::try_new(self.output.clone(), |o| unsafe {
+                    (*o).conn.prepare(q).map(Box::new)
+                })?;
+                e.insert(s)
+            }
+        };
+        s.reset()?;
+        s.bind((1, self.source_id))?;
+        for (i, (_, v)) in record.iter().enumerate() {
+            match v {
+                ReportValue::F64(x) => s.bind((i + 2, *x))?,
+                ReportValue::Bool(b) => s.bind((i + 2, i64::from(*b)))?,
+                ReportValue::Str(v) => s.bind((i + 2, v.as_str()))?,
+                // the dates are ISO-8601 text as in the other reports, and so are the u64
+                // values above i64::MAX
+                v => match v.as_i64() {
+                    Some(n) => s.bind((i + 2, n))?,
+                    None => s.bind((i + 2, v.text().as_str()))?,
+                },
+            }
+        }
+        s.next()?;
+        Ok(())
+    }
+}
+
+impl Report for ReportSqlite {
+    fn footer(&mut self) {
+        finish_or_log(self);
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        // the transaction is ended even if the records can't be written
+        let written = self.error.take().and_then(|_| self.write_record());
+        if !std::mem::replace(&mut self.closed, true) {
+            self.output.end_report()?;
+        }
+        written
+    }
+
+    fn create_new_row(&mut self) {
+        self.error.keep(self.write_record());
+    }
+
+    fn insert_str_val(&self, f: &str, s: String) {
+        self.insert(f, ReportValue::Str(s));
+    }
+
+    fn insert_int_val(&self, f: &str, n: u64) {
+        self.insert(f, ReportValue::Int(n));
+    }
+
+    fn insert_i64(&self, f: &str, n: i64) {
+        self.insert(f, ReportValue::I64(n));
+    }
+
+    fn insert_f64(&self, f: &str, x: f64) {
+        self.insert(f, ReportValue::F64(x));
+    }
+
+    fn insert_bool(&self, f: &str, b: bool) {
+        self.insert(f, ReportValue::Bool(b));
+    }
+
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        self.insert(f, ReportValue::DateTime(t));
+    }
+
+    fn set_field(&self, f: &str) {
+        self.error.keep(self.add_column(f));
+    }
+
+    fn is_some_val_in_record(&self) -> bool {
+        !self.record.borrow().is_empty()
+    }
+}
+
+impl Drop for ReportSqlite {
+    fn drop(&mut self) {
+        self.footer();
+    }
+}
+
 // report json
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum JsonStyle {
+    // a record per line (JSON lines)
+    #[default]
+    Lines,
+    // a document per report, the array of its records
+    Array,
+    // same as Array, pretty-printed
+    Pretty,
+}
+
+// The records are JSON objects, a field set twice in a record gets a suffix: "name_2", ...
 pub struct ReportJson {
     f: Box<dyn Write + 'static>,
     report_output: ReportOutput,
     report_suffix: Option<ReportSuffix>,
-    values: RefCell<Vec<String>>,
+    style: JsonStyle,
+    values: RefCell<Map<String, Value>>,
+    rows: usize,
+    error: RowError,
+    closed: bool,
 }
 
 impl ReportJson {
@@ -168,84 +1867,133 @@ impl ReportJson {
         path: &Path,
         report_output: ReportOutput,
         report_suffix: Option<ReportSuffix>,
-    ) -> Result<Self, SimpleError> {
-        match report_output {
-            ReportOutput::ToFile => {
-                let output: Box<dyn Write> =
-                    Box::new(File::create(path).map_err(|e| SimpleError::new(format!("{e}")))?);
-                Ok(ReportJson {
-                    f: output,
-                    report_output,
-                    report_suffix: None,
-                    values: RefCell::new(Vec::new()),
-                })
-            }
-            ReportOutput::ToStdout => Ok(ReportJson {
-                f: Box::new(BufWriter::new(io::stdout())),
-                report_output,
-                report_suffix,
-                values: RefCell::new(Vec::new()),
-            }),
+        style: JsonStyle,
+    ) -> Result<Self, SidrError> {
+        let (f, report_suffix): (Box<dyn Write>, _) = match report_output {
+            ReportOutput::ToFile => (Box::new(File::create(path)?), None),
+            ReportOutput::ToStdout => (Box::new(BufWriter::new(io::stdout())), report_suffix),
+        };
+        Ok(ReportJson {
+            f,
+            report_output,
+            report_suffix,
+            style,
+            values: RefCell::new(Map::new()),
+            rows: 0,
+            error: RowError::default(),
+            closed: false,
+        })
+    }
+
+    // Sets a field of the current record to a typed value (number, bool, array, null, ...).
+    // A field already set in the record is not overwritten, the value gets the next free key
+    // among "name_2", "name_3", ...
+    pub fn insert_value(&self, f: &str, v: impl Into<Value>) {
+        let mut values = self.values.borrow_mut();
+        let mut key = f.to_string();
+        let mut n = 1;
+        while values.contains_key(&key) {
+            n += 1;
+            key = format!("{f}_{n}");
         }
+        values.insert(key, v.into());
     }
 
-    fn escape(s: String) -> String {
-        json_escape(&s)
+    // the dates are written as in the other reports, with 7 fractional digits
+    pub fn insert_date_time(&self, f: &str, t: DateTime<Utc>) {
+        self.insert_value(f, format_date_time(t));
     }
 
-    pub fn write_values(&mut self) {
-        let mut values = self.values.borrow_mut();
-        let len = values.len();
-        let handle = self.f.as_mut();
-        if len > 0 {
-            handle.write_all(b"{").unwrap();
-        }
+    pub fn write_values(&mut self) -> io::Result<()> {
+        let mut values = self.values.take();
         if self.report_output == ReportOutput::ToStdout {
-            handle
-                .write_all(
-                    format!(
-                        "{}:{},",
-                        serde_json::to_string("report_suffix").unwrap(),
-                        self.report_suffix.as_ref().unwrap()
-                    )
-                    .as_bytes(),
-                )
-                .ok();
+            let report_suffix = self.report_suffix.as_ref().unwrap().name();
+            let mut record = Map::from_iter([("report_suffix".to_string(), report_suffix.into())]);
+            record.append(&mut values);
+            values = record;
         }
-        for i in 0..len {
-            let v = values.index_mut(i);
-            if !v.is_empty() {
-                let last = if i == len - 1 { "" } else { "," };
-                handle.write_all(format!("{v}{last}").as_bytes()).unwrap();
+        let handle = self.f.as_mut();
+        match self.style {
+            JsonStyle::Lines => {
+                serde_json::to_writer(&mut *handle, &values)?;
+                handle.write_all(b"\n")?;
+            }
+            JsonStyle::Array => {
+                handle.write_all(if self.rows == 0 { b"[\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut *handle, &values)?;
+            }
+            // indented as the elements of a pretty-printed array,
+            // the strings hold no line break once escaped
+            JsonStyle::Pretty => {
+                handle.write_all(if self.rows == 0 { b"[\n  " } else { b",\n  " })?;
+                let record = serde_json::to_string_pretty(&values)?;
+                handle.write_all(record.replace('\n', "\n  ").as_bytes())?;
             }
         }
-        if len > 0 {
-            handle.write_all(b"}\n").unwrap();
-            values.clear();
+        self.rows += 1;
+        handle.flush()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if std::mem::replace(&mut self.closed, true) || self.style == JsonStyle::Lines {
+            return Ok(());
         }
-        handle.flush().unwrap();
+        let handle = self.f.as_mut();
+        handle.write_all(if self.rows == 0 { b"[]\n" } else { b"\n]\n" })?;
+        handle.flush()
     }
 }
 
 impl Report for ReportJson {
     fn footer(&mut self) {
-        self.create_new_row();
+        finish_or_log(self);
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.error.take()?;
+        if !self.values.borrow().is_empty() {
+            self.write_values()?;
+        }
+        Ok(self.close()?)
     }
 
     fn create_new_row(&mut self) {
         if !self.values.borrow().is_empty() {
-            self.write_values();
+            let written = self.write_values();
+            self.error.keep(written);
         }
     }
 
     fn insert_str_val(&self, f: &str, s: String) {
-        self.values
-            .borrow_mut()
-            .push(format!("\"{}\":{}", f, ReportJson::escape(s)));
+        self.insert_value(f, s);
     }
 
     fn insert_int_val(&self, f: &str, n: u64) {
-        self.values.borrow_mut().push(format!("\"{f}\":{n}"));
+        self.insert_value(f, n);
+    }
+
+    fn insert_i64(&self, f: &str, n: i64) {
+        self.insert_value(f, n);
+    }
+
+    // NaN and the infinities are not JSON numbers
+    fn insert_f64(&self, f: &str, x: f64) {
+        match serde_json::Number::from_f64(x) {
+            Some(x) => self.insert_value(f, x),
+            None => self.insert_value(f, x.to_string()),
+        }
+    }
+
+    fn insert_bool(&self, f: &str, b: bool) {
+        self.insert_value(f, b);
+    }
+
+    fn insert_datetime(&self, f: &str, t: DateTime<Utc>) {
+        self.insert_date_time(f, t);
+    }
+
+    fn insert_list(&self, f: &str, l: Vec<String>) {
+        self.insert_value(f, l);
     }
 
     fn is_some_val_in_record(&self) -> bool {
@@ -260,12 +2008,80 @@ impl Drop for ReportJson {
 }
 
 // report csv
+const CSV_SPOOL_FILE: &str = "rows.csv";
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// the reports written to stdout share a single byte order mark
+static CSV_STDOUT_BOM: Once = Once::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum CsvQuoting {
+    // the values holding a delimiter, a quote or a line break
+    #[default]
+    Necessary,
+    Always,
+    // the values which are not numbers
+    NonNumeric,
+    Never,
+}
+
+impl From<CsvQuoting> for csv::QuoteStyle {
+    fn from(q: CsvQuoting) -> Self {
+        match q {
+            CsvQuoting::Necessary => csv::QuoteStyle::Necessary,
+            CsvQuoting::Always => csv::QuoteStyle::Always,
+            CsvQuoting::NonNumeric => csv::QuoteStyle::NonNumeric,
+            CsvQuoting::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quoting: CsvQuoting,
+    // keep the line breaks of the values instead of writing them as \n and \r
+    pub preserve_newlines: bool,
+    // start the reports with a UTF-8 byte order mark, for Excel
+    pub bom: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quoting: CsvQuoting::default(),
+            preserve_newlines: false,
+            bom: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    // A single ASCII character, or "tab".
+    pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+        match s {
+            "tab" | "\\t" => Ok(b'\t'),
+            _ if s.len() == 1 && s.is_ascii() && !matches!(s, "\"" | "\n" | "\r") => {
+                Ok(s.as_bytes()[0])
+            }
+            _ => Err(format!("'{s}' is not a single ASCII character")),
+        }
+    }
+}
+
+// The records of a database don't all have the same properties, so the rows are spooled to a
+// temporary file while the columns are collected, and the header and the rows, padded to all
+// the columns, are written when the report is closed.
 pub struct ReportCsv {
     f: Box<dyn Write + 'static>,
     report_output: ReportOutput,
     report_suffix: Option<ReportSuffix>,
-    first_record: Cell<bool>,
-    values: RefCell<Vec<(String /*field*/, String /*value*/)>>,
+    options: CsvOptions,
+    values: RefCell<Vec<(String /*field*/, Option<String> /*value*/)>>,
+    rows: usize,
+    spool: Option<(tempdir::TempDir, csv::Writer<File>)>,
+    error: RowError,
 }
 
 impl ReportCsv {
@@ -273,78 +2089,95 @@ impl ReportCsv {
         f: &Path,
         report_output: ReportOutput,
         report_suffix: Option<ReportSuffix>,
-    ) -> Result<Self, SimpleError> {
-        match report_output {
-            ReportOutput::ToFile => {
-                let output: Box<dyn Write> =
-                    Box::new(File::create(f).map_err(|e| SimpleError::new(format!("{e}")))?);
-                Ok(ReportCsv {
-                    f: output,
-                    report_output,
-                    report_suffix: None,
-                    first_record: Cell::new(true),
-                    values: RefCell::new(Vec::new()),
-                })
-            }
-            ReportOutput::ToStdout => Ok(ReportCsv {
-                f: Box::new(BufWriter::new(io::stdout())),
-                report_output,
-                report_suffix,
-                first_record: Cell::new(true),
-                values: RefCell::new(Vec::new()),
-            }),
+        options: CsvOptions,
+    ) -> Result<Self, SidrError> {
+        let (output, report_suffix): (Box<dyn Write>, _) = match report_output {
+            ReportOutput::ToFile => (Box::new(File::create(f)?), None),
+            ReportOutput::ToStdout => (Box::new(BufWriter::new(io::stdout())), report_suffix),
+        };
+        let dir = tempdir::TempDir::new("sidr_csv")?;
+        // the rows don't have the same number of columns yet
+        let spool = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_path(dir.path().join(CSV_SPOOL_FILE))
+            .map_err(io::Error::from)?;
+        Ok(ReportCsv {
+            f: output,
+            report_output,
+            report_suffix,
+            options,
+            values: RefCell::new(Vec::new()),
+            rows: 0,
+            spool: Some((dir, spool)),
+            error: RowError::default(),
+        })
+    }
+
+    fn escape_newlines(&self, s: String) -> String {
+        if self.options.preserve_newlines {
+            s
+        } else {
+            s.replace('\n', "\\n").replace('\r', "\\r")
         }
     }
 
-    fn escape(s: String) -> String {
-        s.replace('\"', "\"\"")
-            .replace("\n", "\\n")
-            .replace("\r", "\\r")
+    fn spool_values(&mut self) -> io::Result<()> {
+        let Some((_, spool)) = self.spool.as_mut() else {
+            return Ok(());
+        };
+        let mut values = self.values.borrow_mut();
+        spool.write_record(values.iter().map(|v| v.1.as_deref().unwrap_or_default()))?;
+        values.iter_mut().for_each(|v| v.1 = None);
+        self.rows += 1;
+        Ok(())
     }
 
-    pub fn write_header(&mut self) {
+    fn write_report(&mut self) -> io::Result<()> {
+        let Some((dir, spool)) = self.spool.take() else {
+            return Ok(());
+        };
+        if self.rows == 0 {
+            return Ok(());
+        }
+        spool.into_inner().map_err(|e| e.into_error())?;
         let handle = self.f.as_mut();
-        if self.report_output == ReportOutput::ToStdout {
-            handle.write_all(b"\nReportSuffix,").ok();
+        if self.options.bom {
+            match self.report_output {
+                ReportOutput::ToFile => handle.write_all(UTF8_BOM)?,
+                ReportOutput::ToStdout => CSV_STDOUT_BOM.call_once(|| {
+                    handle.write_all(UTF8_BOM).ok();
+                }),
+            }
         }
+        let mut f = csv::WriterBuilder::new()
+            .delimiter(self.options.delimiter)
+            .quote_style(self.options.quoting.into())
+            .from_writer(handle);
         let values = self.values.borrow();
-        for i in 0..values.len() {
-            let v = &values[i];
-            if i == values.len() - 1 {
-                handle.write_all(v.0.as_bytes()).unwrap();
-            } else {
-                handle.write_all(format!("{},", v.0).as_bytes()).unwrap();
-            }
+        let report_suffix = match self.report_output {
+            ReportOutput::ToFile => None,
+            ReportOutput::ToStdout => self.report_suffix.as_ref().map(ReportSuffix::name),
+        };
+        let header = values.iter().map(|v| v.0.as_str());
+        match report_suffix {
+            Some(_) => f.write_record(iter::once("ReportSuffix").chain(header))?,
+            None => f.write_record(header)?,
         }
-    }
-
-    pub fn write_values(&mut self) {
-        let handle = self.f.as_mut();
-        handle.write_all(b"\n").unwrap();
 
-        let mut values = self.values.borrow_mut();
-        let len = values.len();
-        if self.report_output == ReportOutput::ToStdout {
-            handle
-                .write_all(format!("{},", self.report_suffix.as_ref().unwrap()).as_bytes())
-                .ok();
-        }
-        for i in 0..len {
-            let v = values.index_mut(i);
-            let last = if i == len - 1 { "" } else { "," };
-            if v.1.is_empty() {
-                handle.write_all(last.to_string().as_bytes()).unwrap();
-            } else {
-                handle
-                    .write_all(format!("{}{}", v.1, last).as_bytes())
-                    .unwrap();
-                v.1.clear();
-            }
+        let mut rows = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(dir.path().join(CSV_SPOOL_FILE))?;
+        for row in rows.records() {
+            let row = row?;
+            let padding = iter::repeat_n("", values.len() - row.len());
+            let row = report_suffix.into_iter().chain(&row);
+            f.write_record(row.chain(padding))?;
         }
-        handle.flush().unwrap();
+        f.flush()
     }
 
-    pub fn update_field_with_value(&self, f: &str, v: String) {
+    pub fn update_field_with_value(&self, f: &str, v: Option<String>) {
         let mut values = self.values.borrow_mut();
         if let Some(found) = values.iter_mut().find(|i| i.0 == f) {
             found.1 = v;
@@ -356,35 +2189,48 @@ impl ReportCsv {
 
 impl Report for ReportCsv {
     fn footer(&mut self) {
-        self.create_new_row();
+        finish_or_log(self);
+    }
+
+    fn finish(&mut self) -> Result<(), SidrError> {
+        self.error.take()?;
+        if self.is_some_val_in_record() {
+            self.spool_values()?;
+        }
+        Ok(self.write_report()?)
     }
 
     fn create_new_row(&mut self) {
         // at least 1 value was recorded?
         if self.is_some_val_in_record() {
-            if self.first_record.get() {
-                self.write_header();
-                self.first_record.set(false);
-            }
-            self.write_values();
+            let spooled = self.spool_values();
+            self.error.keep(spooled);
         }
     }
 
     fn insert_str_val(&self, f: &str, s: String) {
-        self.update_field_with_value(f, format!("\"{}\"", ReportCsv::escape(s)));
+        self.update_field_with_value(f, Some(self.escape_newlines(s)));
     }
 
     fn insert_int_val(&self, f: &str, n: u64) {
-        self.update_field_with_value(f, n.to_string());
+        self.update_field_with_value(f, Some(n.to_string()));
+    }
+
+    // the other typed values are written as their default text
+    fn insert_i64(&self, f: &str, n: i64) {
+        self.update_field_with_value(f, Some(n.to_string()));
     }
 
     fn set_field(&self, f: &str) {
-        // set field with empty value to record field name
-        self.update_field_with_value(f, "".to_string());
+        // set field without value to record field name, keeping the value of the current record
+        let mut values = self.values.borrow_mut();
+        if !values.iter().any(|i| i.0 == f) {
+            values.push((f.into(), None));
+        }
     }
 
     fn is_some_val_in_record(&self) -> bool {
-        self.values.borrow().iter().any(|i| !i.1.is_empty())
+        self.values.borrow().iter().any(|i| i.1.is_some())
     }
 }
 
@@ -396,10 +2242,13 @@ impl Drop for ReportCsv {
 
 #[cfg(test)]
 mod tests {
+    use crate::error::SidrError;
     use crate::report::{
-        Report, ReportCsv, ReportFormat, ReportJson, ReportOutput, ReportProducer, ReportSuffix,
+        CsvOptions, CsvQuoting, DbSource, JsonStyle, Report, ReportCsv, ReportFormat, ReportJson,
+        ReportOutput, ReportProducer, ReportSource, ReportSqlite, ReportSuffix,
     };
-    use chrono::{DateTime, NaiveDate, Utc};
+    use crate::utils::get_date_time_from_filetime;
+    use chrono::{NaiveDate, TimeZone, Utc};
     use ese_parser_lib::parser::jet::DbState;
     use std::path::Path;
 
@@ -409,7 +2258,8 @@ mod tests {
         let report_type = ReportOutput::ToFile;
         let report_suffix = None;
         {
-            let mut r = ReportCsv::new(p, report_type, report_suffix).unwrap();
+            let mut r =
+                ReportCsv::new(p, report_type, report_suffix, CsvOptions::default()).unwrap();
             r.set_field("int_field");
             r.set_field("str_field");
             r.insert_int_val("int_field", 0);
@@ -424,28 +2274,139 @@ mod tests {
             }
         }
         let data = std::fs::read_to_string(p).unwrap();
-        let expected = r#"int_field,str_field
-0,"string0"
+        let expected = "int_field,str_field
+0,string0
 1,
-,"string2"
+,string2
 3,
-,"string4"
+,string4
 5,
-,"string6"
+,string6
 7,
-,"string8"
-9,"#;
+,string8
+9,
+";
+        assert_eq!(data, expected);
+        std::fs::remove_file(p).unwrap();
+    }
+
+    #[test]
+    pub fn test_report_csv_late_columns() {
+        let p = Path::new("test_late_columns.csv");
+        {
+            let mut r =
+                ReportCsv::new(p, ReportOutput::ToFile, None, CsvOptions::default()).unwrap();
+            r.insert_int_val("int_field", 0);
+            r.create_new_row();
+            r.insert_str_val("str_field", "string1".into());
+            r.create_new_row();
+            r.insert_int_val("int_field", 2);
+            r.insert_str_val("late_field", "with \"quotes\",\nand a line break".into());
+        }
+        let data = std::fs::read_to_string(p).unwrap();
+        let expected = r#"int_field,str_field,late_field
+0,,
+,string1,
+2,,"with ""quotes"",\nand a line break"
+"#;
         assert_eq!(data, expected);
         std::fs::remove_file(p).unwrap();
     }
 
+    const CSV_VALUES: [&str; 6] = [
+        "plain",
+        "with, a comma",
+        "with \"quotes\"",
+        "C:\\Users\\line\r\nbreaks\n",
+        "tab\tand ; semicolon",
+        "",
+    ];
+
+    // Writes CSV_VALUES in a column each, a row per value, and parses the report back.
+    fn csv_round_trip(options: CsvOptions) -> (Vec<u8>, Vec<Vec<String>>) {
+        let dir = tempdir::TempDir::new("sidr_csv_test").unwrap();
+        let p = dir.path().join("test.csv");
+        {
+            let mut r = ReportCsv::new(&p, ReportOutput::ToFile, None, options.clone()).unwrap();
+            for (i, v) in CSV_VALUES.iter().enumerate() {
+                r.create_new_row();
+                r.insert_int_val("n", v.len() as u64);
+                r.insert_str_val(&format!("v{i}"), v.to_string());
+            }
+        }
+        let data = std::fs::read(&p).unwrap();
+        let body = data
+            .strip_prefix(b"\xEF\xBB\xBF".as_slice())
+            .unwrap_or(&data);
+        let rows = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .has_headers(false)
+            .from_reader(body)
+            .records()
+            .map(|r| r.unwrap().iter().map(String::from).collect())
+            .collect();
+        (data, rows)
+    }
+
+    #[test]
+    pub fn test_report_csv_round_trip() {
+        for delimiter in [b',', b';', b'\t'] {
+            for quoting in [
+                CsvQuoting::Necessary,
+                CsvQuoting::Always,
+                CsvQuoting::NonNumeric,
+            ] {
+                let options = CsvOptions {
+                    delimiter,
+                    quoting,
+                    preserve_newlines: true,
+                    bom: false,
+                };
+                let (_, rows) = csv_round_trip(options);
+                assert_eq!(rows[0], ["n", "v0", "v1", "v2", "v3", "v4", "v5"]);
+                assert_eq!(rows.len(), CSV_VALUES.len() + 1);
+                for (i, v) in CSV_VALUES.iter().enumerate() {
+                    let row = &rows[i + 1];
+                    assert_eq!(row.len(), CSV_VALUES.len() + 1);
+                    assert_eq!(row[0], v.len().to_string());
+                    assert_eq!(row[i + 1], *v);
+                    assert!(row[1..]
+                        .iter()
+                        .enumerate()
+                        .all(|(j, c)| j == i || c.is_empty()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_report_csv_options() {
+        // the line breaks are escaped by default, every row stays on a line
+        let (data, rows) = csv_round_trip(CsvOptions::default());
+        assert_eq!(rows[4][4], "C:\\Users\\line\\r\\nbreaks\\n");
+        assert_eq!(data.iter().filter(|&&b| b == b'\n').count(), rows.len());
+
+        let (data, rows) = csv_round_trip(CsvOptions {
+            quoting: CsvQuoting::Always,
+            bom: true,
+            ..CsvOptions::default()
+        });
+        assert!(data.starts_with(b"\xEF\xBB\xBF\"n\",\"v0\","));
+        assert_eq!(rows[1][1], "plain");
+
+        assert_eq!(CsvOptions::parse_delimiter(";"), Ok(b';'));
+        assert_eq!(CsvOptions::parse_delimiter("tab"), Ok(b'\t'));
+        assert!(CsvOptions::parse_delimiter("\"").is_err());
+        assert!(CsvOptions::parse_delimiter(";;").is_err());
+    }
+
     #[test]
     pub fn test_report_jsonl() {
         let p = Path::new("test.json");
         let report_type = ReportOutput::ToFile;
         let report_suffix = Some(ReportSuffix::FileReport);
         {
-            let mut r = ReportJson::new(p, report_type, report_suffix).unwrap();
+            let mut r = ReportJson::new(p, report_type, report_suffix, JsonStyle::Lines).unwrap();
             r.insert_int_val("int_field", 0);
             r.insert_str_val("str_field", "string0_with_escapes_here1\"here2\\".into());
             for i in 1..10 {
@@ -473,11 +2434,503 @@ mod tests {
         std::fs::remove_file(p).unwrap();
     }
 
+    // A record with typed values, a field set twice and a string with escapes.
+    fn write_json(p: &Path, style: JsonStyle) -> String {
+        {
+            let mut r = ReportJson::new(p, ReportOutput::ToFile, None, style).unwrap();
+            r.insert_int_val("WorkId", 1);
+            r.insert_str_val("System_ItemName", "a \"b\"\n".into());
+            r.insert_value("System_IsFolder", true);
+            r.insert_value("System_Rating", -1.5);
+            r.insert_value("System_Keywords", vec!["x", "y"]);
+            r.insert_value("System_Title", serde_json::Value::Null);
+            let t = NaiveDate::from_ymd_opt(2023, 3, 7)
+                .unwrap()
+                .and_hms_nano_opt(1, 52, 44, 123456700)
+                .unwrap()
+                .and_utc();
+            r.insert_date_time("System_DateModified", t);
+            r.insert_int_val("WorkId", 2);
+            r.create_new_row();
+            r.insert_int_val("WorkId", 3);
+        }
+        let data = std::fs::read_to_string(p).unwrap();
+        std::fs::remove_file(p).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_report_json_styles() {
+        let record = r#"{"WorkId":1,"System_ItemName":"a \"b\"\n","System_IsFolder":true,"System_Rating":-1.5,"System_Keywords":["x","y"],"System_Title":null,"System_DateModified":"2023-03-07T01:52:44.1234567Z","WorkId_2":2}"#;
+        let p = Path::new("test_styles.json");
+        assert_eq!(
+            write_json(p, JsonStyle::Lines),
+            format!("{record}\n{{\"WorkId\":3}}\n")
+        );
+        let array = write_json(p, JsonStyle::Array);
+        assert_eq!(array, format!("[\n{record},\n{{\"WorkId\":3}}\n]\n"));
+
+        let pretty = write_json(p, JsonStyle::Pretty);
+        assert!(pretty.starts_with("[\n  {\n    \"WorkId\": 1,\n    \"System_ItemName\""));
+        assert!(pretty.ends_with("\n  },\n  {\n    \"WorkId\": 3\n  }\n]\n"));
+        let array: serde_json::Value = serde_json::from_str(&array).unwrap();
+        let pretty: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(array, pretty);
+
+        {
+            let _ = ReportJson::new(p, ReportOutput::ToFile, None, JsonStyle::Array).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(p).unwrap(), "[]\n");
+        std::fs::remove_file(p).unwrap();
+    }
+
+    #[test]
+    fn test_report_json_duplicates() {
+        let p = Path::new("test_duplicates.json");
+        {
+            let mut r = ReportJson::new(p, ReportOutput::ToFile, None, JsonStyle::Lines).unwrap();
+            r.insert_str_val("Name", "a".into());
+            r.insert_str_val("Name_2", "b".into());
+            r.insert_str_val("Name", "c".into());
+            r.insert_str_val("Name", "d".into());
+            r.create_new_row();
+            r.insert_str_val("Name", "e".into());
+        }
+        let data = std::fs::read_to_string(p).unwrap();
+        std::fs::remove_file(p).unwrap();
+        assert_eq!(
+            data,
+            "{\"Name\":\"a\",\"Name_2\":\"b\",\"Name_3\":\"c\",\"Name_4\":\"d\"}\n{\"Name\":\"e\"}\n"
+        );
+    }
+
+    // the writes to /dev/full fail with ENOSPC
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_report_finish_error() {
+        let p = Path::new("/dev/full");
+        let mut r = ReportJson::new(p, ReportOutput::ToFile, None, JsonStyle::Array).unwrap();
+        r.insert_int_val("WorkId", 1);
+        assert!(r.finish().is_err());
+        // dropping it only logs the error
+        drop(r);
+
+        let mut r = ReportCsv::new(p, ReportOutput::ToFile, None, CsvOptions::default()).unwrap();
+        r.insert_int_val("WorkId", 1);
+        assert!(r.finish().is_err());
+        // the error of writing a row is returned by finish
+        let mut r = ReportJson::new(p, ReportOutput::ToFile, None, JsonStyle::Lines).unwrap();
+        r.insert_int_val("WorkId", 1);
+        r.create_new_row();
+        assert!(r.finish().is_err());
+    }
+
+    fn insert_typed_values(r: &mut dyn Report) {
+        r.insert_int_val("WorkId", 1);
+        r.insert_i64("System_Rating", -1);
+        r.insert_i64("System_Size", 174);
+        r.insert_f64("System_Latitude", 47.5);
+        r.insert_bool("Recovered", true);
+        let t = NaiveDate::from_ymd_opt(2023, 2, 8)
+            .unwrap()
+            .and_hms_nano_opt(22, 13, 58, 617645900)
+            .unwrap()
+            .and_utc();
+        r.insert_datetime("System_DateModified", t);
+        r.insert_bytes("UserData", &[0xDE, 0xAD]);
+        r.insert_list("System_Keywords", vec!["a".into(), "b".into()]);
+    }
+
+    #[test]
+    fn test_report_typed_values() {
+        let dir = tempdir::TempDir::new("sidr_typed").unwrap();
+        let p = dir.path().join("typed.json");
+        {
+            let json = ReportJson::new(&p, ReportOutput::ToFile, None, JsonStyle::Lines).unwrap();
+            let mut r = ReportSource::new(
+                Box::new(json),
+                DbSource::Archive {
+                    archive: "c.zip".into(),
+                    path: "Windows.db".into(),
+                },
+            );
+            insert_typed_values(&mut r);
+        }
+        let expected = concat!(
+            r#"{"Archive":"c.zip","ArchiveFile":"Windows.db","WorkId":1,"System_Rating":-1,"#,
+            r#""System_Size":174,"System_Latitude":47.5,"Recovered":true,"#,
+            r#""System_DateModified":"2023-02-08T22:13:58.6176459Z","UserData":"DEAD","#,
+            r#""System_Keywords":["a","b"]}"#,
+            "\n"
+        );
+        assert_eq!(std::fs::read_to_string(&p).unwrap(), expected);
+
+        // the CSV reports keep the text of the values
+        let p = dir.path().join("typed.csv");
+        {
+            let mut r =
+                ReportCsv::new(&p, ReportOutput::ToFile, None, CsvOptions::default()).unwrap();
+            insert_typed_values(&mut r);
+        }
+        let expected = "WorkId,System_Rating,System_Size,System_Latitude,Recovered,System_DateModified,UserData,System_Keywords
+1,-1,174,47.5,true,2023-02-08T22:13:58.6176459Z,DEAD,a; b
+";
+        assert_eq!(std::fs::read_to_string(&p).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_report_source() {
+        let p = Path::new("test_image.json");
+        let source = DbSource::Image {
+            image: "case/disk.E01".into(),
+            volume_offset: 1048576,
+            path: "\\ProgramData\\Windows.edb".to_string(),
+        };
+        {
+            let json = ReportJson::new(p, ReportOutput::ToFile, None, JsonStyle::Lines).unwrap();
+            let mut r = ReportSource::new(Box::new(json), source);
+            r.insert_int_val("WorkId", 1);
+            r.create_new_row();
+            r.insert_int_val("WorkId", 2);
+            r.create_new_row();
+        }
+        let data = std::fs::read_to_string(p).unwrap();
+        let expected = r#"{"Image":"case/disk.E01","ImageVolumeOffset":1048576,"ImageFile":"\\ProgramData\\Windows.edb","WorkId":1}
+{"Image":"case/disk.E01","ImageVolumeOffset":1048576,"ImageFile":"\\ProgramData\\Windows.edb","WorkId":2}
+"#;
+        assert_eq!(data, expected);
+        std::fs::remove_file(p).unwrap();
+    }
+
+    #[test]
+    fn test_report_timeline() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_timeline").unwrap();
+        let rp = ReportProducer::new(dir.path(), ReportFormat::Csv, ReportOutput::ToFile)?
+            .with_timeline();
+        let db = Path::new("C/Windows.db");
+        {
+            let (_, mut files) = rp.new_report(db, "HOST", "File_Report", None).unwrap();
+            files.insert_int_val("WorkId", 1);
+            files.insert_str_val("System_DateModified", "2023-01-02T00:00:00.0000000Z".into());
+            files.insert_str_val("System_ItemPathDisplay", "C:\\a.txt".into());
+            files.insert_str_val("System_DateCreated", "1601-01-01T00:00:00.0000000Z".into());
+            files.insert_str_val("System_DateAccessed", "2023-01-01T00:00:00.0000000Z".into());
+            files.create_new_row();
+            files.insert_int_val("WorkId", 2);
+            files.insert_str_val("System_ItemPathDisplay", "C:\\b.txt".into());
+            let (_, history) = rp
+                .new_report(db, "HOST", "Internet_History_Report", None)
+                .unwrap();
+            history.insert_int_val("WorkId", 3);
+            history.insert_str_val("System_ItemUrl", "iehistory://{S-1-5-21-1}".into());
+            history.insert_str_val("System_Link_TargetUrl", "https://example.com/".into());
+            history.insert_str_val("System_ItemDate", "2023-01-01T12:00:00.0000000Z".into());
+        }
+        let path = rp.write_timeline().unwrap().unwrap();
+        drop(rp);
+        let data = std::fs::read_to_string(path).unwrap();
+        let expected = r#"timestamp,source_db,host,report,WorkId,timestamp_kind,description
+2023-01-01T00:00:00.0000000Z,C/Windows.db,HOST,File_Report,1,System_DateAccessed,C:\a.txt
+2023-01-01T12:00:00.0000000Z,C/Windows.db,HOST,Internet_History_Report,3,System_ItemDate,https://example.com/
+2023-01-02T00:00:00.0000000Z,C/Windows.db,HOST,File_Report,1,System_DateModified,C:\a.txt
+"#;
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_l2tcsv() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_l2t").unwrap();
+        let rp = ReportProducer::new(dir.path(), ReportFormat::L2tcsv, ReportOutput::ToFile)?;
+        let (path, history) = rp
+            .new_report(
+                Path::new("C/Windows.db"),
+                "HOST",
+                "Internet_History_Report",
+                None,
+            )
+            .unwrap();
+        history.insert_int_val("WorkId", 3);
+        history.insert_str_val("System_Link_TargetUrl", "https://example.com/a,b".into());
+        history.insert_str_val("System_DateCreated", "2023-01-01T12:30:15.1000000Z".into());
+        history.insert_str_val("UserSID", "S-1-5-21-1".into());
+        drop(history);
+        let data = std::fs::read_to_string(path).unwrap();
+        let expected = "date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra
+01/01/2023,12:30:15,UTC,...B,WEBHIST,Windows Search Internet History Report,System_DateCreated,S-1-5-21-1,HOST,https://example.com/a b,System_DateCreated: https://example.com/a b,2,C/Windows.db,3,-,sidr,-
+";
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_bodyfile() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_body").unwrap();
+        let rp = ReportProducer::new(dir.path(), ReportFormat::Bodyfile, ReportOutput::ToFile)?;
+        let (path, mut files) = rp
+            .new_report(Path::new("C/Windows.db"), "HOST", "File_Report", None)
+            .unwrap();
+        files.insert_int_val("WorkId", 1);
+        files.insert_str_val("System_IsFolder", "false".into());
+        files.insert_str_val("System_ItemPathDisplay", "C:\\a.txt".into());
+        files.insert_int_val("System_Size", 174);
+        files.insert_str_val("System_DateModified", "2023-01-02T00:00:00.0000000Z".into());
+        files.insert_str_val("System_DateAccessed", "2023-01-03T00:00:00.5000000Z".into());
+        files.insert_str_val("System_DateCreated", "2023-01-01T00:00:00.0000000Z".into());
+        files.create_new_row();
+        // no date
+        files.insert_int_val("WorkId", 2);
+        files.insert_str_val("System_ItemPathDisplay", "C:\\b.txt".into());
+        files.create_new_row();
+        files.insert_int_val("WorkId", 3);
+        files.insert_str_val("System_IsFolder", "true".into());
+        files.insert_str_val("System_ItemPathDisplay", "C:\\c".into());
+        files.insert_str_val("System_DateCreated", "2023-01-01T00:00:00.0000000Z".into());
+        drop(files);
+        let data = std::fs::read_to_string(path).unwrap();
+        let expected = r#"0|C:\a.txt|1|r/r---------|0|0|174|1672704000|1672617600|0|1672531200
+0|C:\c|3|d/d---------|0|0|0|0|0|0|1672531200
+"#;
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_parquet() -> Result<(), SidrError> {
+        use parquet::basic::Type as PhysicalType;
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::{Row, RowAccessor};
+
+        let dir = tempdir::TempDir::new("sidr_parquet").unwrap();
+        let rp = ReportProducer::new(dir.path(), ReportFormat::Parquet, ReportOutput::ToFile)?;
+        let (path, mut r) = rp
+            .new_report(Path::new("C/Windows.db"), "HOST", "File_Report", None)
+            .unwrap();
+        r.set_field("WorkId");
+        r.set_field("System_Size");
+        r.set_field("Unset");
+        for i in 1..4 {
+            r.insert_int_val("WorkId", i);
+            r.insert_str_val(
+                "System_DateModified",
+                format!("2023-01-0{i}T00:00:00.1234567Z"),
+            );
+            if i % 2 == 1 {
+                r.insert_int_val("System_Size", 100 * i);
+            }
+            r.insert_i64("DeletedCount", if i == 3 { -1 } else { i as i64 });
+            // integers and floats make a double column
+            if i == 1 {
+                r.insert_i64("System_Latitude", 1);
+            } else {
+                r.insert_f64("System_Latitude", i as f64 / 2.0);
+            }
+            r.insert_bool("Recovered", i == 3);
+            // the unset dates, from a zero FILETIME, are nulls
+            let accessed = match i {
+                2 => Utc.with_ymd_and_hms(1601, 1, 1, 0, 0, 0).unwrap(),
+                _ => get_date_time_from_filetime(133170048001234567),
+            };
+            r.insert_datetime("System_DateAccessed", accessed);
+            r.create_new_row();
+        }
+        drop(r);
+        let reader = SerializedFileReader::new(std::fs::File::open(path).unwrap()).unwrap();
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let columns: Vec<(String, PhysicalType, String)> = schema
+            .columns()
+            .iter()
+            .map(|c| {
+                let logical = c.logical_type().map(|t| format!("{t:?}"));
+                (
+                    c.name().to_string(),
+                    c.physical_type(),
+                    logical.unwrap_or_default(),
+                )
+            })
+            .collect();
+        let timestamp = "Timestamp { is_adjusted_to_u_t_c: true, unit: NANOS(NanoSeconds) }";
+        assert_eq!(
+            columns,
+            [
+                ("WorkId".to_string(), PhysicalType::INT64, String::new()),
+                (
+                    "System_Size".to_string(),
+                    PhysicalType::INT64,
+                    String::new()
+                ),
+                (
+                    "Unset".to_string(),
+                    PhysicalType::BYTE_ARRAY,
+                    "String".to_string()
+                ),
+                (
+                    "System_DateModified".to_string(),
+                    PhysicalType::INT64,
+                    timestamp.to_string()
+                ),
+                (
+                    "DeletedCount".to_string(),
+                    PhysicalType::INT64,
+                    String::new()
+                ),
+                (
+                    "System_Latitude".to_string(),
+                    PhysicalType::DOUBLE,
+                    String::new()
+                ),
+                (
+                    "Recovered".to_string(),
+                    PhysicalType::BOOLEAN,
+                    String::new()
+                ),
+                (
+                    "System_DateAccessed".to_string(),
+                    PhysicalType::INT64,
+                    timestamp.to_string()
+                ),
+            ]
+        );
+        let rows: Vec<Row> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(
+            rows.iter().map(|row| row.to_string()).collect::<Vec<_>>(),
+            [
+                "{WorkId: 1, System_Size: 100, Unset: null, System_DateModified: 1672531200123456700, DeletedCount: 1, System_Latitude: 1.0, Recovered: false, System_DateAccessed: 1672531200123456700}",
+                "{WorkId: 2, System_Size: null, Unset: null, System_DateModified: 1672617600123456700, DeletedCount: 2, System_Latitude: 1.0, Recovered: false, System_DateAccessed: null}",
+                "{WorkId: 3, System_Size: 300, Unset: null, System_DateModified: 1672704000123456700, DeletedCount: -1, System_Latitude: 1.5, Recovered: true, System_DateAccessed: 1672531200123456700}",
+            ]
+        );
+        // the row reader has no nanoseconds timestamps, they are read as their INT64
+        assert_eq!(rows[0].get_long(3).unwrap(), 1672531200123456700);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_sqlite() -> Result<(), SidrError> {
+        use sqlite::State;
+
+        let dir = tempdir::TempDir::new("sidr_sqlite").unwrap();
+        let db = dir.path().join("Windows.db");
+        std::fs::write(&db, b"abc").unwrap();
+        let rp = ReportProducer::new(dir.path(), ReportFormat::Sqlite, ReportOutput::ToFile)?;
+        let path = {
+            let (path, mut files) = rp.new_report(&db, "HOST", "File_Report", None).unwrap();
+            files.insert_int_val("WorkId", 1);
+            files.insert_str_val("System_ItemPathDisplay", "C:\\a.txt".into());
+            files.create_new_row();
+            files.insert_int_val("WorkId", 2);
+            files.insert_int_val("System_Size", 174);
+            // same columns, whatever their case is
+            files.create_new_row();
+            files.insert_int_val("WorkId", 3);
+            files.insert_int_val("system_size", 5);
+            files.insert_int_val("SYSTEM_SIZE", 6);
+            files.create_new_row();
+            files.insert_int_val("WorkId", 4);
+            files.insert_int_val("System_Size", 7);
+            files.insert_i64("System_Rating", -1);
+            files.insert_f64("System_Latitude", 47.5);
+            files.insert_bool("System_IsFolder", true);
+            files.insert_datetime(
+                "System_DateModified",
+                Utc.with_ymd_and_hms(2023, 1, 2, 3, 4, 5).unwrap(),
+            );
+            let (_, history) = rp
+                .new_report(&db, "HOST", "Internet_History_Report_without_WAL", None)
+                .unwrap();
+            history.insert_str_val("System_ItemUrl", "https://example.com/".into());
+            path
+        };
+        assert_eq!(path, dir.path().join("sidr_reports.sqlite"));
+
+        let conn = sqlite::Connection::open(&path).unwrap();
+        let mut s = conn
+            .prepare(
+                "select source_id, WorkId, System_ItemPathDisplay, System_Size from file_report",
+            )
+            .unwrap();
+        let mut rows = Vec::new();
+        while let State::Row = s.next().unwrap() {
+            rows.push((
+                s.read::<i64, _>(0).unwrap(),
+                s.read::<i64, _>(1).unwrap(),
+                s.read::<Option<String>, _>(2).unwrap(),
+                s.read::<Option<i64>, _>(3).unwrap(),
+            ));
+        }
+        assert_eq!(
+            rows,
+            [
+                (1, 1, Some("C:\\a.txt".to_string()), None),
+                (1, 2, None, Some(174)),
+                (1, 3, None, Some(6)),
+                (1, 4, None, Some(7))
+            ]
+        );
+        let mut s = conn
+            .prepare("select System_Rating, System_Latitude from file_report where WorkId = 4")
+            .unwrap();
+        assert_eq!(s.next().unwrap(), State::Row);
+        assert_eq!(s.read::<i64, _>(0).unwrap(), -1);
+        assert_eq!(s.read::<f64, _>(1).unwrap(), 47.5);
+        // the booleans are 0 or 1 and the dates ISO-8601 text
+        let mut s = conn
+            .prepare(
+                "select System_IsFolder, typeof(System_IsFolder), System_DateModified
+                 from file_report where WorkId = 4",
+            )
+            .unwrap();
+        assert_eq!(s.next().unwrap(), State::Row);
+        assert_eq!(s.read::<i64, _>(0).unwrap(), 1);
+        assert_eq!(s.read::<String, _>(1).unwrap(), "integer");
+        assert_eq!(
+            s.read::<String, _>(2).unwrap(),
+            "2023-01-02T03:04:05.0000000Z"
+        );
+        let mut s = conn
+            .prepare("select source_id, System_ItemUrl from internet_history_without_wal")
+            .unwrap();
+        assert_eq!(s.next().unwrap(), State::Row);
+        assert_eq!(s.read::<i64, _>(0).unwrap(), 1);
+        let mut s = conn
+            .prepare("select id, path, sha256, hostname, db_state from sources")
+            .unwrap();
+        assert_eq!(s.next().unwrap(), State::Row);
+        assert_eq!(s.read::<i64, _>(0).unwrap(), 1);
+        assert_eq!(s.read::<String, _>(1).unwrap(), db.to_string_lossy());
+        assert_eq!(
+            s.read::<String, _>(2).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(s.read::<String, _>(3).unwrap(), "HOST");
+        assert_eq!(s.read::<Option<String>, _>(4).unwrap(), None);
+        assert_eq!(s.next().unwrap(), State::Done);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sqlite_table_name() {
+        assert_eq!(ReportSqlite::table_name("File_Report"), "file_report");
+        assert_eq!(
+            ReportSqlite::table_name("Activity_History_Report_without_WAL"),
+            "activity_history_without_wal"
+        );
+        assert_eq!(ReportSqlite::table_name("My Report-2"), "my_report_2");
+    }
+
     #[test]
     fn test_report_suffix() {
         let report_suffix = Some(ReportSuffix::FileReport);
         assert_eq!(ReportSuffix::get_match("File_Report"), report_suffix);
         assert_ne!(ReportSuffix::get_match("Activity"), report_suffix);
+        assert_eq!(
+            ReportSuffix::get_match("File_Report_without_WAL"),
+            report_suffix
+        );
 
         assert_eq!(
             ReportSuffix::message(report_suffix.as_ref().unwrap()),
@@ -491,6 +2944,14 @@ mod tests {
             ReportSuffix::message(&ReportSuffix::InternetHistory),
             serde_json::to_string("internet_history").unwrap()
         );
+        assert_eq!(
+            ReportSuffix::message(&ReportSuffix::GatherHistory),
+            serde_json::to_string("gather_history").unwrap()
+        );
+        assert_eq!(
+            ReportSuffix::message(&ReportSuffix::WalFrames),
+            serde_json::to_string("wal_frames").unwrap()
+        );
         assert_eq!(
             ReportSuffix::message(&ReportSuffix::Unknown),
             serde_json::to_string("").unwrap()
@@ -498,14 +2959,14 @@ mod tests {
     }
 
     #[test]
-    fn test_get_path_db_status() {
+    fn test_get_path_db_status() -> Result<(), SidrError> {
         let path = Path::new("./tests");
-        let rp = ReportProducer::new(path, ReportFormat::Json, ReportOutput::ToStdout);
+        let rp = ReportProducer::new(path, ReportFormat::Json, ReportOutput::ToStdout)?;
         let naivedatetime_utc = NaiveDate::from_ymd_opt(2000, 1, 12)
             .unwrap()
             .and_hms_opt(2, 0, 0)
             .unwrap();
-        let dt = DateTime::<Utc>::from_utc(naivedatetime_utc, Utc);
+        let dt = Utc.from_utc_datetime(&naivedatetime_utc);
         assert_eq!(
             rp.get_path_db_status(
                 "test_hostname",
@@ -532,14 +2993,83 @@ mod tests {
                 .join("test_hostname_activity_20000112_020000_dirty.edb.test")
                 .to_string_lossy()
         );
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_inputs() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_inputs").unwrap();
+        let db = dir.path().join("Windows.edb");
+        let rp = ReportProducer::new(dir.path(), ReportFormat::Json, ReportOutput::ToFile)?;
+        rp.add_input(&db, "ESE", None, Vec::new());
+        let (path, mut r) = rp
+            .new_report(&db, "host", "File_Report", Some(DbState::DirtyShutdown))
+            .unwrap();
+        r.insert_int_val("WorkId", 1);
+        r.create_new_row();
+        // empty records are not written
+        r.create_new_row();
+        r.insert_int_val("WorkId", 2);
+        drop(r);
+        // not an input of the run
+        rp.new_report(Path::new("other.edb"), "host", "File_Report", None)
+            .unwrap();
+
+        let inputs = rp.inputs();
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].source_db, db.to_string_lossy());
+        assert_eq!(inputs[0].hostname.as_deref(), Some("host"));
+        assert_eq!(inputs[0].db_state, Some(DbState::DirtyShutdown));
+        assert_eq!(inputs[0].reports.len(), 1);
+        assert_eq!(inputs[0].reports[0].report, "File_Report");
+        assert_eq!(inputs[0].reports[0].path, path);
+        assert_eq!(inputs[0].reports[0].rows.get(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_path_db_hash() -> Result<(), SidrError> {
+        let dir = tempdir::TempDir::new("sidr_deterministic").unwrap();
+        let db = dir.path().join("Windows.db");
+        std::fs::write(&db, b"abc").unwrap();
+        let rp = ReportProducer::new(dir.path(), ReportFormat::Csv, ReportOutput::ToFile)?
+            .with_deterministic()
+            .with_timeline();
+        let path = |state| {
+            rp.get_path_db_hash(&db, "test_hostname", "File_Report", Some(state))
+                .unwrap()
+        };
+        assert_eq!(
+            path(DbState::CleanShutdown),
+            dir.path()
+                .join("test_hostname_File_Report_ba7816bf8f01cfea.csv")
+        );
+        assert_eq!(
+            path(DbState::DirtyShutdown),
+            dir.path()
+                .join("test_hostname_File_Report_ba7816bf8f01cfea_dirty.csv")
+        );
+        // the same database given twice
+        assert_eq!(
+            path(DbState::CleanShutdown),
+            dir.path()
+                .join("test_hostname_File_Report_ba7816bf8f01cfea_2.csv")
+        );
+        assert_eq!(
+            rp.timeline.as_ref().map(|(path, _)| path.clone()),
+            Some(dir.path().join("Timeline.csv"))
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_is_db_dirty() {
+    fn test_is_db_dirty() -> Result<(), SidrError> {
         let path = Path::new("./tests");
-        let rp = ReportProducer::new(path, ReportFormat::Json, ReportOutput::ToStdout);
-        assert_eq!(rp.is_db_dirty(Some(DbState::CleanShutdown)), false);
-        assert_eq!(rp.is_db_dirty(Some(DbState::DirtyShutdown)), true);
-        assert_eq!(rp.is_db_dirty(Some(DbState::BeingConverted)), true);
+        let rp = ReportProducer::new(path, ReportFormat::Json, ReportOutput::ToStdout)?;
+        assert!(!rp.is_db_dirty(Some(DbState::CleanShutdown)));
+        assert!(rp.is_db_dirty(Some(DbState::DirtyShutdown)));
+        assert!(rp.is_db_dirty(Some(DbState::BeingConverted)));
+        Ok(())
     }
 }
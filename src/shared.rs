@@ -1,11 +1,54 @@
-use simple_error::SimpleError;
+use crate::error::SidrError;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 use crate::report::*;
+use crate::utils::sid_from_url;
 use ese_parser_lib::parser::jet::DbState;
 use std::io::Write;
+use wsa_lib::FieldReader;
 
-type Reports = (
+// Options controlling how the databases are parsed.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    // report every column of the ESE property store instead of the built-in selection
+    pub all_properties: bool,
+    // also report the deleted/orphaned records carved from the database pages
+    pub recover_deleted: bool,
+    // how the write-ahead log next to Windows.db is handled
+    pub wal: WalMode,
+    // look for databases with any name in the input directories
+    pub any_name: bool,
+    // the inputs are disk images to extract the databases from
+    pub images: bool,
+    // user SID -> account name, from the registry hives given with --registry
+    pub user_names: HashMap<String, String>,
+    // merge the records of all the reports into a single timeline
+    pub timeline: bool,
+    // delimiter, quoting, ... of the CSV reports
+    pub csv: CsvOptions,
+    // JSON lines or a JSON array per report
+    pub json_style: JsonStyle,
+    // the same evidence gives byte-identical reports, named after the database hashes
+    pub deterministic: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum WalMode {
+    // report the database as SQLite reads it, with the committed WAL frames
+    #[default]
+    Auto,
+    // report the database with the committed WAL frames applied
+    Apply,
+    // report the database as it is on disk, without the WAL
+    Ignore,
+    // report the database both with and without the WAL
+    Both,
+}
+
+pub type Reports = (
     Box<dyn Report>, /* file report */
     Box<dyn Report>, /* ie report */
     Box<dyn Report>, /* act report */
@@ -17,21 +60,26 @@ pub fn init_reports(
     recovered_hostname: &str,
     status_logger: &mut Box<dyn Write>,
     edb_database_state: Option<DbState>,
-) -> Result<Reports, SimpleError> {
-    let (file_rep_path, file_rep) =
-        report_prod.new_report(f, recovered_hostname, "File_Report", edb_database_state)?;
+    name_suffix: &str,
+) -> Result<Reports, SidrError> {
+    let (file_rep_path, file_rep) = report_prod.new_report(
+        f,
+        recovered_hostname,
+        &format!("File_Report{name_suffix}"),
+        edb_database_state,
+    )?;
 
     let (ie_rep_path, ie_rep) = report_prod.new_report(
         f,
         recovered_hostname,
-        "Internet_History_Report",
+        &format!("Internet_History_Report{name_suffix}"),
         edb_database_state,
     )?;
 
     let (act_rep_path, act_rep) = report_prod.new_report(
         f,
         recovered_hostname,
-        "Activity_History_Report",
+        &format!("Activity_History_Report{name_suffix}"),
         edb_database_state,
     )?;
 
@@ -41,7 +89,336 @@ pub fn init_reports(
         file_rep_path.to_string_lossy(),
         ie_rep_path.to_string_lossy(),
         act_rep_path.to_string_lossy()
-    )
-    .map_err(|e| SimpleError::new(format!("{e}")))?;
+    )?;
     Ok((file_rep, ie_rep, act_rep))
 }
+
+// Closes the reports, all of them even if one fails. UserSID (and UserName with --registry)
+// is declared in every report, even when no record of the database has a known user.
+pub fn finish_reports(reports: &mut Reports, opts: &ParseOptions) -> Result<(), SidrError> {
+    for r in [&reports.0, &reports.1, &reports.2] {
+        r.set_field("UserSID");
+        if !opts.user_names.is_empty() {
+            r.set_field("UserName");
+        }
+    }
+    let results = [reports.0.finish(), reports.1.finish(), reports.2.finish()];
+    results.into_iter().collect()
+}
+
+pub fn init_gather_report(
+    f: &Path,
+    report_prod: &ReportProducer,
+    recovered_hostname: &str,
+    status_logger: &mut Box<dyn Write>,
+    edb_database_state: Option<DbState>,
+) -> Result<Box<dyn Report>, SidrError> {
+    let (gather_rep_path, gather_rep) = report_prod.new_report(
+        f,
+        recovered_hostname,
+        "Gather_History_Report",
+        edb_database_state,
+    )?;
+    writeln!(status_logger, "{}\n", gather_rep_path.to_string_lossy())?;
+    Ok(gather_rep)
+}
+
+// SystemIndex_GthrPth rows: Scope -> (Parent, Name)
+pub type GatherScopes = HashMap<i64, (i64, String)>;
+
+// One row of SystemIndex_Gthr, the crawl state of a gathered item.
+#[derive(Debug, Default)]
+pub struct GatherRecord {
+    pub scope_id: i64,
+    pub document_id: i64,
+    pub file_name: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub transaction_flags: Option<i64>,
+    pub crawl_number_crawled: Option<i64>,
+    pub deleted_count: Option<i64>,
+    pub run_time: Option<i64>,
+    pub last_requested_run_time: Option<i64>,
+    pub failure_update_attempts: Option<i64>,
+}
+
+// Full path of a scope, built by following the GthrPth parent links up to the root.
+pub fn gather_scope_path(scopes: &GatherScopes, scope_id: i64) -> String {
+    let mut names = Vec::new();
+    let mut visited = HashSet::new();
+    let mut scope = scope_id;
+    while let Some((parent, name)) = scopes.get(&scope) {
+        // guard against corrupted parent links
+        if !visited.insert(scope) {
+            break;
+        }
+        names.push(name.as_str());
+        scope = *parent;
+    }
+    names
+        .iter()
+        .rev()
+        .fold(String::new(), |path, name| join_path(path, name))
+}
+
+fn join_path(mut path: String, name: &str) -> String {
+    if !path.is_empty() && !path.ends_with(['\\', '/']) {
+        path.push('\\');
+    }
+    path.push_str(name);
+    path
+}
+
+// Gather History Report
+pub fn write_gather_record(
+    r: &mut dyn Report,
+    g: &GatherRecord,
+    scopes: &GatherScopes,
+    in_property_store: bool,
+    db_sid: Option<&str>,
+) {
+    r.create_new_row();
+    r.insert_i64("DocumentID", g.document_id);
+    r.insert_i64("ScopeID", g.scope_id);
+    let scope_path = gather_scope_path(scopes, g.scope_id);
+    let full_path = match &g.file_name {
+        Some(file_name) => join_path(scope_path, file_name),
+        None => scope_path,
+    };
+    let user_sid = sid_from_url(&full_path).or_else(|| db_sid.map(str::to_string));
+    r.insert_str_val("FullPath", full_path);
+    if let Some(file_name) = &g.file_name {
+        r.insert_str_val("FileName", file_name.clone());
+    }
+    if let Some(last_modified) = g.last_modified {
+        r.insert_datetime("LastModified", last_modified);
+    }
+    let ints = [
+        ("TransactionFlags", g.transaction_flags),
+        ("CrawlNumberCrawled", g.crawl_number_crawled),
+        ("DeletedCount", g.deleted_count),
+        ("RunTime", g.run_time),
+        ("LastRequestedRunTime", g.last_requested_run_time),
+        ("FailureUpdateAttempts", g.failure_update_attempts),
+    ];
+    for (f, n) in ints {
+        if let Some(n) = n {
+            r.insert_i64(f, n);
+        }
+    }
+    // deleted files are often only left in the gather tables
+    r.insert_bool("InPropertyStore", in_property_store);
+    if let Some(sid) = user_sid {
+        r.insert_str_val("UserSID", sid);
+    }
+}
+
+// Through its shortest text, 0.1f32 is not reported as 0.10000000149011612.
+pub fn insert_f32(r: &mut dyn Report, f: &str, x: f32) {
+    match x.to_string().parse() {
+        Ok(x) => r.insert_f64(f, x),
+        Err(_) => r.insert_f64(f, x.into()),
+    }
+}
+
+// Integer columns of the gather tables have different widths, other sizes are not integers.
+pub fn int_from_bytes(v: &[u8]) -> Option<i64> {
+    match v.len() {
+        1 => Some(v[0] as i64),
+        2 => Some(u16::from_le_bytes(v.try_into().ok()?) as i64),
+        4 => Some(i32::from_le_bytes(v.try_into().ok()?) as i64),
+        8 => Some(i64::from_le_bytes(v.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+// Multi-valued strings are separated by NUL, a single value is reported as a string.
+pub fn insert_strings(r: &mut dyn Report, f: &str, s: &str) {
+    let mut values: Vec<String> = s
+        .trim_end_matches('\0')
+        .split('\0')
+        .map(String::from)
+        .collect();
+    if values.len() > 1 {
+        r.insert_list(f, values);
+    } else {
+        r.insert_str_val(f, values.remove(0));
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecordKind {
+    File,
+    InternetHistory,
+    ActivityHistory,
+}
+
+// Decides which report a record goes to. The same rules are used for Windows.edb and Windows.db,
+// so the fields are addressed by ESE-style names (e.g. "System_ItemUrl").
+pub fn classify_record(record: &mut dyn FieldReader) -> RecordKind {
+    // IE history: "iehistory://{SID}/..."
+    // Edge history: "winrt://{SID}/LS/Desktop/Microsoft Edge/stable/Default/..."
+    if let Some(url) = record.get_str(&"System_ItemUrl".to_string()) {
+        if url.starts_with("iehistory://")
+            || url.starts_with("winrt://")
+                && url.contains("/LS/Desktop/Microsoft Edge/stable/Default/")
+        {
+            return RecordKind::InternetHistory;
+        }
+    }
+    if let Some(item_type) = record.get_str(&"System_ItemType".to_string()) {
+        if item_type == "ActivityHistoryItem" {
+            return RecordKind::ActivityHistory;
+        }
+    }
+    RecordKind::File
+}
+
+#[test]
+fn test_gather_scope_path() {
+    let scopes: GatherScopes = [
+        (1, (0, "file:///C:\\".to_string())),
+        (2, (1, "Users".to_string())),
+        (3, (2, "user".to_string())),
+        // corrupted loop
+        (5, (6, "a".to_string())),
+        (6, (5, "b".to_string())),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(gather_scope_path(&scopes, 3), "file:///C:\\Users\\user");
+    assert_eq!(
+        join_path(gather_scope_path(&scopes, 3), "notes.txt"),
+        "file:///C:\\Users\\user\\notes.txt"
+    );
+    assert_eq!(gather_scope_path(&scopes, 5), "b\\a");
+    assert_eq!(gather_scope_path(&scopes, 42), "");
+}
+
+#[test]
+fn test_classify_ese_and_sqlite_records() {
+    use crate::ese::EseRecord;
+    use crate::sqlite::{PropertyInfo, SqliteRecord};
+
+    let sid = "{S-1-5-21-29705265-400737687-482427116-1001}";
+    let ie_url = format!("iehistory://{sid}/https://www.msn.com/?ocid=iehp");
+    let edge_url = format!("winrt://{sid}/LS/Desktop/Microsoft Edge/stable/Default/History/9");
+    let act_url = format!("winrt://{sid}/LS/Desktop/ActivityData/ActivityHistory/{{19FD5D40-4527-47F4-751B-2CFEACEB0B56}}");
+    let cases = [
+        (
+            vec![
+                ("System_ItemUrl", ie_url.as_str()),
+                ("System_Link_TargetUrl", "https://www.msn.com/?ocid=iehp"),
+            ],
+            RecordKind::InternetHistory,
+        ),
+        (
+            vec![
+                ("System_ItemUrl", edge_url.as_str()),
+                ("System_Link_TargetUrl", "https://ipchicken.com/"),
+            ],
+            RecordKind::InternetHistory,
+        ),
+        (
+            vec![
+                ("System_ItemUrl", act_url.as_str()),
+                ("System_ItemType", "ActivityHistoryItem"),
+            ],
+            RecordKind::ActivityHistory,
+        ),
+        (
+            vec![
+                ("System_ItemUrl", "file:C:/Users/fisft/Favorites/Bing.url"),
+                ("System_ItemType", ".url"),
+                (
+                    "System_Link_TargetUrl",
+                    "http://go.microsoft.com/fwlink/p/?LinkId=255142",
+                ),
+            ],
+            RecordKind::File,
+        ),
+        (
+            vec![("System_ItemPathDisplay", "C:\\Users")],
+            RecordKind::File,
+        ),
+    ];
+
+    for (fields, kind) in cases {
+        // Windows.edb: UTF-16 values in "<id>-<name>" columns
+        let ese_record: HashMap<String, Vec<u8>> = fields
+            .iter()
+            .enumerate()
+            .map(|(i, (name, val))| {
+                let val = val.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+                (format!("{:X}-{name}", i + 1), val)
+            })
+            .collect();
+        // Windows.db: UTF-8 values keyed by ColumnId, names come from the metadata table
+        let idToProp: HashMap<i64, PropertyInfo> = fields
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                let prop = PropertyInfo {
+                    name: name.replace('_', "."),
+                    field: name.to_string(),
+                    storage_type: 11,
+                    variant_type: 31,
+                };
+                (i as i64 + 1, prop)
+            })
+            .collect();
+        let sqlite_record: HashMap<i64, sqlite::Value> = fields
+            .iter()
+            .enumerate()
+            .map(|(i, (_, val))| (i as i64 + 1, sqlite::Value::String(val.to_string())))
+            .collect();
+
+        assert_eq!(classify_record(&mut EseRecord::new(&ese_record)), kind);
+        assert_eq!(
+            classify_record(&mut SqliteRecord::new(&sqlite_record, &idToProp)),
+            kind
+        );
+    }
+}
+
+#[test]
+fn test_int_from_bytes() {
+    assert_eq!(int_from_bytes(&[0xFF]), Some(255));
+    assert_eq!(int_from_bytes(&[0xFF, 0xFF]), Some(65535));
+    assert_eq!(int_from_bytes(&(-2_i32).to_le_bytes()), Some(-2));
+    assert_eq!(int_from_bytes(&(1_i64 << 40).to_le_bytes()), Some(1 << 40));
+    assert_eq!(int_from_bytes(&[]), None);
+    assert_eq!(int_from_bytes(&[1, 2, 3]), None);
+    assert_eq!(int_from_bytes(&[0; 16]), None);
+}
+
+#[test]
+fn test_user_name() {
+    use crate::ese::EseRecord;
+    use crate::utils::record_user_name;
+
+    let sid = "S-1-5-21-4268361623-692440835-3372367631-1001";
+    let user_names: HashMap<String, String> = [(sid.to_string(), "fisft".to_string())]
+        .into_iter()
+        .collect();
+    let owner: Vec<u8> = sid
+        .to_ascii_lowercase()
+        .encode_utf16()
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+    let h: HashMap<String, Vec<u8>> = [("4-System_FileOwner".to_string(), owner)]
+        .into_iter()
+        .collect();
+    let mut record = EseRecord::new(&h);
+    assert_eq!(
+        record_user_name(&mut record, None, &user_names),
+        Some("fisft".to_string())
+    );
+    assert_eq!(
+        record_user_name(&mut record, Some("S-1-5-18"), &user_names),
+        None
+    );
+    assert_eq!(
+        record_user_name(&mut EseRecord::new(&HashMap::new()), Some(sid), &user_names),
+        Some("fisft".to_string())
+    );
+}
@@ -1,19 +1,24 @@
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
-use simple_error::SimpleError;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
 
+use crate::error::SidrError;
 use crate::report::*;
 use crate::shared::*;
+use crate::sqlite_recover::*;
 use crate::utils::*;
 
 use ese_parser_lib::ese_parser::FromBytes;
-use sqlite::State;
+use sqlite::{State, Value};
 use std::io::Write;
+use wsa_lib::{do_reports, ColumnPair, ConstrainedField, FieldReader, ReportsCfg, SqlReader};
 
-macro_rules! map_err(($result:expr) => ($result.map_err(|e| SimpleError::new(format!("{}", e)))));
-
-fn sqlite_get_hostname(c: &sqlite::Connection) -> Result<String, SimpleError> {
+fn sqlite_get_hostname(c: &sqlite::Connection) -> Result<String, SidrError> {
     // We take the System.ComputerName field from each record, filter out any records
     // where the System.ItemType field is equal to ".url", and save the first one as the computer
     // name for the entire report.
@@ -31,30 +36,82 @@ fn sqlite_get_hostname(c: &sqlite::Connection) -> Result<String, SimpleError> {
                  and Name == 'System.ItemType'
                  ) <> '.url' limit 1;"
         .to_string();
-    let mut s = map_err!(c.prepare(q))?;
-    if let Ok(State::Row) = s.next() {
-        let val = map_err!(s.read::<Vec<u8>, _>("Value"))?;
+    let mut s = c.prepare(q)?;
+    if s.next()? == State::Row {
+        let val = s.read::<Vec<u8>, _>("Value")?;
         return Ok(String::from_utf8_lossy(&val).into_owned());
     }
-    Err(SimpleError::new(
+    Err(SidrError::Corrupt(
         "Empty field System.ComputerName".to_string(),
     ))
 }
 
+// VARTYPEs found in SystemIndex_1_PropertyStore_Metadata.VariantType
+const VT_I2: i64 = 2;
+const VT_I4: i64 = 3;
+const VT_R4: i64 = 4;
+const VT_R8: i64 = 5;
+const VT_DATE: i64 = 7;
+const VT_BOOL: i64 = 11;
+const VT_I1: i64 = 16;
+const VT_UI1: i64 = 17;
+const VT_UI2: i64 = 18;
+const VT_UI4: i64 = 19;
+const VT_I8: i64 = 20;
+const VT_UI8: i64 = 21;
+const VT_LPWSTR: i64 = 31;
+const VT_FILETIME: i64 = 64;
+const VT_CLSID: i64 = 72;
+
+// StorageTypes found in SystemIndex_1_PropertyStore_Metadata.StorageType
+const STORAGE_TYPE_UI1: i64 = 0;
+const STORAGE_TYPE_UI2: i64 = 2;
+const STORAGE_TYPE_I4: i64 = 3;
+const STORAGE_TYPE_UI4: i64 = 4;
+const STORAGE_TYPE_R8: i64 = 7;
+const STORAGE_TYPE_BOOL: i64 = 8;
+const STORAGE_TYPE_GUID: i64 = 9;
+const STORAGE_TYPE_STRING: i64 = 11;
+// 8 byte values, FILETIME or UI8
+const STORAGE_TYPE_QWORD: i64 = 12;
+const STORAGE_TYPE_BLOB: i64 = 13;
+
+// Row of SystemIndex_1_PropertyStore_Metadata describing a property (ColumnId).
+#[derive(Clone, Debug)]
+pub struct PropertyInfo {
+    pub name: String,
+    // name with '.' replaced by '_' (e.g. "System_ItemUrl"), the report field
+    pub field: String,
+    pub storage_type: i64,
+    pub variant_type: i64,
+}
+
 fn populate_property_id_maps<'a>(
     c: &sqlite::Connection,
-    idToProp: &'a mut HashMap<i64, (String, i64)>,
+    idToProp: &'a mut HashMap<i64, PropertyInfo>,
     NameToId: &'a mut HashMap<String, i64>,
-) -> Result<(), SimpleError> {
-    let q = "select Id, Name, StorageType from SystemIndex_1_PropertyStore_Metadata";
-    let s = map_err!(c.prepare(q))?;
+) -> Result<(), SidrError> {
+    let q = "select Id, Name, StorageType, VariantType from SystemIndex_1_PropertyStore_Metadata";
+    let s = c.prepare(q)?;
 
-    for row in s.into_iter().map(|row| row.unwrap()) {
-        let id = row.read::<i64, _>("Id");
-        let name = row.read::<&str, _>("Name").to_string();
-        let storageType = row.read::<i64, _>("StorageType");
+    for row in s.into_iter() {
+        let row = row?;
+        let id = row.try_read::<i64, _>("Id")?;
+        let name = row.try_read::<&str, _>("Name")?.to_string();
+        let storage_type = row.try_read::<i64, _>("StorageType")?;
+        let variant_type = row
+            .try_read::<Option<i64>, _>("VariantType")?
+            .unwrap_or_default();
 
-        idToProp.insert(id, (name.clone(), storageType));
+        idToProp.insert(
+            id,
+            PropertyInfo {
+                field: name.replace('.', "_"),
+                name: name.clone(),
+                storage_type,
+                variant_type,
+            },
+        );
         NameToId.insert(name, id);
     }
     Ok(())
@@ -65,21 +122,66 @@ fn populate_property_id_maps<'a>(
 pub fn sqlite_generate_report(
     f: &Path,
     report_prod: &ReportProducer,
+    opts: &ParseOptions,
     status_logger: &mut Box<dyn Write>,
-) -> Result<(), SimpleError> {
+) -> Result<(), SidrError> {
     writeln!(
         status_logger,
         "Processing SQLite db: {}",
         &f.to_string_lossy()
-    )
-    .map_err(|e| SimpleError::new(format!("{e}")))?;
+    )?;
 
-    let c = map_err!(sqlite::Connection::open_with_flags(
-        f,
-        sqlite::OpenFlags::new().with_read_only()
-    ))?;
+    // The database is always reported from a copy, with or without the WAL, so SQLite
+    // never writes the database or its -wal/-shm files next to the evidence.
+    let tmp = TempDir::new("sidr")?;
+    let db = sqlite_copy_db(f, tmp.path(), opts.wal != WalMode::Ignore)?;
+    sqlite_generate_db_report(f, &db, report_prod, opts, "", true, status_logger)?;
+    if opts.wal == WalMode::Both {
+        let tmp = TempDir::new("sidr")?;
+        let db = sqlite_copy_db(f, tmp.path(), false)?;
+        sqlite_generate_db_report(
+            f,
+            &db,
+            report_prod,
+            opts,
+            "_without_WAL",
+            false,
+            status_logger,
+        )?
+    }
+    Ok(())
+}
+
+// Copies a database (and its -wal when with_wal is set) into dir and returns the path of the copy.
+fn sqlite_copy_db(f: &Path, dir: &Path, with_wal: bool) -> Result<PathBuf, SidrError> {
+    let name = f
+        .file_name()
+        .ok_or_else(|| SidrError::Cfg(format!("Invalid path {}", f.to_string_lossy())))?;
+    let db = dir.join(name);
+    std::fs::copy(f, &db)?;
+    let wal = wal_path(f);
+    if with_wal && wal.exists() {
+        std::fs::copy(&wal, wal_path(&db))?;
+    }
+    Ok(db)
+}
+
+// Generates the reports of the database opened from db, f being the original Windows.db
+// used for the report names, the carving of deleted rows and the gather/WAL reports
+// (which are only generated when main_pass is set).
+fn sqlite_generate_db_report(
+    f: &Path,
+    db: &Path,
+    report_prod: &ReportProducer,
+    opts: &ParseOptions,
+    name_suffix: &str,
+    main_pass: bool,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    // the copy is opened read-write, so SQLite replays (or discards) its WAL
+    let c = sqlite::Connection::open_with_flags(db, sqlite::OpenFlags::new().with_read_write())?;
     let query = "select * from SystemIndex_1_PropertyStore";
-    let mut s = map_err!(c.prepare(query))?;
+    let mut s = c.prepare(query)?;
 
     let recovered_hostname = match sqlite_get_hostname(&c) {
         Ok(h) => h,
@@ -88,25 +190,52 @@ pub fn sqlite_generate_report(
             "Unknown".to_string()
         }
     };
+    let db_sid = sid_from_db_path(f);
+    let recovered_hostname = report_host_name(recovered_hostname, db_sid.as_deref());
 
-    let (mut file_rep, mut ie_rep, mut act_rep) =
-        init_reports(f, report_prod, &recovered_hostname, status_logger, None)?;
+    let (mut file_rep, mut ie_rep, mut act_rep) = init_reports(
+        f,
+        report_prod,
+        &recovered_hostname,
+        status_logger,
+        None,
+        name_suffix,
+    )?;
 
-    let mut idToProp = HashMap::<i64, (String, i64)>::new();
+    let mut idToProp = HashMap::<i64, PropertyInfo>::new();
     let mut propNameToId = HashMap::<String, i64>::new();
-    if populate_property_id_maps(&c, &mut idToProp, &mut propNameToId).is_err() {
-        panic!("Unable to read property IDs.")
-    };
+    populate_property_id_maps(&c, &mut idToProp, &mut propNameToId)
+        .map_err(|e| SidrError::Corrupt(format!("Unable to read property IDs: {e}")))?;
 
-    let mut handler = |workId: u32, record: &mut HashMap<i64, Vec<u8>>| {
+    let mut work_ids = HashSet::new();
+    let mut handler = |workId: u32, record: &mut HashMap<i64, Value>, recovered: bool| {
         // new WorkId, handle all collected fields
         if !record.is_empty() {
-            if is_internet_record(&record, &propNameToId).is_ok() {
-                write_record_to_report(record, workId, &idToProp, &mut *ie_rep);
-            } else if is_activity_history_record(record, &propNameToId).is_ok() {
-                write_record_to_report(record, workId, &idToProp, &mut *act_rep);
-            } else {
-                write_record_to_report(record, workId, &idToProp, &mut *file_rep);
+            if !recovered {
+                work_ids.insert(workId);
+            }
+            let user_sid =
+                record_user_sid(&mut SqliteRecord::new(record, &idToProp), db_sid.as_deref());
+            let user_name = record_user_name(
+                &mut SqliteRecord::new(record, &idToProp),
+                user_sid.as_deref(),
+                &opts.user_names,
+            );
+            let report: &mut dyn Report =
+                match classify_record(&mut SqliteRecord::new(record, &idToProp)) {
+                    RecordKind::InternetHistory => &mut *ie_rep,
+                    RecordKind::ActivityHistory => &mut *act_rep,
+                    RecordKind::File => &mut *file_rep,
+                };
+            write_record_to_report(record, workId, &idToProp, report);
+            if opts.recover_deleted {
+                report.insert_bool("Recovered", recovered);
+            }
+            if let Some(sid) = user_sid {
+                report.insert_str_val("UserSID", sid);
+            }
+            if let Some(name) = user_name {
+                report.insert_str_val("UserName", name);
             }
             record.clear();
         }
@@ -114,108 +243,685 @@ pub fn sqlite_generate_report(
 
     let mut record = HashMap::new();
     let mut workId_current = 0;
-    while let Ok(State::Row) = s.next() {
-        let workId = map_err!(s.read::<i64, _>("WorkId"))? as u32;
+    let mut live_tuples = HashSet::new();
+    while s.next()? == State::Row {
+        let workId = s.read::<i64, _>("WorkId")? as u32;
         if workId_current != workId {
-            handler(workId_current, &mut record);
+            handler(workId_current, &mut record, false);
             workId_current = workId;
         }
-        let columnId = map_err!(s.read::<i64, _>("ColumnId"))?;
-        let value = map_err!(s.read::<Vec<u8>, _>("Value"))?;
+        let columnId = s.read::<i64, _>("ColumnId")?;
+        let value = s.read::<Value, _>("Value")?;
+        if opts.recover_deleted {
+            live_tuples.insert((workId as i64, columnId, value_digest(&value)));
+        }
         record.insert(columnId, value);
     }
     // handle last element
     if !record.is_empty() {
-        handler(workId_current, &mut record);
+        handler(workId_current, &mut record, false);
+    }
+
+    if opts.recover_deleted {
+        // carved tuples which are not in the table anymore, grouped by WorkId
+        let root = sqlite_get_root_page(&c, "SystemIndex_1_PropertyStore")?;
+        let is_plausible = |(_, col, val): &PropertyTuple| {
+            idToProp
+                .get(col)
+                .is_some_and(|prop| is_plausible_value(prop, val))
+        };
+        let mut recovered = BTreeMap::<i64, HashMap<i64, Value>>::new();
+        for (workId, columnId, value) in sqlite_recover_tuples(f, root, &is_plausible)? {
+            if !live_tuples.contains(&(workId, columnId, value_digest(&value))) {
+                recovered
+                    .entry(workId)
+                    .or_default()
+                    .entry(columnId)
+                    .or_insert(value);
+            }
+        }
+        for (workId, mut record) in recovered {
+            handler(workId as u32, &mut record, true);
+        }
+    }
+    finish_reports(&mut (file_rep, ie_rep, act_rep), opts)?;
+
+    if !main_pass {
+        return Ok(());
+    }
+
+    if opts.wal != WalMode::Auto {
+        if let Err(e) = sqlite_generate_wal_report(
+            f,
+            &c,
+            report_prod,
+            &recovered_hostname,
+            &idToProp,
+            status_logger,
+        ) {
+            eprintln!("WAL Frames Report skipped: {e}");
+        }
+    }
+
+    match sqlite_open_gather_db(f, db, &c, opts.wal != WalMode::Ignore) {
+        Some(gather_db) => {
+            if let Err(e) = sqlite_generate_gather_report(
+                f,
+                &gather_db,
+                report_prod,
+                &recovered_hostname,
+                &work_ids,
+                status_logger,
+            ) {
+                eprintln!("Gather History Report skipped: {e}");
+            }
+        }
+        None => {
+            writeln!(
+                status_logger,
+                "No gather tables found for {}, Gather History Report skipped.",
+                &f.to_string_lossy()
+            )?;
+        }
     }
     Ok(())
 }
 
+// One version of a PropertyStore row, either from the database file or from a WAL frame.
+struct RowVersion {
+    value: Value,
+    // 1-based index of the WAL frame, None for the database file
+    frame: Option<usize>,
+    page: u32,
+    committed: bool,
+}
+
+// Lists every version of the property rows changed by the frames of Windows.db-wal:
+// rows which only exist in the WAL, and the older values superseded by later frames.
+fn sqlite_generate_wal_report(
+    f: &Path,
+    c: &sqlite::Connection,
+    report_prod: &ReportProducer,
+    recovered_hostname: &str,
+    idToProp: &HashMap<i64, PropertyInfo>,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    let wal = wal_path(f);
+    if !wal.exists() {
+        return Ok(());
+    }
+    let frames = read_wal(&std::fs::read(&wal)?)?;
+    if frames.is_empty() {
+        writeln!(
+            status_logger,
+            "No frames in {}, WAL Frames Report skipped.",
+            &wal.to_string_lossy()
+        )?;
+        return Ok(());
+    }
+
+    let root = sqlite_get_root_page(c, "SystemIndex_1_PropertyStore")?;
+    let mut pages = SqlitePages::open(f)?;
+    let (usable_size, text_encoding) = (pages.usable_size, pages.text_encoding);
+    // frames of pages which belong to the table, before or after the WAL is applied
+    let db_leaves = pages.leaf_pages(root, &HashMap::new())?;
+    let latest: HashMap<u32, Vec<u8>> = frames
+        .iter()
+        .map(|frame| (frame.pgno, frame.page.clone()))
+        .collect();
+    let mut table_pages: HashSet<u32> = db_leaves.iter().copied().collect();
+    table_pages.extend(pages.leaf_pages(root, &latest)?);
+
+    let mut versions = BTreeMap::<(i64, i64), Vec<RowVersion>>::new();
+    for pgno in db_leaves {
+        let page = pages.read_page(pgno)?;
+        for (workId, columnId, value) in leaf_cells(&page, pgno, usable_size, text_encoding) {
+            versions
+                .entry((workId, columnId))
+                .or_default()
+                .push(RowVersion {
+                    value,
+                    frame: None,
+                    page: pgno,
+                    committed: true,
+                });
+        }
+    }
+    for (i, frame) in frames.iter().enumerate() {
+        if !table_pages.contains(&frame.pgno) {
+            continue;
+        }
+        for (workId, columnId, value) in
+            leaf_cells(&frame.page, frame.pgno, usable_size, text_encoding)
+        {
+            let is_plausible = idToProp
+                .get(&columnId)
+                .is_some_and(|prop| is_plausible_value(prop, &value));
+            if !is_plausible {
+                continue;
+            }
+            // the same row is in every frame of its page, keep the changes only
+            let row = versions.entry((workId, columnId)).or_default();
+            if row.last().is_none_or(|last| last.value != value) {
+                row.push(RowVersion {
+                    value,
+                    frame: Some(i + 1),
+                    page: frame.pgno,
+                    committed: frame.committed,
+                });
+            }
+        }
+    }
+
+    let (wal_rep_path, mut wal_rep) =
+        report_prod.new_report(f, recovered_hostname, "WAL_Frames_Report", None)?;
+    writeln!(status_logger, "{}\n", wal_rep_path.to_string_lossy())?;
+    // csv header, Frame and Value are not set in every row
+    for field in [
+        "WorkId",
+        "ColumnId",
+        "Property",
+        "Value",
+        "Source",
+        "Frame",
+        "Page",
+        "Committed",
+        "WalOnly",
+        "Superseded",
+    ] {
+        wal_rep.set_field(field);
+    }
+    // the rows of a per-user database belong to its user
+    let db_sid = sid_from_db_path(f);
+    if db_sid.is_some() {
+        wal_rep.set_field("UserSID");
+    }
+    for ((workId, columnId), row) in versions {
+        if row.iter().all(|v| v.frame.is_none()) {
+            continue;
+        }
+        let Some(prop) = idToProp.get(&columnId) else {
+            continue;
+        };
+        let wal_only = row[0].frame.is_some();
+        for (i, version) in row.iter().enumerate() {
+            // uncommitted versions are superseded by any later one, committed ones by committed ones
+            let superseded = row[i + 1..]
+                .iter()
+                .any(|later| later.committed || !version.committed);
+            wal_rep.create_new_row();
+            wal_rep.insert_i64("WorkId", workId);
+            wal_rep.insert_i64("ColumnId", columnId);
+            wal_rep.insert_str_val("Property", prop.name.clone());
+            insert_property_val(&mut *wal_rep, "Value", prop, &version.value);
+            match version.frame {
+                Some(frame) => {
+                    wal_rep.insert_str_val("Source", "wal".to_string());
+                    wal_rep.insert_int_val("Frame", frame as u64);
+                }
+                None => wal_rep.insert_str_val("Source", "db".to_string()),
+            }
+            wal_rep.insert_int_val("Page", version.page as u64);
+            wal_rep.insert_bool("Committed", version.committed);
+            wal_rep.insert_bool("WalOnly", wal_only);
+            wal_rep.insert_bool("Superseded", superseded);
+            if let Some(sid) = &db_sid {
+                wal_rep.insert_str_val("UserSID", sid.clone());
+            }
+        }
+    }
+    wal_rep.finish()
+}
+
+pub fn sqlite_get_root_page(c: &sqlite::Connection, table: &str) -> Result<u32, SidrError> {
+    let q = "select rootpage from sqlite_master where type = 'table' and name = ?";
+    let mut s = c.prepare(q)?;
+    s.bind((1, table))?;
+    if s.next()? == State::Row {
+        return Ok(s.read::<i64, _>("rootpage")? as u32);
+    }
+    Err(SidrError::Corrupt(format!("Can't find table {table}")))
+}
+
+// Whether a SQLite database holds the PropertyStore table, unlike the gather and USN
+// databases next to Windows.db.
+pub fn sqlite_is_search_db(f: &Path) -> bool {
+    let flags = sqlite::OpenFlags::new().with_read_only();
+    sqlite::Connection::open_with_flags(f, flags)
+        .is_ok_and(|c| sqlite_get_root_page(&c, "SystemIndex_1_PropertyStore").is_ok())
+}
+
+// Whether a carved value has the SQLite type the property is stored with.
+fn is_plausible_value(prop: &PropertyInfo, val: &Value) -> bool {
+    match (prop.storage_type, val) {
+        (STORAGE_TYPE_STRING, Value::String(_)) => true,
+        (STORAGE_TYPE_QWORD, Value::Binary(b)) => b.len() == 8,
+        (STORAGE_TYPE_GUID, Value::Binary(b)) => b.len() == 16,
+        (STORAGE_TYPE_BLOB, Value::Binary(_)) => true,
+        (STORAGE_TYPE_R8, Value::Float(_) | Value::Integer(_)) => true,
+        (
+            STORAGE_TYPE_UI1 | STORAGE_TYPE_UI2 | STORAGE_TYPE_I4 | STORAGE_TYPE_UI4
+            | STORAGE_TYPE_BOOL,
+            Value::Integer(_),
+        ) => true,
+        _ => false,
+    }
+}
+
+fn value_digest(val: &Value) -> u64 {
+    let mut h = DefaultHasher::new();
+    match val {
+        Value::Binary(b) => (0u8, b).hash(&mut h),
+        Value::Float(n) => (1u8, n.to_bits()).hash(&mut h),
+        Value::Integer(n) => (2u8, n).hash(&mut h),
+        Value::String(s) => (3u8, s).hash(&mut h),
+        Value::Null => 4u8.hash(&mut h),
+    }
+    h.finish()
+}
+
+fn sqlite_has_gather_tables(c: &sqlite::Connection) -> bool {
+    let q = "select count(*) from sqlite_master
+             where type = 'table'
+             and name in ('SystemIndex_Gthr', 'SystemIndex_GthrPth')";
+    match c.prepare(q) {
+        Ok(mut s) => matches!(s.next(), Ok(State::Row)) && s.read::<i64, _>(0).unwrap_or(0) == 2,
+        Err(_) => false,
+    }
+}
+
+// The gather tables are kept either in Windows.db itself or next to it in Windows-gather.db,
+// which is copied next to the copy of Windows.db (db) as well.
+fn sqlite_open_gather_db(
+    f: &Path,
+    db: &Path,
+    c: &sqlite::Connection,
+    with_wal: bool,
+) -> Option<sqlite::Connection> {
+    let gather_db = if sqlite_has_gather_tables(c) {
+        db.to_path_buf()
+    } else {
+        let stem = f.file_stem()?.to_string_lossy();
+        let gather_path = f.with_file_name(format!("{stem}-gather.db"));
+        if !gather_path.exists() {
+            return None;
+        }
+        sqlite_copy_db(&gather_path, db.parent()?, with_wal).ok()?
+    };
+    let gc =
+        sqlite::Connection::open_with_flags(gather_db, sqlite::OpenFlags::new().with_read_write())
+            .ok()?;
+    sqlite_has_gather_tables(&gc).then_some(gc)
+}
+
+// Gather History Report: every item crawled by the indexer (SystemIndex_Gthr),
+// including the ones already removed from the PropertyStore.
+fn sqlite_generate_gather_report(
+    f: &Path,
+    c: &sqlite::Connection,
+    report_prod: &ReportProducer,
+    recovered_hostname: &str,
+    work_ids: &HashSet<u32>,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    let db_sid = sid_from_db_path(f);
+    let mut scopes = GatherScopes::new();
+    let s = c.prepare("select Scope, Parent, Name from SystemIndex_GthrPth")?;
+    for row in s.into_iter() {
+        let row = row?;
+        let (Some(scope), Some(parent)) = (int_value(&row["Scope"]), int_value(&row["Parent"]))
+        else {
+            continue;
+        };
+        let name = str_value(&row["Name"]).unwrap_or_default();
+        scopes.insert(scope, (parent, name));
+    }
+
+    let mut gather_rep =
+        init_gather_report(f, report_prod, recovered_hostname, status_logger, None)?;
+    let s = c.prepare("select * from SystemIndex_Gthr")?;
+    let columns = s.column_names().to_vec();
+    for row in s.into_iter() {
+        let row = row?;
+        let get = |name: &str| -> Option<&Value> {
+            columns.iter().any(|c| c == name).then(|| &row[name])
+        };
+        let get_int = |name: &str| get(name).and_then(int_value);
+        let g = GatherRecord {
+            scope_id: get_int("ScopeID").unwrap_or_default(),
+            document_id: get_int("DocumentID").unwrap_or_default(),
+            file_name: get("FileName").and_then(str_value),
+            last_modified: get("LastModified").and_then(|v| match v {
+                Value::Binary(b) if b.len() == 8 => {
+                    Some(get_date_time_from_filetime(u64::from_bytes(b)))
+                }
+                Value::Integer(n) => Some(get_date_time_from_filetime(*n as u64)),
+                _ => None,
+            }),
+            transaction_flags: get_int("TransactionFlags"),
+            crawl_number_crawled: get_int("CrawlNumberCrawled"),
+            deleted_count: get_int("DeletedCount"),
+            run_time: get_int("RunTime"),
+            last_requested_run_time: get_int("LastRequestedRunTime"),
+            failure_update_attempts: get_int("FailureUpdateAttempts"),
+        };
+        let in_property_store = work_ids.contains(&(g.document_id as u32));
+        write_gather_record(
+            &mut *gather_rep,
+            &g,
+            &scopes,
+            in_property_store,
+            db_sid.as_deref(),
+        );
+    }
+    gather_rep.finish()
+}
+
+fn int_value(v: &Value) -> Option<i64> {
+    match v {
+        Value::Integer(n) => Some(*n),
+        Value::Binary(b) => int_from_bytes(b),
+        _ => None,
+    }
+}
+
+// Text columns may hold either UTF-8 text or UTF-16 blobs.
+fn str_value(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Binary(b) => Some(from_utf16(b)),
+        _ => None,
+    }
+}
+
+// Same as sqlite_generate_report, but the reports are described by a ReportsCfg.
+pub fn sqlite_generate_cfg_report(
+    f: &Path,
+    cfg: &ReportsCfg,
+    report_prod: &ReportProducer,
+    opts: &ParseOptions,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    writeln!(
+        status_logger,
+        "Processing SQLite db: {}",
+        &f.to_string_lossy()
+    )?;
+    let tmp = TempDir::new("sidr")?;
+    let db = sqlite_copy_db(f, tmp.path(), opts.wal != WalMode::Ignore)?;
+    let mut reader = SqlReader::new(&db.to_string_lossy())?;
+    do_reports(cfg, &mut reader, report_prod, f, None, "", &opts.user_names)?;
+    if opts.wal == WalMode::Both {
+        let tmp = TempDir::new("sidr")?;
+        let db = sqlite_copy_db(f, tmp.path(), false)?;
+        let mut reader = SqlReader::new(&db.to_string_lossy())?;
+        do_reports(
+            cfg,
+            &mut reader,
+            report_prod,
+            f,
+            None,
+            "_without_WAL",
+            &opts.user_names,
+        )?;
+    }
+    if opts.wal != WalMode::Auto {
+        if let Err(e) = sqlite_generate_cfg_wal_report(f, &db, report_prod, status_logger) {
+            eprintln!("WAL Frames Report skipped: {e}");
+        }
+    }
+    Ok(())
+}
+
+// The WAL Frames Report of the built-in reports, for a database reported by a ReportsCfg.
+fn sqlite_generate_cfg_wal_report(
+    f: &Path,
+    db: &Path,
+    report_prod: &ReportProducer,
+    status_logger: &mut Box<dyn Write>,
+) -> Result<(), SidrError> {
+    let c = sqlite::Connection::open_with_flags(db, sqlite::OpenFlags::new().with_read_write())?;
+    let recovered_hostname = sqlite_get_hostname(&c).unwrap_or_else(|_| "Unknown".to_string());
+    let recovered_hostname = report_host_name(recovered_hostname, sid_from_db_path(f).as_deref());
+    let mut idToProp = HashMap::<i64, PropertyInfo>::new();
+    let mut propNameToId = HashMap::<String, i64>::new();
+    populate_property_id_maps(&c, &mut idToProp, &mut propNameToId)
+        .map_err(|e| SidrError::Corrupt(format!("Unable to read property IDs: {e}")))?;
+    sqlite_generate_wal_report(
+        f,
+        &c,
+        report_prod,
+        &recovered_hostname,
+        &idToProp,
+        status_logger,
+    )
+}
+
 fn write_record_to_report(
-    record: &HashMap<i64, Vec<u8>>,
+    record: &HashMap<i64, Value>,
     workId: u32,
-    idToProp: &HashMap<i64, (String, i64)>,
+    idToProp: &HashMap<i64, PropertyInfo>,
     report: &mut dyn Report,
 ) {
     report.create_new_row();
     report.insert_int_val("WorkId", workId as u64);
 
-    for (col, val) in record.iter().sorted() {
-        let property_name = idToProp.get(col);
-        if let Some((property_name, storage_type)) = property_name {
-            let property_name = property_name.replace(".", "_");
-            match storage_type {
-                11 => {
-                    // inferred to be string type
-                    report.insert_str_val(&property_name, String::from_utf8_lossy(val).into_owned())
-                }
-                12 => {
-                    // inferred to be date type when "Date" present in property name
-                    if property_name.contains("Date") || property_name.contains("Time") {
-                        report.insert_str_val(
-                            &property_name,
-                            format_date_time(get_date_time_from_filetime(u64::from_bytes(val))),
-                        )
-                    } else {
-                        // otherwise inferred to be int type
-                        report.insert_int_val(&property_name, u64::from_bytes(val))
-                    }
-                }
-                _ => { /* Storage type not supported. */ }
+    for (col, val) in record.iter().sorted_by_key(|(col, _)| **col) {
+        match idToProp.get(col) {
+            Some(prop) => insert_property_val(report, &prop.field, prop, val),
+            // ColumnId without a metadata row, keep the raw value
+            None => insert_property_val(
+                report,
+                &format!("ColumnId_{col}"),
+                &PropertyInfo {
+                    name: String::new(),
+                    field: String::new(),
+                    storage_type: -1,
+                    variant_type: -1,
+                },
+                val,
+            ),
+        }
+    }
+}
+
+// Decodes a property value according to its StorageType/VariantType.
+// Values which can't be interpreted are written as hex, so nothing is dropped.
+fn insert_property_val(r: &mut dyn Report, f: &str, prop: &PropertyInfo, val: &Value) {
+    let is_date_name = prop.name.contains("Date") || prop.name.contains("Time");
+    match val {
+        Value::Null => {}
+        Value::Integer(n) if prop.variant_type == VT_BOOL => r.insert_bool(f, *n != 0),
+        Value::Integer(n) => r.insert_i64(f, *n),
+        Value::Float(n) if prop.variant_type == VT_DATE => {
+            match get_date_time_from_variant_time(*n) {
+                Some(dt) => r.insert_datetime(f, dt),
+                None => r.insert_f64(f, *n),
             }
         }
+        Value::Float(n) => r.insert_f64(f, *n),
+        Value::String(s) => insert_strings(r, f, s),
+        Value::Binary(b) => insert_binary_val(r, f, prop, b, is_date_name),
     }
 }
 
-fn is_internet_record(
-    record: &HashMap<i64 /*ColumnId*/, Vec<u8> /*Value*/>,
-    propNameToId: &HashMap<String, i64>,
-) -> Result<(), SimpleError> {
-    let targetUriId = propNameToId
-        .get("System.Link.TargetUrl")
-        .ok_or_else(|| SimpleError::new("Could not find System.Link.TargetUrl ID in map."))?;
-    let targetUriVal = record
-        .get(targetUriId)
-        .ok_or_else(|| SimpleError::new("Could not find System.Link.TargetUrl field in record."))?;
-    let uriValStr = String::from_utf8_lossy(targetUriVal).into_owned();
-    if !(uriValStr.starts_with("http")) {
-        return Err(SimpleError::new(
-            "System.Link.TargetUrl does not start with http.",
-        ));
+fn insert_binary_val(
+    r: &mut dyn Report,
+    f: &str,
+    prop: &PropertyInfo,
+    b: &[u8],
+    is_date_name: bool,
+) {
+    match (prop.variant_type, b.len()) {
+        (VT_FILETIME, 8) => r.insert_datetime(f, get_date_time_from_filetime(u64::from_bytes(b))),
+        // FILETIMEs are also stored as UI8, guess them by the property name
+        (VT_UI8, 8) if is_date_name => {
+            r.insert_datetime(f, get_date_time_from_filetime(u64::from_bytes(b)))
+        }
+        (VT_UI8, 8) => r.insert_int_val(f, u64::from_bytes(b)),
+        (VT_I8, 8) => r.insert_i64(f, i64::from_bytes(b)),
+        (VT_CLSID, 16) => r.insert_str_val(f, format_guid(b)),
+        (VT_R8, 8) => r.insert_f64(f, f64::from_le_bytes(b.try_into().unwrap())),
+        (VT_R4, 4) => insert_f32(r, f, f32::from_le_bytes(b.try_into().unwrap())),
+        (VT_DATE, 8) => {
+            match get_date_time_from_variant_time(f64::from_le_bytes(b.try_into().unwrap())) {
+                Some(dt) => r.insert_datetime(f, dt),
+                None => r.insert_bytes(f, b),
+            }
+        }
+        (VT_BOOL, 2) => r.insert_bool(f, i16::from_bytes(b) != 0),
+        (VT_I1, 1) => r.insert_i64(f, b[0] as i8 as i64),
+        (VT_UI1, 1) => r.insert_int_val(f, u8::from_bytes(b) as u64),
+        (VT_I2, 2) => r.insert_i64(f, i16::from_bytes(b) as i64),
+        (VT_UI2, 2) => r.insert_int_val(f, u16::from_bytes(b) as u64),
+        (VT_I4, 4) => r.insert_i64(f, i32::from_bytes(b) as i64),
+        (VT_UI4, 4) => r.insert_int_val(f, u32::from_bytes(b) as u64),
+        (VT_LPWSTR, _) => insert_strings(r, f, &from_utf16(b)),
+        // no usable VariantType, fall back to the StorageType
+        _ if prop.storage_type == STORAGE_TYPE_QWORD && b.len() == 8 => {
+            if is_date_name {
+                r.insert_datetime(f, get_date_time_from_filetime(u64::from_bytes(b)))
+            } else {
+                r.insert_int_val(f, u64::from_bytes(b))
+            }
+        }
+        _ if prop.storage_type == STORAGE_TYPE_GUID && b.len() == 16 => {
+            r.insert_str_val(f, format_guid(b))
+        }
+        // VT_BLOB/VT_STREAM and the rest: text is often kept as (multi-valued) UTF-16
+        _ => match utf16_strings(b) {
+            Some(mut strings) if strings.len() == 1 => r.insert_str_val(f, strings.remove(0)),
+            Some(strings) => r.insert_list(f, strings),
+            None => r.insert_bytes(f, b),
+        },
     }
-    Ok(())
 }
 
-fn is_activity_history_record(
-    record: &HashMap<i64 /*ColumnId*/, Vec<u8> /*Value*/>,
-    propNameToId: &HashMap<String, i64>,
-) -> Result<(), SimpleError> {
-    let itemTypeId = propNameToId
-        .get("System.ItemType")
-        .ok_or_else(|| SimpleError::new("Could not find System.ItemType ID in map."))?;
-    let itemTypeVal = record
-        .get(itemTypeId)
-        .ok_or_else(|| SimpleError::new("Could not find System.ItemType field in record."))?;
-    let itemTypeStr = String::from_utf8_lossy(itemTypeVal).into_owned();
-    if itemTypeStr != "ActivityHistoryItem" {
-        return Err(SimpleError::new(
-            "System.ItemType is not ActivityHistoryItem.",
-        ));
+// FieldReader over a single SQLite record (all the properties of one WorkId),
+// fields are addressed by property name with '.' replaced by '_' (e.g. "System_ItemUrl").
+pub struct SqliteRecord<'a> {
+    record: &'a HashMap<i64 /*ColumnId*/, Value>,
+    idToProp: &'a HashMap<i64, PropertyInfo>,
+    read: bool,
+}
+
+impl<'a> SqliteRecord<'a> {
+    pub fn new(record: &'a HashMap<i64, Value>, idToProp: &'a HashMap<i64, PropertyInfo>) -> Self {
+        SqliteRecord {
+            record,
+            idToProp,
+            read: false,
+        }
+    }
+
+    fn value(&self, id: &str) -> Option<&Value> {
+        self.record
+            .iter()
+            .find(|(col, _)| self.idToProp.get(col).is_some_and(|prop| prop.field == id))
+            .map(|(_, val)| val)
+    }
+}
+
+impl FieldReader for SqliteRecord<'_> {
+    fn get_used_columns(
+        &mut self,
+        _columns: &[ColumnPair],
+    ) -> Result<Vec<ConstrainedField>, SidrError> {
+        Ok(Vec::new())
+    }
+
+    fn init(&mut self) -> Result<bool, SidrError> {
+        self.read = false;
+        Ok(true)
+    }
+
+    fn next(&mut self) -> Result<bool, SidrError> {
+        Ok(!std::mem::replace(&mut self.read, true))
+    }
+
+    fn get_int(&mut self, id: &String) -> Option<i64> {
+        match self.value(id)? {
+            Value::Integer(n) => Some(*n),
+            Value::Binary(b) if b.len() == 8 => Some(i64::from_bytes(b)),
+            _ => None,
+        }
+    }
+
+    fn get_str(&mut self, id: &String) -> Option<String> {
+        match self.value(id)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Binary(b) => Some(from_utf16(b)),
+            Value::Integer(n) => Some(n.to_string()),
+            Value::Float(n) => Some(n.to_string()),
+            Value::Null => None,
+        }
+    }
+
+    fn get_guid(&mut self, id: &String) -> Option<String> {
+        self.get_str(id)
+            .map(|s| find_guid(&s, (id.to_owned() + "=").as_str()))
+    }
+
+    fn get_datetime(&mut self, id: &String) -> Option<DateTime<Utc>> {
+        match self.value(id)? {
+            Value::Binary(b) if b.len() == 8 => {
+                Some(get_date_time_from_filetime(u64::from_bytes(b)))
+            }
+            _ => None,
+        }
     }
-    Ok(())
 }
 
 #[test]
 fn test_get_property_id_map() {
-    let f = "tests/testdata/Windows.db";
-    let c = map_err!(sqlite::Connection::open_with_flags(
-        f,
-        sqlite::OpenFlags::new().with_read_only()
-    ))
-    .unwrap();
-    let mut idToProp = HashMap::<i64, (String, i64)>::new();
+    let tmp = TempDir::new("sidr").unwrap();
+    let f = sqlite_copy_db(Path::new("tests/testdata/Windows.db"), tmp.path(), true).unwrap();
+    let c = sqlite::Connection::open(f).unwrap();
+    let mut idToProp = HashMap::<i64, PropertyInfo>::new();
     let mut PropNameToId = HashMap::<String, i64>::new();
     populate_property_id_maps(&c, &mut idToProp, &mut PropNameToId).unwrap();
     assert!(idToProp.len() == 597);
     assert!(PropNameToId.len() == idToProp.len());
 }
+
+#[test]
+fn test_sqlite_record_sizes() {
+    let idToProp: HashMap<i64, PropertyInfo> = ["System.Size", "System.DateModified"]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let prop = PropertyInfo {
+                name: name.to_string(),
+                field: name.replace('.', "_"),
+                storage_type: STORAGE_TYPE_QWORD,
+                variant_type: VT_UI8,
+            };
+            (i as i64 + 1, prop)
+        })
+        .collect();
+    let size = "System_Size".to_string();
+    let date = "System_DateModified".to_string();
+
+    let record = HashMap::from([
+        (1, Value::Binary(174_u64.to_le_bytes().to_vec())),
+        (
+            2,
+            Value::Binary(133196328453588944_u64.to_le_bytes().to_vec()),
+        ),
+    ]);
+    let mut r = SqliteRecord::new(&record, &idToProp);
+    assert_eq!(r.get_int(&size), Some(174));
+    assert_eq!(
+        r.get_datetime(&date).map(format_date_time),
+        Some("2023-01-31T10:00:45.3588944Z".to_string())
+    );
+
+    // truncated values are not read past their end
+    let record = HashMap::from([
+        (1, Value::Binary(vec![0xAE, 0x00])),
+        (2, Value::Binary(vec![0xD0, 0x9B, 0x3D])),
+    ]);
+    let mut r = SqliteRecord::new(&record, &idToProp);
+    assert_eq!(r.get_int(&size), None);
+    assert_eq!(r.get_datetime(&date), None);
+}
@@ -0,0 +1,651 @@
+// Carving of (WorkId, ColumnId, Value) tuples of SystemIndex_1_PropertyStore
+// from the freelist pages and the unallocated space of the table's b-tree pages,
+// and from the frames of the write-ahead log (Windows.db-wal).
+//
+// SystemIndex_1_PropertyStore is a WITHOUT ROWID table, so its rows are stored in
+// index b-tree cells whose payload is a plain record: header, then WorkId, ColumnId, Value.
+
+use crate::error::SidrError;
+use sqlite::Value;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const DB_HEADER_SIZE: usize = 100;
+
+const PAGE_TYPE_INTERIOR_INDEX: u8 = 0x02;
+const PAGE_TYPE_INTERIOR_TABLE: u8 = 0x05;
+const PAGE_TYPE_LEAF_INDEX: u8 = 0x0A;
+
+const WAL_HEADER_SIZE: usize = 32;
+const WAL_FRAME_HEADER_SIZE: usize = 24;
+const WAL_MAGIC_LE: u32 = 0x377F0682;
+const WAL_MAGIC_BE: u32 = 0x377F0683;
+
+const TEXT_ENCODING_UTF8: u32 = 1;
+const TEXT_ENCODING_UTF16LE: u32 = 2;
+
+// (WorkId, ColumnId, Value)
+pub type PropertyTuple = (i64, i64, Value);
+
+pub struct SqlitePages {
+    file: File,
+    pub page_size: usize,
+    pub usable_size: usize,
+    pub text_encoding: u32,
+    first_trunk: u32,
+}
+
+impl SqlitePages {
+    pub fn open(f: &Path) -> Result<Self, SidrError> {
+        let mut file = File::open(f)?;
+        let mut header = [0u8; DB_HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if !header.starts_with(b"SQLite format 3\0") {
+            return Err(SidrError::Corrupt(format!(
+                "{} is not a SQLite database",
+                f.to_string_lossy()
+            )));
+        }
+        let page_size = match read_u16(&header, 16).unwrap_or(0) {
+            1 => 65536,
+            size => size as usize,
+        };
+        Ok(SqlitePages {
+            file,
+            page_size,
+            usable_size: page_size - header[20] as usize,
+            text_encoding: read_u32(&header, 56).unwrap_or(TEXT_ENCODING_UTF8),
+            first_trunk: read_u32(&header, 32).unwrap_or(0),
+        })
+    }
+
+    pub fn read_page(&mut self, pgno: u32) -> Result<Vec<u8>, SidrError> {
+        if pgno == 0 {
+            return Err(SidrError::Corrupt("Invalid page number 0".to_string()));
+        }
+        let mut page = vec![0u8; self.page_size];
+        self.file
+            .seek(SeekFrom::Start((pgno as u64 - 1) * self.page_size as u64))?;
+        self.file.read_exact(&mut page)?;
+        Ok(page)
+    }
+
+    // Freelist trunk pages (the part after the leaf page numbers) and whole freelist leaf pages.
+    fn freelist_regions(&mut self) -> Result<Vec<(Vec<u8>, usize)>, SidrError> {
+        let mut regions = Vec::new();
+        let mut visited = HashSet::new();
+        let mut trunk = self.first_trunk;
+        while trunk != 0 && visited.insert(trunk) {
+            let page = self.read_page(trunk)?;
+            let next = read_u32(&page, 0).unwrap_or(0);
+            let leaves = read_u32(&page, 4).unwrap_or(0) as usize;
+            let leaves = leaves.min((self.usable_size - 8) / 4);
+            for i in 0..leaves {
+                if let Some(leaf) = read_u32(&page, 8 + 4 * i) {
+                    if visited.insert(leaf) {
+                        regions.push((self.read_page(leaf)?, 0));
+                    }
+                }
+            }
+            regions.push((page, 8 + 4 * leaves));
+            trunk = next;
+        }
+        Ok(regions)
+    }
+
+    // Leaf pages of the b-tree starting at root, pages found in overlay (e.g. the latest
+    // WAL frames) take precedence over the database file.
+    pub fn leaf_pages(
+        &mut self,
+        root: u32,
+        overlay: &HashMap<u32, Vec<u8>>,
+    ) -> Result<Vec<u32>, SidrError> {
+        let mut leaves = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(pgno) = stack.pop() {
+            if !visited.insert(pgno) {
+                continue;
+            }
+            let page = match overlay.get(&pgno) {
+                Some(page) => page.clone(),
+                None => self.read_page(pgno)?,
+            };
+            let off = page_header_offset(pgno);
+            match page.get(off) {
+                Some(&PAGE_TYPE_INTERIOR_INDEX) | Some(&PAGE_TYPE_INTERIOR_TABLE) => {
+                    let cells = read_u16(&page, off + 3).unwrap_or(0) as usize;
+                    stack.extend(read_u32(&page, off + 8));
+                    for i in 0..cells {
+                        let cell = read_u16(&page, off + 12 + 2 * i).map(|c| c as usize);
+                        stack.extend(cell.and_then(|c| read_u32(&page, c)));
+                    }
+                }
+                Some(&PAGE_TYPE_LEAF_INDEX) => leaves.push(pgno),
+                _ => {}
+            }
+        }
+        Ok(leaves)
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+// page 1 starts with the database header
+fn page_header_offset(pgno: u32) -> usize {
+    if pgno == 1 {
+        DB_HEADER_SIZE
+    } else {
+        0
+    }
+}
+
+// Part of the unallocated space of a b-tree leaf page.
+#[derive(Debug, PartialEq)]
+pub struct UnallocatedRegion {
+    pub start: usize,
+    pub end: usize,
+    // a freeblock, without its 4 byte header which overwrote the start of the freed cell
+    pub freeblock: bool,
+}
+
+// Unallocated space of a b-tree leaf page: the gap between the cell pointer array
+// and the cell content area, and the freeblocks.
+pub fn unallocated_regions(page: &[u8], pgno: u32) -> Vec<UnallocatedRegion> {
+    let off = page_header_offset(pgno);
+    let mut regions = Vec::new();
+    let (Some(first_freeblock), Some(cells), Some(content)) = (
+        read_u16(page, off + 1),
+        read_u16(page, off + 3),
+        read_u16(page, off + 5),
+    ) else {
+        return regions;
+    };
+    let content = if content == 0 {
+        65536
+    } else {
+        content as usize
+    };
+    let gap_start = off + 8 + 2 * cells as usize;
+    if gap_start < content {
+        regions.push(UnallocatedRegion {
+            start: gap_start,
+            end: content.min(page.len()),
+            freeblock: false,
+        });
+    }
+    let mut freeblock = first_freeblock as usize;
+    let mut visited = HashSet::new();
+    while freeblock != 0 && visited.insert(freeblock) {
+        let (Some(next), Some(size)) = (read_u16(page, freeblock), read_u16(page, freeblock + 2))
+        else {
+            break;
+        };
+        regions.push(UnallocatedRegion {
+            start: freeblock + 4,
+            end: (freeblock + size as usize).min(page.len()),
+            freeblock: true,
+        });
+        freeblock = next as usize;
+    }
+    regions
+}
+
+fn read_varint(data: &[u8], pos: usize) -> Option<(u64, usize)> {
+    let mut v = 0u64;
+    for i in 0..9 {
+        let b = *data.get(pos + i)?;
+        if i == 8 {
+            return Some(((v << 8) | b as u64, pos + 9));
+        }
+        v = (v << 7) | (b & 0x7F) as u64;
+        if b < 0x80 {
+            return Some((v, pos + i + 1));
+        }
+    }
+    None
+}
+
+fn serial_type_size(t: u64) -> Option<usize> {
+    match t {
+        0 | 8 | 9 => Some(0),
+        1..=4 => Some(t as usize),
+        5 => Some(6),
+        6 | 7 => Some(8),
+        10 | 11 => None,
+        _ => Some(((t - 12) / 2) as usize),
+    }
+}
+
+fn is_int_type(t: u64) -> bool {
+    matches!(t, 1..=6 | 8 | 9)
+}
+
+fn read_value(t: u64, data: &[u8], text_encoding: u32) -> Value {
+    match t {
+        0 => Value::Null,
+        8 => Value::Integer(0),
+        9 => Value::Integer(1),
+        1..=6 => {
+            // big-endian two's complement, sign extended
+            let n = data
+                .iter()
+                .fold(if data[0] & 0x80 != 0 { -1i64 } else { 0 }, |n, b| {
+                    (n << 8) | *b as i64
+                });
+            Value::Integer(n)
+        }
+        7 => Value::Float(f64::from_be_bytes(data.try_into().unwrap())),
+        t if t % 2 == 0 => Value::Binary(data.to_vec()),
+        _ if text_encoding == TEXT_ENCODING_UTF8 => {
+            Value::String(String::from_utf8_lossy(data).into_owned())
+        }
+        _ => {
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|c| match text_encoding {
+                    TEXT_ENCODING_UTF16LE => u16::from_le_bytes([c[0], c[1]]),
+                    _ => u16::from_be_bytes([c[0], c[1]]),
+                })
+                .collect();
+            Value::String(String::from_utf16_lossy(&units))
+        }
+    }
+}
+
+// Tries to read a (WorkId, ColumnId, Value) record at pos, returns the tuple and its end.
+fn parse_record(region: &[u8], pos: usize, text_encoding: u32) -> Option<(PropertyTuple, usize)> {
+    let (header_size, mut p) = read_varint(region, pos)?;
+    let header_end = pos + header_size as usize;
+    if !(4..=12).contains(&header_size) || header_end > region.len() {
+        return None;
+    }
+    let mut types = Vec::with_capacity(3);
+    while p < header_end {
+        let (t, next) = read_varint(region, p)?;
+        types.push(t);
+        p = next;
+    }
+    if p != header_end || types.len() != 3 {
+        return None;
+    }
+    read_values(region, p, [types[0], types[1], types[2]], text_encoding)
+}
+
+// Reads the WorkId, ColumnId and Value of the serial types at pos.
+fn read_values(
+    region: &[u8],
+    mut p: usize,
+    types: [u64; 3],
+    text_encoding: u32,
+) -> Option<(PropertyTuple, usize)> {
+    if !is_int_type(types[0]) || !is_int_type(types[1]) {
+        return None;
+    }
+    let mut values = Vec::with_capacity(3);
+    for t in types {
+        let size = serial_type_size(t)?;
+        if t == 0 || p + size > region.len() {
+            return None;
+        }
+        values.push(read_value(t, &region[p..p + size], text_encoding));
+        p += size;
+    }
+    let value = values.pop()?;
+    let (Some(Value::Integer(column_id)), Some(Value::Integer(work_id))) =
+        (values.pop(), values.pop())
+    else {
+        return None;
+    };
+    if work_id <= 0 {
+        return None;
+    }
+    Some(((work_id, column_id, value), p))
+}
+
+// The 4 byte header of a freeblock overwrites the payload size and the start of the record
+// header of the cell it held: its header size and the serial types of WorkId and ColumnId
+// (with a 1 byte payload size), or the serial type of WorkId only (with a 2 byte one).
+// The record header is known to hold 3 integer serial types before the one of the Value,
+// so the lost ones are guessed until the record fills the freeblock.
+fn carve_freeblock(
+    block: &[u8],
+    text_encoding: u32,
+    is_plausible: &dyn Fn(&PropertyTuple) -> bool,
+) -> Option<(PropertyTuple, usize)> {
+    // smallest first, is_plausible tells apart the ColumnIds of the same total size
+    const INT_TYPES: [u64; 7] = [1, 2, 3, 4, 6, 8, 9];
+    let mut candidates = Vec::new();
+    // 1 byte payload size: [Value type] WorkId ColumnId Value
+    if let Some((value_type, pos)) = read_varint(block, 0) {
+        for work_id_type in INT_TYPES {
+            for column_id_type in INT_TYPES {
+                candidates.push(([work_id_type, column_id_type, value_type], pos));
+            }
+        }
+    }
+    // 2 byte payload size: [ColumnId type, Value type] WorkId ColumnId Value
+    if let Some((column_id_type, pos)) = read_varint(block, 0) {
+        if let Some((value_type, pos)) = read_varint(block, pos) {
+            for work_id_type in INT_TYPES {
+                candidates.push(([work_id_type, column_id_type, value_type], pos));
+            }
+        }
+    }
+    candidates.into_iter().find_map(|(types, pos)| {
+        let (tuple, end) = read_values(block, pos, types, text_encoding)?;
+        (end == block.len() && is_plausible(&tuple)).then_some((tuple, end))
+    })
+}
+
+// Scans a region byte by byte for PropertyStore records.
+pub fn carve_region(
+    region: &[u8],
+    text_encoding: u32,
+    is_plausible: &dyn Fn(&PropertyTuple) -> bool,
+) -> Vec<PropertyTuple> {
+    let mut tuples = Vec::new();
+    let mut pos = 0;
+    while pos + 4 < region.len() {
+        match parse_record(region, pos, text_encoding) {
+            Some((tuple, end)) if is_plausible(&tuple) => {
+                tuples.push(tuple);
+                pos = end;
+            }
+            _ => pos += 1,
+        }
+    }
+    tuples
+}
+
+// Carves the freelist and the unallocated space of the b-tree rooted at root.
+pub fn sqlite_recover_tuples(
+    f: &Path,
+    root: u32,
+    is_plausible: &dyn Fn(&PropertyTuple) -> bool,
+) -> Result<Vec<PropertyTuple>, SidrError> {
+    let mut pages = SqlitePages::open(f)?;
+    let usable_size = pages.usable_size;
+    let text_encoding = pages.text_encoding;
+    let mut tuples = Vec::new();
+    for (page, start) in pages.freelist_regions()? {
+        tuples.extend(carve_region(
+            &page[start.min(usable_size)..usable_size],
+            text_encoding,
+            is_plausible,
+        ));
+    }
+    for pgno in pages.leaf_pages(root, &HashMap::new())? {
+        let page = pages.read_page(pgno)?;
+        for region in unallocated_regions(&page, pgno) {
+            if region.start >= region.end {
+                continue;
+            }
+            let data = &page[region.start..region.end];
+            let freed = region
+                .freeblock
+                .then(|| carve_freeblock(data, text_encoding, is_plausible))
+                .flatten();
+            match freed {
+                Some((tuple, _)) => tuples.push(tuple),
+                None => tuples.extend(carve_region(data, text_encoding, is_plausible)),
+            }
+        }
+    }
+    Ok(tuples)
+}
+
+// Rows stored in the cells of a b-tree leaf page, payloads spilling to overflow pages are skipped.
+pub fn leaf_cells(
+    page: &[u8],
+    pgno: u32,
+    usable_size: usize,
+    text_encoding: u32,
+) -> Vec<PropertyTuple> {
+    let off = page_header_offset(pgno);
+    let mut tuples = Vec::new();
+    if page.get(off) != Some(&PAGE_TYPE_LEAF_INDEX) {
+        return tuples;
+    }
+    let max_local = (usable_size - 12) * 64 / 255 - 23;
+    let cells = read_u16(page, off + 3).unwrap_or(0) as usize;
+    for i in 0..cells {
+        let Some(cell) = read_u16(page, off + 8 + 2 * i) else {
+            break;
+        };
+        let Some((payload_size, start)) = read_varint(page, cell as usize) else {
+            continue;
+        };
+        let end = start + payload_size as usize;
+        if payload_size as usize > max_local || end > page.len() {
+            continue;
+        }
+        if let Some((tuple, _)) = parse_record(&page[start..end], 0, text_encoding) {
+            tuples.push(tuple);
+        }
+    }
+    tuples
+}
+
+pub struct WalFrame {
+    pub pgno: u32,
+    // part of a transaction whose commit frame is in the WAL
+    pub committed: bool,
+    pub page: Vec<u8>,
+}
+
+pub fn wal_path(f: &Path) -> std::path::PathBuf {
+    let mut name = f.as_os_str().to_owned();
+    name.push("-wal");
+    name.into()
+}
+
+fn wal_checksum(big_endian: bool, data: &[u8], (mut s0, mut s1): (u32, u32)) -> (u32, u32) {
+    for chunk in data.chunks_exact(8) {
+        let (x0, x1) = if big_endian {
+            (read_u32(chunk, 0).unwrap(), read_u32(chunk, 4).unwrap())
+        } else {
+            (
+                u32::from_le_bytes(chunk[0..4].try_into().unwrap()),
+                u32::from_le_bytes(chunk[4..8].try_into().unwrap()),
+            )
+        };
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+// Valid frames of a WAL file in log order: the salts match the WAL header and
+// the checksum chain is intact (the same frames SQLite would consider).
+pub fn read_wal(data: &[u8]) -> Result<Vec<WalFrame>, SidrError> {
+    let mut frames = Vec::new();
+    if data.len() < WAL_HEADER_SIZE {
+        return Ok(frames);
+    }
+    let big_endian = match read_u32(data, 0) {
+        Some(WAL_MAGIC_LE) => false,
+        Some(WAL_MAGIC_BE) => true,
+        _ => return Err(SidrError::Corrupt("Invalid WAL header".to_string())),
+    };
+    let page_size = read_u32(data, 8).unwrap_or(0) as usize;
+    if !(512..=65536).contains(&page_size) {
+        return Err(SidrError::Corrupt(format!(
+            "Invalid WAL page size {page_size}"
+        )));
+    }
+    let salts = &data[16..24];
+    let mut checksum = wal_checksum(big_endian, &data[..24], (0, 0));
+    if (read_u32(data, 24), read_u32(data, 28)) != (Some(checksum.0), Some(checksum.1)) {
+        return Err(SidrError::Corrupt(
+            "Invalid WAL header checksum".to_string(),
+        ));
+    }
+
+    let frame_size = WAL_FRAME_HEADER_SIZE + page_size;
+    let mut last_commit = 0;
+    for frame in data[WAL_HEADER_SIZE..].chunks_exact(frame_size) {
+        if &frame[8..16] != salts {
+            break;
+        }
+        checksum = wal_checksum(big_endian, &frame[..8], checksum);
+        checksum = wal_checksum(big_endian, &frame[WAL_FRAME_HEADER_SIZE..], checksum);
+        if (read_u32(frame, 16), read_u32(frame, 20)) != (Some(checksum.0), Some(checksum.1)) {
+            break;
+        }
+        frames.push(WalFrame {
+            pgno: read_u32(frame, 0).unwrap_or(0),
+            committed: false,
+            page: frame[WAL_FRAME_HEADER_SIZE..].to_vec(),
+        });
+        // commit frames hold the database size in pages
+        if read_u32(frame, 4).unwrap_or(0) != 0 {
+            last_commit = frames.len();
+        }
+    }
+    for frame in &mut frames[..last_commit] {
+        frame.committed = true;
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // index b-tree cell payload: header (size 4), WorkId, ColumnId, Value
+    fn record(work_id: u8, column_id: u8, text: &str) -> Vec<u8> {
+        let mut r = vec![4, 1, 1, 13 + 2 * text.len() as u8, work_id, column_id];
+        r.extend(text.as_bytes());
+        r
+    }
+
+    #[test]
+    fn test_carve_region() {
+        let mut region = vec![0xFF, 0x00, 0x13];
+        region.extend(record(42, 33, "C:\\deleted.txt"));
+        region.extend([0x00; 5]);
+        region.extend(record(43, 39, "file:C:/other.txt"));
+        let all = |_: &PropertyTuple| true;
+        let tuples = carve_region(&region, TEXT_ENCODING_UTF8, &all);
+        assert_eq!(tuples.len(), 2);
+        assert_eq!(tuples[0].0, 42);
+        assert_eq!(tuples[0].1, 33);
+        assert_eq!(tuples[0].2, Value::String("C:\\deleted.txt".to_string()));
+        assert_eq!(tuples[1].0, 43);
+
+        let only_33 = |t: &PropertyTuple| t.1 == 33;
+        assert_eq!(carve_region(&region, TEXT_ENCODING_UTF8, &only_33).len(), 1);
+    }
+
+    #[test]
+    fn test_unallocated_regions() {
+        let mut page = vec![0u8; 512];
+        page[0] = PAGE_TYPE_LEAF_INDEX;
+        page[1..3].copy_from_slice(&300u16.to_be_bytes()); // first freeblock
+        page[3..5].copy_from_slice(&2u16.to_be_bytes()); // cells
+        page[5..7].copy_from_slice(&200u16.to_be_bytes()); // cell content area
+        page[300..302].copy_from_slice(&0u16.to_be_bytes());
+        page[302..304].copy_from_slice(&20u16.to_be_bytes());
+        let region = |start, end, freeblock| UnallocatedRegion {
+            start,
+            end,
+            freeblock,
+        };
+        assert_eq!(
+            unallocated_regions(&page, 2),
+            vec![region(12, 200, false), region(304, 320, true)]
+        );
+    }
+
+    #[test]
+    fn test_carve_freeblock() {
+        let all = |_: &PropertyTuple| true;
+        // the freeblock header overwrote the payload size, header size, WorkId and ColumnId types
+        let freed = record(42, 33, "C:\\deleted.txt");
+        let tuple = carve_freeblock(&freed[3..], TEXT_ENCODING_UTF8, &all).unwrap();
+        assert_eq!(tuple.1, freed.len() - 3);
+        assert_eq!(
+            tuple.0,
+            (42, 33, Value::String("C:\\deleted.txt".to_string()))
+        );
+
+        // 2 byte WorkId, only the Value serial type is left
+        let mut freed = vec![4, 2, 1, 13 + 2 * 5, 0x04, 0x44, 39];
+        freed.extend(b"file:");
+        let known_column = |t: &PropertyTuple| t.1 == 33 || t.1 == 39;
+        let tuple = carve_freeblock(&freed[3..], TEXT_ENCODING_UTF8, &known_column).unwrap();
+        assert_eq!(tuple.0, (0x0444, 39, Value::String("file:".to_string())));
+
+        // the record must fill the freeblock
+        let freed = record(42, 33, "C:\\deleted.txt");
+        let truncated = &freed[3..freed.len() - 2];
+        assert!(carve_freeblock(truncated, TEXT_ENCODING_UTF8, &known_column).is_none());
+    }
+
+    #[test]
+    fn test_read_wal() {
+        let data = std::fs::read("tests/wal/Windows.db-wal").unwrap();
+        let frames = read_wal(&data).unwrap();
+        assert!(!frames.is_empty());
+        // the last transaction was not committed
+        assert!(frames[0].committed);
+        assert!(!frames.last().unwrap().committed);
+
+        let mut pages = SqlitePages::open(Path::new("tests/wal/Windows.db")).unwrap();
+        let (usable_size, text_encoding) = (pages.usable_size, pages.text_encoding);
+        let committed: HashMap<u32, Vec<u8>> = frames
+            .iter()
+            .filter(|frame| frame.committed)
+            .map(|frame| (frame.pgno, frame.page.clone()))
+            .collect();
+        let mut tuples = Vec::new();
+        for pgno in pages.leaf_pages(2, &committed).unwrap() {
+            let page = match committed.get(&pgno) {
+                Some(page) => page.clone(),
+                None => pages.read_page(pgno).unwrap(),
+            };
+            tuples.extend(leaf_cells(&page, pgno, usable_size, text_encoding));
+        }
+        let path = |w: i64, p: &str| (w, 33, Value::String(p.to_string()));
+        assert!(tuples.contains(&path(1, "C:\\newer.txt")));
+        assert!(tuples.contains(&path(3, "C:\\wal_only.txt")));
+        // the database file alone
+        let page = pages.read_page(2).unwrap();
+        let tuples = leaf_cells(&page, 2, usable_size, text_encoding);
+        assert!(tuples.contains(&path(1, "C:\\old.txt")));
+        assert!(!tuples.iter().any(|t| t.0 == 3));
+
+        // a broken checksum ends the log
+        let mut broken = data.clone();
+        broken[WAL_HEADER_SIZE + WAL_FRAME_HEADER_SIZE] ^= 0xFF;
+        assert!(read_wal(&broken).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recover_deleted_rows() {
+        let db = Path::new("tests/testdata/Windows.db");
+        let tmp = tempdir::TempDir::new("sidr").unwrap();
+        let copy = tmp.path().join("Windows.db");
+        std::fs::copy(db, &copy).unwrap();
+        let c = sqlite::Connection::open(&copy).unwrap();
+        let root = crate::sqlite::sqlite_get_root_page(&c, "SystemIndex_1_PropertyStore").unwrap();
+        let tuples = sqlite_recover_tuples(db, root, &|t| t.1 == 33).unwrap();
+        assert!(tuples.iter().any(|(work_id, _, value)| *work_id == 1092
+            && *value
+                == Value::String("C:\\Users\\Public\\malware\\New Text Document.txt".to_string())));
+    }
+}
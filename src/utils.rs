@@ -1,7 +1,16 @@
 use chrono::prelude::*;
 
 use bitflags::bitflags;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::FieldReader;
 
 /// Converts a u64 filetime to a DateTime<Utc>
 pub fn get_date_time_from_filetime(filetime: u64) -> DateTime<Utc> {
@@ -10,16 +19,30 @@ pub fn get_date_time_from_filetime(filetime: u64) -> DateTime<Utc> {
     let filetime_nanos: i128 = filetime as i128 * 100;
 
     // Add nanoseconds to timestamp via Duration
-    DateTime::<Utc>::from_utc(
-        NaiveDate::from_ymd_opt(1970, 1, 1)
+    Utc.from_utc_datetime(
+        &(NaiveDate::from_ymd_opt(1970, 1, 1)
             .unwrap()
             .and_hms_nano_opt(0, 0, 0, 0)
             .unwrap()
-            + chrono::Duration::nanoseconds((filetime_nanos - UNIX_EPOCH_NANOS) as i64),
-        Utc,
+            + chrono::Duration::nanoseconds((filetime_nanos - UNIX_EPOCH_NANOS) as i64)),
     )
 }
 
+/// Converts an OLE Automation date (days since 1899-12-30, as used by ESE DateTime columns) to a DateTime<Utc>
+pub fn get_date_time_from_variant_time(vartime: f64) -> Option<DateTime<Utc>> {
+    // Valid range of VariantTimeToSystemTime: 0100-01-01 .. 9999-12-31
+    if !vartime.is_finite() || !(-657434.0..2958466.0).contains(&vartime) {
+        return None;
+    }
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)?.and_hms_opt(0, 0, 0)?;
+    // the fractional part is the time of the day, also for negative dates
+    let days = vartime.trunc();
+    let millis = (vartime.fract().abs() * 86_400_000.0).round() as i64;
+    let naive =
+        epoch + chrono::Duration::days(days as i64) + chrono::Duration::milliseconds(millis);
+    Some(Utc.from_utc_datetime(&naive))
+}
+
 /// Converts a DateTime<Utc> to ISO-8601/RFC-3339 format `%Y-%m-%dT%H:%M:%S%.7f` (manually, since Rust doesn't support `%.7f`)
 pub fn format_date_time(date_time: DateTime<Utc>) -> String {
     let fractional_seconds = date_time.format("%9f").to_string();
@@ -55,6 +78,26 @@ pub fn find_guid(inp: &str, v: &str) -> String {
     s
 }
 
+// in: 16 bytes of a GUID as stored on disk
+// out: {AC048C6D-1E3C-4B21-B20D-75745DD788B3}
+pub fn format_guid(val: &[u8]) -> String {
+    if val.len() != 16 {
+        return to_hex(val);
+    }
+    format!(
+        "{{{:08X}-{:04X}-{:04X}-{}-{}}}",
+        u32::from_le_bytes(val[0..4].try_into().unwrap()),
+        u16::from_le_bytes(val[4..6].try_into().unwrap()),
+        u16::from_le_bytes(val[6..8].try_into().unwrap()),
+        to_hex(&val[8..10]),
+        to_hex(&val[10..16])
+    )
+}
+
+pub fn to_hex(val: &[u8]) -> String {
+    val.iter().map(|b| format!("{b:02X}")).collect()
+}
+
 pub fn from_utf16(val: &[u8]) -> String {
     let s: Vec<u16> = val
         .chunks_exact(2)
@@ -63,6 +106,27 @@ pub fn from_utf16(val: &[u8]) -> String {
     String::from_utf16_lossy(s.as_slice())
 }
 
+// Splits a blob into NUL separated UTF-16 strings, if it looks like text at all.
+pub fn utf16_strings(val: &[u8]) -> Option<Vec<String>> {
+    if val.is_empty() || !val.len().is_multiple_of(2) {
+        return None;
+    }
+    let s: Vec<u16> = val
+        .chunks_exact(2)
+        .map(|a| u16::from_le_bytes([a[0], a[1]]))
+        .collect();
+    let s = String::from_utf16(s.as_slice()).ok()?;
+    let s = s.trim_end_matches('\0');
+    let ascii = s.chars().filter(|c| c.is_ascii()).count();
+    if s.is_empty() || ascii * 2 < s.chars().count() {
+        return None;
+    }
+    if s.chars().any(|c| c != '\0' && c.is_control()) {
+        return None;
+    }
+    Some(s.split('\0').map(|s| s.to_string()).collect())
+}
+
 bitflags! {
     #[derive(Debug)]
     struct file_attributes_flag: u32 {
@@ -113,6 +177,139 @@ pub fn column_string_part(s: &str) -> &str {
     }
 }
 
+// "S-1-5-21-29705265-400737687-482427116-1001"
+fn is_sid(s: &str) -> bool {
+    let mut parts = s.split('-');
+    parts.next().is_some_and(|p| p.eq_ignore_ascii_case("s"))
+        && s.split('-').count() >= 3
+        && parts.all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+// SID of a per-user database of Windows 11, named after the user (S-1-5-21-...\S-1-5-21-....db).
+pub fn sid_from_db_path(f: &Path) -> Option<String> {
+    [f.file_stem(), f.parent().and_then(|p| p.file_name())]
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.to_str())
+        .find(|s| is_sid(s))
+        .map(|s| s.to_ascii_uppercase())
+}
+
+// "iehistory://{SID}/..." and "winrt://{SID}/..."
+pub fn sid_from_url(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("iehistory://")
+        .or_else(|| url.strip_prefix("winrt://"))?;
+    let (sid, _) = rest.strip_prefix('{')?.split_once('}')?;
+    is_sid(sid).then(|| sid.to_ascii_uppercase())
+}
+
+// User of a PropertyStore record: the SID of its URL, else the one of the per-user database.
+pub fn record_user_sid(record: &mut dyn FieldReader, db_sid: Option<&str>) -> Option<String> {
+    record
+        .get_str(&"System_ItemUrl".to_string())
+        .and_then(|url| sid_from_url(&url))
+        .or_else(|| db_sid.map(str::to_string))
+}
+
+// Account name of the user of a record: the one of its SID (see record_user_sid),
+// else the one of System_FileOwner when it is a SID.
+pub fn record_user_name(
+    record: &mut dyn FieldReader,
+    user_sid: Option<&str>,
+    user_names: &HashMap<String, String>,
+) -> Option<String> {
+    if user_names.is_empty() {
+        return None;
+    }
+    let sid = match user_sid {
+        Some(sid) => sid.to_string(),
+        None => record
+            .get_str(&"System_FileOwner".to_string())
+            .filter(|owner| is_sid(owner))?
+            .to_ascii_uppercase(),
+    };
+    user_names.get(&sid).cloned()
+}
+
+// Reports of per-user databases are named HOSTNAME_SID_ReportName_DateTime.
+pub fn report_host_name(hostname: String, db_sid: Option<&str>) -> String {
+    match db_sid {
+        Some(sid) => format!("{hostname}_{sid}"),
+        None => hostname,
+    }
+}
+
+#[test]
+fn user_sid_test() {
+    let sid = "S-1-5-21-29705265-400737687-482427116-1001";
+    assert_eq!(
+        sid_from_db_path(Path::new(&format!("Applications/{sid}/{sid}.db"))),
+        Some(sid.to_string())
+    );
+    assert_eq!(
+        sid_from_db_path(Path::new("Applications/s-1-5-18/Windows.db")),
+        Some("S-1-5-18".to_string())
+    );
+    assert_eq!(sid_from_db_path(Path::new("Windows/Windows.edb")), None);
+    assert_eq!(sid_from_db_path(Path::new("S-1-x.db")), None);
+
+    assert_eq!(
+        sid_from_url(&format!("iehistory://{{{sid}}}/https://www.msn.com/")),
+        Some(sid.to_string())
+    );
+    assert_eq!(
+        sid_from_url(&format!("winrt://{{{sid}}}/LS/Desktop/ActivityData")),
+        Some(sid.to_string())
+    );
+    assert_eq!(sid_from_url("file:C:/Users/user/notes.txt"), None);
+    assert_eq!(sid_from_url("winrt://{not a sid}/LS"), None);
+
+    assert_eq!(
+        report_host_name("DESKTOP-1".to_string(), Some(sid)),
+        format!("DESKTOP-1_{sid}")
+    );
+    assert_eq!(report_host_name("DESKTOP-1".to_string(), None), "DESKTOP-1");
+}
+
+#[test]
+fn format_guid_test() {
+    let guid = [
+        0x6D, 0x8C, 0x04, 0xAC, 0x3C, 0x1E, 0x21, 0x4B, 0xB2, 0x0D, 0x75, 0x74, 0x5D, 0xD7, 0x88,
+        0xB3,
+    ];
+    assert_eq!(format_guid(&guid), "{AC048C6D-1E3C-4B21-B20D-75745DD788B3}");
+    assert_eq!(format_guid(&guid[..4]), "6D8C04AC");
+}
+
+#[test]
+fn utf16_strings_test() {
+    let to_utf16 =
+        |s: &str| -> Vec<u8> { s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect() };
+    assert_eq!(
+        utf16_strings(&to_utf16("link\0program\0")),
+        Some(vec!["link".to_string(), "program".to_string()])
+    );
+    assert_eq!(
+        utf16_strings(&to_utf16("folder")),
+        Some(vec!["folder".to_string()])
+    );
+    // MD5 digest, not a string
+    let md5 = [
+        0x14, 0x34, 0x98, 0x3B, 0x29, 0x42, 0x88, 0x38, 0x83, 0xD5, 0x0A, 0xBE, 0x47, 0xED, 0x5D,
+        0x19,
+    ];
+    assert_eq!(utf16_strings(&md5), None);
+    assert_eq!(utf16_strings(&[0x28, 0x12, 0x65]), None);
+}
+
+#[test]
+fn variant_time_test() {
+    let dt = get_date_time_from_variant_time(44967.5).unwrap();
+    assert_eq!(format_date_time(dt), "2023-02-10T12:00:00.0000000Z");
+    assert!(get_date_time_from_variant_time(f64::NAN).is_none());
+}
+
 pub fn json_escape(input: &str) -> String {
     json::stringify(input)
 }
@@ -144,6 +341,58 @@ fn json_escape_test() {
     }
 }
 
+/// Size and MD5/SHA-1/SHA-256 of a file, as lowercase hex strings
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileHashes {
+    pub size: u64,
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// Hashes a file, read once for the 3 digests
+pub fn file_hashes(p: &Path) -> std::io::Result<FileHashes> {
+    let mut f = File::open(p)?;
+    let (mut md5, mut sha1, mut sha256) = (Md5::new(), Sha1::new(), Sha256::new());
+    let mut buf = vec![0u8; 1 << 16];
+    let mut size = 0;
+    loop {
+        let n = match f.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        md5.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+        sha256.update(&buf[..n]);
+        size += n as u64;
+    }
+    let hex = |digest: &[u8]| digest.iter().map(|b| format!("{b:02x}")).collect();
+    Ok(FileHashes {
+        size,
+        md5: hex(&md5.finalize()),
+        sha1: hex(&sha1.finalize()),
+        sha256: hex(&sha256.finalize()),
+    })
+}
+
+#[test]
+fn file_hashes_test() {
+    let dir = tempdir::TempDir::new("sidr_hashes").unwrap();
+    let p = dir.path().join("abc");
+    std::fs::write(&p, b"abc").unwrap();
+    assert_eq!(
+        file_hashes(&p).unwrap(),
+        FileHashes {
+            size: 3,
+            md5: "900150983cd24fb0d6963f7d28e17f72".to_string(),
+            sha1: "a9993e364706816aba3e25717850c26c9cd0d89d".to_string(),
+            sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+        }
+    );
+}
+
 pub struct DropMe<F>
 where
     F: Fn(),